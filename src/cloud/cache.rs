@@ -0,0 +1,329 @@
+use crate::cloud::client::CloudClientError;
+use crate::cloud::types::VaultEntry;
+use crate::security::encryption::{decrypt_data, encrypt_data};
+use crate::security::kdf::KdfParams;
+use crate::utils::write_private_file;
+use aes_gcm::{Aes256Gcm, Key};
+use dirs::data_dir;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+#[derive(Serialize, Deserialize)]
+struct CacheFile {
+    base_url: String,
+    #[serde(default)]
+    kdf: Option<KdfParams>,
+    data: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct CachedItem {
+    pub value: String,
+    pub revision: u64,
+    #[serde(default)]
+    pub updated_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum PendingOp {
+    Put { entry: VaultEntry },
+    Delete { id: String, revision: u64 },
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct VaultCache {
+    #[serde(skip)]
+    base_url: String,
+    #[serde(skip)]
+    pub kdf: Option<KdfParams>,
+    #[serde(default)]
+    pub items: BTreeMap<String, CachedItem>,
+    #[serde(default)]
+    pub pending: Vec<PendingOp>,
+    #[serde(default)]
+    pub last_sync: Option<u64>,
+}
+
+impl VaultCache {
+    pub fn empty(base_url: &str) -> Self {
+        Self {
+            base_url: base_url.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn open(base_url: &str, key: &Key<Aes256Gcm>) -> Result<Self, String> {
+        let file = match read_cache_file(base_url)? {
+            Some(file) => file,
+            None => return Ok(Self::empty(base_url)),
+        };
+
+        let plaintext = decrypt_data(key, &file.data).map_err(|_| {
+            "Unable to decrypt the offline vault copy with this master password".to_string()
+        })?;
+        let mut cache: Self = serde_json::from_str(&plaintext)
+            .map_err(|err| format!("Local vault cache is corrupted: {}", err))?;
+        cache.base_url = file.base_url;
+        cache.kdf = file.kdf;
+        Ok(cache)
+    }
+
+    pub fn stored_kdf(base_url: &str) -> Option<KdfParams> {
+        read_cache_file(base_url).ok().flatten()?.kdf
+    }
+
+    pub fn save(&self, key: &Key<Aes256Gcm>) -> Result<(), String> {
+        let plaintext = serde_json::to_string(self)
+            .map_err(|err| format!("Failed to serialize vault cache: {}", err))?;
+        let file = CacheFile {
+            base_url: self.base_url.clone(),
+            kdf: self.kdf.clone(),
+            data: encrypt_data(key, &plaintext),
+        };
+        let data = serde_json::to_vec_pretty(&file)
+            .map_err(|err| format!("Failed to serialize vault cache: {}", err))?;
+
+        let path = cache_path();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|err| format!("Failed to create cache directory: {}", err))?;
+        }
+        write_private_file(&path, &data)
+            .map_err(|err| format!("Failed to write vault cache: {}", err))
+    }
+
+    pub fn exists() -> bool {
+        cache_path().exists()
+    }
+
+    // Keeps an unreadable cache (and any pending changes in it) next to the new
+    // one instead of overwriting it.
+    pub fn set_aside() -> io::Result<PathBuf> {
+        let path = cache_path();
+        let backup = path.with_extension("json.bak");
+        fs::rename(&path, &backup)?;
+        Ok(backup)
+    }
+
+    pub fn remove() -> io::Result<()> {
+        match fs::remove_file(cache_path()) {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+            other => other,
+        }
+    }
+
+    pub fn entry(&self, id: &str) -> Option<VaultEntry> {
+        self.items.get(id).map(|item| VaultEntry {
+            key: id.to_string(),
+            value: item.value.clone(),
+            revision: item.revision,
+        })
+    }
+
+    pub fn queue_put(
+        &mut self,
+        entry: VaultEntry,
+        updated_at: u64,
+    ) -> Result<(), CloudClientError> {
+        let known = self.items.get(&entry.key).map_or(0, |item| item.revision);
+        if entry.revision != known {
+            return Err(CloudClientError::Conflict(format!(
+                "{} was changed while offline",
+                entry.key
+            )));
+        }
+
+        let mut base = known;
+        if let Some(pos) = self.pending_position(&entry.key) {
+            base = match self.pending.remove(pos) {
+                PendingOp::Put { entry } => entry.revision,
+                PendingOp::Delete { revision, .. } => revision,
+            };
+        }
+
+        self.items.insert(
+            entry.key.clone(),
+            CachedItem {
+                value: entry.value.clone(),
+                revision: known,
+                updated_at,
+            },
+        );
+        self.pending.push(PendingOp::Put {
+            entry: VaultEntry {
+                revision: base,
+                ..entry
+            },
+        });
+        Ok(())
+    }
+
+    pub fn queue_delete(&mut self, id: &str, revision: u64) -> Result<(), CloudClientError> {
+        match self.items.get(id) {
+            Some(item) if item.revision == revision => {}
+            Some(_) => {
+                return Err(CloudClientError::Conflict(format!(
+                    "{} was changed while offline",
+                    id
+                )))
+            }
+            None => {
+                return Err(CloudClientError::NotFound(format!(
+                    "Item not found: {}",
+                    id
+                )))
+            }
+        }
+        self.items.remove(id);
+
+        let base = match self
+            .pending_position(id)
+            .map(|pos| self.pending.remove(pos))
+        {
+            Some(PendingOp::Put { entry }) if entry.revision == 0 => return Ok(()),
+            Some(PendingOp::Put { entry }) => entry.revision,
+            _ => revision,
+        };
+        self.pending.push(PendingOp::Delete {
+            id: id.to_string(),
+            revision: base,
+        });
+        Ok(())
+    }
+
+    fn pending_position(&self, id: &str) -> Option<usize> {
+        self.pending.iter().position(|op| op.id() == id)
+    }
+}
+
+impl PendingOp {
+    pub fn id(&self) -> &str {
+        match self {
+            PendingOp::Put { entry } => &entry.key,
+            PendingOp::Delete { id, .. } => id,
+        }
+    }
+}
+
+fn read_cache_file(base_url: &str) -> Result<Option<CacheFile>, String> {
+    let bytes = match fs::read(cache_path()) {
+        Ok(bytes) => bytes,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(format!("Failed to read vault cache: {}", err)),
+    };
+    let file: CacheFile = serde_json::from_slice(&bytes)
+        .map_err(|err| format!("Local vault cache is corrupted: {}", err))?;
+    Ok((file.base_url == base_url).then_some(file))
+}
+
+fn cache_path() -> PathBuf {
+    let mut path = data_dir().unwrap_or_else(|| PathBuf::from("."));
+    path.push("x_cli");
+    path.push("vault_cache.json");
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cache_with(id: &str, revision: u64) -> VaultCache {
+        let mut cache = VaultCache::empty("http://localhost:4000");
+        cache.items.insert(
+            id.to_string(),
+            CachedItem {
+                value: "old".to_string(),
+                revision,
+                updated_at: 1,
+            },
+        );
+        cache
+    }
+
+    fn entry(key: &str, value: &str, revision: u64) -> VaultEntry {
+        VaultEntry {
+            key: key.to_string(),
+            value: value.to_string(),
+            revision,
+        }
+    }
+
+    fn pending_put(cache: &VaultCache) -> (String, u64) {
+        match cache.pending.as_slice() {
+            [PendingOp::Put { entry }] => (entry.value.clone(), entry.revision),
+            _ => panic!("expected a single pending put"),
+        }
+    }
+
+    #[test]
+    fn offline_edits_replay_against_the_last_synced_revision() {
+        let mut cache = cache_with("a", 4);
+        cache.queue_put(entry("a", "first", 4), 2).unwrap();
+        cache.queue_put(entry("a", "second", 4), 3).unwrap();
+
+        assert_eq!(pending_put(&cache), ("second".to_string(), 4));
+        assert_eq!(cache.entry("a").unwrap().value, "second");
+        assert_eq!(cache.entry("a").unwrap().revision, 4);
+    }
+
+    #[test]
+    fn stale_offline_writes_are_conflicts() {
+        let mut cache = cache_with("a", 4);
+        assert!(matches!(
+            cache.queue_put(entry("a", "new", 3), 2),
+            Err(CloudClientError::Conflict(_))
+        ));
+        assert!(matches!(
+            cache.queue_delete("a", 3),
+            Err(CloudClientError::Conflict(_))
+        ));
+        assert!(matches!(
+            cache.queue_delete("missing", 0),
+            Err(CloudClientError::NotFound(_))
+        ));
+        assert!(cache.pending.is_empty());
+    }
+
+    #[test]
+    fn deleting_an_offline_addition_leaves_nothing_to_replay() {
+        let mut cache = VaultCache::empty("http://localhost:4000");
+        cache.queue_put(entry("new", "value", 0), 2).unwrap();
+        cache.queue_delete("new", 0).unwrap();
+
+        assert!(cache.pending.is_empty());
+        assert!(cache.entry("new").is_none());
+    }
+
+    #[test]
+    fn deleting_an_offline_edit_replays_one_delete() {
+        let mut cache = cache_with("a", 4);
+        cache.queue_put(entry("a", "edited", 4), 2).unwrap();
+        cache.queue_delete("a", 4).unwrap();
+
+        match cache.pending.as_slice() {
+            [PendingOp::Delete { id, revision }] => {
+                assert_eq!(id, "a");
+                assert_eq!(*revision, 4);
+            }
+            _ => panic!("expected a single pending delete"),
+        }
+    }
+
+    #[test]
+    fn pending_ops_survive_serialization() {
+        let mut cache = cache_with("a", 4);
+        cache.queue_put(entry("b", "added", 0), 2).unwrap();
+        cache.queue_delete("a", 4).unwrap();
+
+        let json = serde_json::to_string(&cache).unwrap();
+        let restored: VaultCache = serde_json::from_str(&json).unwrap();
+        let ids: Vec<&str> = restored.pending.iter().map(PendingOp::id).collect();
+        assert_eq!(ids, ["b", "a"]);
+        assert!(json.contains(r#""op":"put""#));
+        assert!(json.contains(r#""op":"delete""#));
+    }
+}
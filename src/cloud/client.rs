@@ -1,21 +1,47 @@
-use crate::cloud::types::{CloudInfoResponse, CloudResponse, VaultEntry};
+use crate::cloud::tls::{self, PinnedVerifier};
+use crate::cloud::types::{
+    ChallengeResponse, CloudInfoResponse, CloudResponse, HistoryResponse, ItemMetadata,
+    ItemResponse, ItemsResponse, KdfResponse, LoginResponse, SessionInfo, SessionsResponse,
+    VaultEntry, PASSWORD_CHANGED, SESSION_EXPIRED,
+};
+use crate::config::SessionConfig;
+use crate::security::challenge::compute_proof;
+use crate::security::encryption::{decrypt_data, encrypt_data};
+use crate::security::kdf::{KdfParams, MasterKeys};
+use crate::utils::timestamp_now;
 use aes_gcm::{Aes256Gcm, Key};
-use blake3;
-use hex;
 use reqwest::blocking::{Client, Response};
-use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
+use reqwest::Url;
+use serde::de::DeserializeOwned;
 use serde_json::json;
 use std::fmt;
+use std::sync::Arc;
 use std::time::Duration;
 
-const MASTER_HASH_HEADER: &str = "X-Master-Hash";
 const AUTH_FAILURE: &str = "Authentication with the cloud host failed";
+const CERTIFICATE_CHANGED: &str = "The host certificate does not match the pinned fingerprint. Refusing to connect; run `x cloud <url>` again only if you trust the new certificate";
+const CERTIFICATE_UNTRUSTED: &str = "The host certificate is not signed by a trusted authority and no fingerprint is pinned. Run `x cloud <url>` to review and trust it";
+const OUTDATED_SCHEME: &str =
+    "The host vault uses an outdated key scheme. Restart `x host` to migrate it.";
 
 #[derive(Debug)]
 pub enum CloudClientError {
     Http(reqwest::Error),
     Failure(String),
     AuthenticationFailed,
+    SessionExpired,
+    PasswordChanged,
+    CertificateChanged(String),
+    CertificateUntrusted(String),
+    NotFound(String),
+    Conflict(String),
+}
+
+impl CloudClientError {
+    pub fn is_unreachable(&self) -> bool {
+        matches!(self, CloudClientError::Http(err) if err.is_connect() || err.is_timeout())
+    }
 }
 
 impl From<reqwest::Error> for CloudClientError {
@@ -30,6 +56,16 @@ impl fmt::Display for CloudClientError {
             CloudClientError::Http(err) => write!(f, "Network error: {}", err),
             CloudClientError::Failure(msg) => write!(f, "{}", msg),
             CloudClientError::AuthenticationFailed => write!(f, "{}", AUTH_FAILURE),
+            CloudClientError::SessionExpired => write!(f, "{}", SESSION_EXPIRED),
+            CloudClientError::PasswordChanged => write!(f, "{}", PASSWORD_CHANGED),
+            CloudClientError::CertificateChanged(actual) => {
+                write!(f, "{} (now {})", CERTIFICATE_CHANGED, actual)
+            }
+            CloudClientError::CertificateUntrusted(actual) => {
+                write!(f, "{} (fingerprint {})", CERTIFICATE_UNTRUSTED, actual)
+            }
+            CloudClientError::NotFound(msg) => write!(f, "{}", msg),
+            CloudClientError::Conflict(msg) => write!(f, "{}", msg),
         }
     }
 }
@@ -38,60 +74,375 @@ impl fmt::Display for CloudClientError {
 pub struct CloudApi {
     client: Client,
     base_url: String,
+    verifier: Option<Arc<PinnedVerifier>>,
 }
 
 impl CloudApi {
-    pub fn new(base_url: impl Into<String>) -> Result<Self, CloudClientError> {
-        let client = Client::builder()
-            .timeout(Duration::from_secs(15))
-            .build()
-            .map_err(CloudClientError::Http)?;
+    pub fn new(
+        base_url: impl Into<String>,
+        fingerprint: Option<String>,
+    ) -> Result<Self, CloudClientError> {
+        let base_url = base_url.into();
+        let mut builder = Client::builder().timeout(Duration::from_secs(15));
+        let mut verifier = None;
+        if base_url.starts_with("https://") {
+            let pinned = Arc::new(PinnedVerifier::new(fingerprint));
+            builder = builder.use_preconfigured_tls(tls::client_config(pinned.clone()));
+            verifier = Some(pinned);
+        }
+        let client = builder.build().map_err(CloudClientError::Http)?;
         Ok(Self {
             client,
-            base_url: base_url.into(),
+            base_url,
+            verifier,
         })
     }
 
-    pub fn verify_master(&self, auth_hash: &str) -> Result<(), CloudClientError> {
-        let url = self.url("auth");
-        let headers = auth_headers(auth_hash)?;
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    pub fn kdf_params(&self) -> Result<KdfParams, CloudClientError> {
         let res = self
             .client
-            .post(url)
-            .headers(headers)
-            .header(CONTENT_TYPE, "application/json")
-            .body("{}")
+            .get(self.url("kdf"))
             .send()
-            .map_err(CloudClientError::Http)?;
+            .map_err(|err| self.http_error(err))?;
+        let status = res.status();
+        let body = res.text().map_err(CloudClientError::Http)?;
+        let response: KdfResponse = serde_json::from_str(&body).map_err(|_| {
+            CloudClientError::Failure(format!(
+                "Server returned {} without key derivation parameters. Is the host up to date?",
+                status
+            ))
+        })?;
+        match response.kdf {
+            Some(kdf) if response.success && kdf.is_current() => Ok(kdf),
+            Some(_) if response.success => Err(CloudClientError::Failure(OUTDATED_SCHEME.into())),
+            _ => Err(CloudClientError::Failure(format!(
+                "Server returned {}: {}",
+                status,
+                response
+                    .message
+                    .unwrap_or_else(|| "Missing key derivation parameters".to_string())
+            ))),
+        }
+    }
 
-        if res.status().is_success() {
-            Ok(())
+    pub fn login(&self, auth_hash: &str, label: &str) -> Result<LoginResponse, CloudClientError> {
+        let res = self
+            .client
+            .post(self.url("auth/challenge"))
+            .json(&json!({}))
+            .send()
+            .map_err(|err| self.http_error(err))?;
+        let challenge: ChallengeResponse = Self::parse_json(res)?;
+        let nonce = challenge
+            .nonce
+            .ok_or_else(|| CloudClientError::Failure("Host did not issue a challenge".into()))?;
+        let proof = compute_proof(auth_hash, &nonce).map_err(CloudClientError::Failure)?;
+
+        let res = self
+            .client
+            .post(self.url("auth"))
+            .json(&json!({
+                "nonce": nonce,
+                "proof": proof,
+                "label": label,
+            }))
+            .send()
+            .map_err(|err| self.http_error(err))?;
+        let response: LoginResponse = Self::parse_json(res)?;
+        if response.success && response.token.is_some() {
+            Ok(response)
         } else {
             Err(CloudClientError::AuthenticationFailed)
         }
     }
 
-    pub fn fetch_vault(&self, auth_hash: &str) -> Result<Vec<VaultEntry>, CloudClientError> {
-        let headers = auth_headers(auth_hash)?;
+    pub fn current_session(&self, token: &str) -> Result<SessionInfo, CloudClientError> {
+        let res = self
+            .client
+            .get(self.url("session"))
+            .headers(auth_headers(token)?)
+            .send()
+            .map_err(|err| self.http_error(err))?;
+        let response: SessionsResponse = Self::parse_json(res)?;
+        response
+            .sessions
+            .into_iter()
+            .next()
+            .ok_or(CloudClientError::SessionExpired)
+    }
+
+    pub fn sessions(&self, token: &str) -> Result<Vec<SessionInfo>, CloudClientError> {
+        let res = self
+            .client
+            .get(self.url("sessions"))
+            .headers(auth_headers(token)?)
+            .send()
+            .map_err(|err| self.http_error(err))?;
+        let response: SessionsResponse = Self::parse_json(res)?;
+        Ok(response.sessions)
+    }
+
+    pub fn revoke_session(&self, token: &str, id: &str) -> Result<String, CloudClientError> {
+        let res = self
+            .client
+            .delete(self.url(&format!("sessions/{}", id)))
+            .headers(auth_headers(token)?)
+            .send()
+            .map_err(|err| self.http_error(err))?;
+        let response: SessionsResponse = Self::parse_json(res)?;
+        Ok(response.message.unwrap_or_default())
+    }
+
+    pub fn list_items(&self, token: &str) -> Result<Vec<ItemMetadata>, CloudClientError> {
+        let res = self
+            .client
+            .get(self.url("vault/items"))
+            .headers(auth_headers(token)?)
+            .send()
+            .map_err(|err| self.http_error(err))?;
+        let response: ItemsResponse = Self::parse_json(res)?;
+        Ok(response.items)
+    }
+
+    pub fn get_item(&self, token: &str, id: &str) -> Result<VaultEntry, CloudClientError> {
+        let res = self
+            .client
+            .get(self.item_url(id)?)
+            .headers(auth_headers(token)?)
+            .send()
+            .map_err(|err| self.http_error(err))?;
+        let response: ItemResponse = Self::parse_json(res)?;
+        response
+            .item
+            .ok_or_else(|| CloudClientError::Failure(format!("Item not found: {}", id)))
+    }
+
+    pub fn put_item(
+        &self,
+        token: &str,
+        entry: &VaultEntry,
+    ) -> Result<ItemMetadata, CloudClientError> {
+        let res = self
+            .client
+            .put(self.item_url(&entry.key)?)
+            .headers(auth_headers(token)?)
+            .json(&json!({
+                "value": entry.value,
+                "base_revision": entry.revision,
+            }))
+            .send()
+            .map_err(|err| self.http_error(err))?;
+        let response: ItemsResponse = Self::parse_json(res)?;
+        response
+            .items
+            .into_iter()
+            .next()
+            .ok_or_else(|| CloudClientError::Failure("Host did not confirm the update".into()))
+    }
+
+    pub fn delete_item(
+        &self,
+        token: &str,
+        id: &str,
+        revision: u64,
+        quarantine: bool,
+    ) -> Result<(), CloudClientError> {
+        let mut url = self.item_url(id)?;
+        url.query_pairs_mut()
+            .append_pair("base_revision", &revision.to_string());
+        if quarantine {
+            url.query_pairs_mut().append_pair("quarantine", "true");
+        }
+        let res = self
+            .client
+            .delete(url)
+            .headers(auth_headers(token)?)
+            .send()
+            .map_err(|err| self.http_error(err))?;
+        let _ = Self::parse_response(res)?;
+        Ok(())
+    }
+
+    pub fn move_item(
+        &self,
+        token: &str,
+        id: &str,
+        to: &str,
+        revision: u64,
+    ) -> Result<ItemMetadata, CloudClientError> {
+        let mut url = self.item_url(id)?;
+        url.path_segments_mut()
+            .map_err(|_| CloudClientError::Failure("Invalid host URL".into()))?
+            .push("move");
+        let res = self
+            .client
+            .post(url)
+            .headers(auth_headers(token)?)
+            .json(&json!({
+                "to": to,
+                "base_revision": revision,
+            }))
+            .send()
+            .map_err(|err| self.http_error(err))?;
+        let response: ItemsResponse = Self::parse_json(res)?;
+        response
+            .items
+            .into_iter()
+            .next()
+            .ok_or_else(|| CloudClientError::Failure("Host did not confirm the move".into()))
+    }
+
+    pub fn put_attachment_chunk(
+        &self,
+        token: &str,
+        id: &str,
+        attachment: &str,
+        index: u32,
+        data: Vec<u8>,
+    ) -> Result<(), CloudClientError> {
+        let res = self
+            .client
+            .put(self.attachment_url(id, attachment, Some(index))?)
+            .headers(auth_headers(token)?)
+            .body(data)
+            .send()
+            .map_err(|err| self.http_error(err))?;
+        let _ = Self::parse_response(res)?;
+        Ok(())
+    }
+
+    pub fn get_attachment_chunk(
+        &self,
+        token: &str,
+        id: &str,
+        attachment: &str,
+        index: u32,
+    ) -> Result<Vec<u8>, CloudClientError> {
+        let res = self
+            .client
+            .get(self.attachment_url(id, attachment, Some(index))?)
+            .headers(auth_headers(token)?)
+            .send()
+            .map_err(|err| self.http_error(err))?;
+        if !res.status().is_success() {
+            return Err(Self::error_from(res));
+        }
+        Ok(res.bytes().map_err(CloudClientError::Http)?.to_vec())
+    }
+
+    pub fn delete_attachment(
+        &self,
+        token: &str,
+        id: &str,
+        attachment: &str,
+    ) -> Result<(), CloudClientError> {
+        let res = self
+            .client
+            .delete(self.attachment_url(id, attachment, None)?)
+            .headers(auth_headers(token)?)
+            .send()
+            .map_err(|err| self.http_error(err))?;
+        let _ = Self::parse_response(res)?;
+        Ok(())
+    }
+
+    pub fn list_trash(&self, token: &str) -> Result<Vec<ItemMetadata>, CloudClientError> {
+        let res = self
+            .client
+            .get(self.url("vault/trash"))
+            .headers(auth_headers(token)?)
+            .send()
+            .map_err(|err| self.http_error(err))?;
+        let response: ItemsResponse = Self::parse_json(res)?;
+        Ok(response.items)
+    }
+
+    pub fn get_trashed(&self, token: &str, id: &str) -> Result<VaultEntry, CloudClientError> {
+        let res = self
+            .client
+            .get(self.trash_url(id)?)
+            .headers(auth_headers(token)?)
+            .send()
+            .map_err(|err| self.http_error(err))?;
+        let response: ItemResponse = Self::parse_json(res)?;
+        response
+            .item
+            .ok_or_else(|| CloudClientError::Failure(format!("Item not found: {}", id)))
+    }
+
+    pub fn restore_trashed(
+        &self,
+        token: &str,
+        id: &str,
+        revision: u64,
+    ) -> Result<ItemMetadata, CloudClientError> {
+        let mut url = self.trash_url(id)?;
+        url.path_segments_mut()
+            .map_err(|_| CloudClientError::Failure("Invalid host URL".into()))?
+            .push("restore");
+        url.query_pairs_mut()
+            .append_pair("base_revision", &revision.to_string());
+        let res = self
+            .client
+            .post(url)
+            .headers(auth_headers(token)?)
+            .send()
+            .map_err(|err| self.http_error(err))?;
+        let response: ItemsResponse = Self::parse_json(res)?;
+        response
+            .items
+            .into_iter()
+            .next()
+            .ok_or_else(|| CloudClientError::Failure("Host did not confirm the restore".into()))
+    }
+
+    pub fn purge_trashed(
+        &self,
+        token: &str,
+        id: &str,
+        revision: u64,
+    ) -> Result<(), CloudClientError> {
+        let mut url = self.trash_url(id)?;
+        url.query_pairs_mut()
+            .append_pair("base_revision", &revision.to_string());
+        let res = self
+            .client
+            .delete(url)
+            .headers(auth_headers(token)?)
+            .send()
+            .map_err(|err| self.http_error(err))?;
+        let _ = Self::parse_response(res)?;
+        Ok(())
+    }
+
+    pub fn empty_trash(&self, token: &str) -> Result<String, CloudClientError> {
         let res = self
             .client
-            .get(self.url("vault"))
-            .headers(headers)
+            .delete(self.url("vault/trash"))
+            .headers(auth_headers(token)?)
             .send()
-            .map_err(CloudClientError::Http)?;
+            .map_err(|err| self.http_error(err))?;
         let response = Self::parse_response(res)?;
-        Ok(response.vault.unwrap_or_default())
+        Ok(response.message)
     }
 
-    pub fn save_vault(
+    pub fn change_master(
         &self,
-        auth_hash: &str,
+        token: &str,
+        new_auth_hash: &str,
+        kdf: &KdfParams,
         vault: &[VaultEntry],
     ) -> Result<(), CloudClientError> {
         let payload = json!({
+            "auth_hash": new_auth_hash,
+            "kdf": kdf,
             "vault": vault,
         });
-        let response = self.post_with_auth(self.url("vault"), auth_hash, payload)?;
+        let response = self.post_with_auth(self.url("passwd"), token, payload)?;
         if response.success {
             Ok(())
         } else {
@@ -99,28 +450,46 @@ impl CloudApi {
         }
     }
 
-    pub fn info(&self, auth_hash: &str) -> Result<CloudInfoResponse, CloudClientError> {
-        let headers = auth_headers(auth_hash)?;
+    pub fn history(&self, token: &str) -> Result<HistoryResponse, CloudClientError> {
         let res = self
             .client
-            .get(self.url("info"))
-            .headers(headers)
+            .get(self.url("vault/history"))
+            .headers(auth_headers(token)?)
             .send()
-            .map_err(CloudClientError::Http)?;
+            .map_err(|err| self.http_error(err))?;
+        Self::parse_json(res)
+    }
 
-        if res.status().is_success() {
-            res.json().map_err(CloudClientError::Http)
-        } else if res.status().as_u16() == 401 {
-            Err(CloudClientError::AuthenticationFailed)
+    pub fn restore(&self, token: &str, revision: u64) -> Result<String, CloudClientError> {
+        let payload = json!({ "revision": revision });
+        let response = self.post_with_auth(self.url("vault/restore"), token, payload)?;
+        if response.success {
+            Ok(response.message)
         } else {
-            let status = res.status();
-            let message = res
-                .text()
-                .unwrap_or_else(|_| "Unable to read response body".to_string());
-            Err(CloudClientError::Failure(format!(
-                "Server returned {}: {}",
-                status, message
-            )))
+            Err(CloudClientError::Failure(response.message))
+        }
+    }
+
+    pub fn info(&self, token: &str) -> Result<CloudInfoResponse, CloudClientError> {
+        let res = self
+            .client
+            .get(self.url("info"))
+            .headers(auth_headers(token)?)
+            .send()
+            .map_err(|err| self.http_error(err))?;
+        Self::parse_json(res)
+    }
+
+    fn http_error(&self, err: reqwest::Error) -> CloudClientError {
+        let Some(verifier) = self.verifier.as_ref() else {
+            return CloudClientError::Http(err);
+        };
+        if let Some(actual) = verifier.mismatch() {
+            return CloudClientError::CertificateChanged(actual);
+        }
+        match verifier.untrusted() {
+            Some(actual) => CloudClientError::CertificateUntrusted(actual),
+            None => CloudClientError::Http(err),
         }
     }
 
@@ -128,99 +497,316 @@ impl CloudApi {
         format!("{}/{}", self.base_url.trim_end_matches('/'), path)
     }
 
+    fn item_url(&self, id: &str) -> Result<Url, CloudClientError> {
+        self.entry_url("vault/items", id)
+    }
+
+    fn trash_url(&self, id: &str) -> Result<Url, CloudClientError> {
+        self.entry_url("vault/trash", id)
+    }
+
+    fn attachment_url(
+        &self,
+        id: &str,
+        attachment: &str,
+        index: Option<u32>,
+    ) -> Result<Url, CloudClientError> {
+        let mut url = self.item_url(id)?;
+        {
+            let mut segments = url
+                .path_segments_mut()
+                .map_err(|_| CloudClientError::Failure("Invalid host URL".into()))?;
+            segments.push("attachments").push(attachment);
+            if let Some(index) = index {
+                segments.push(&index.to_string());
+            }
+        }
+        Ok(url)
+    }
+
+    fn entry_url(&self, collection: &str, id: &str) -> Result<Url, CloudClientError> {
+        let mut url = Url::parse(&self.url(collection))
+            .map_err(|err| CloudClientError::Failure(format!("Invalid host URL: {}", err)))?;
+        url.path_segments_mut()
+            .map_err(|_| CloudClientError::Failure("Invalid host URL".into()))?
+            .push(id);
+        Ok(url)
+    }
+
     fn post_with_auth<T: serde::Serialize>(
         &self,
         url: String,
-        auth_hash: &str,
+        token: &str,
         payload: T,
     ) -> Result<CloudResponse, CloudClientError> {
-        let headers = auth_headers(auth_hash)?;
         let res = self
             .client
             .post(url)
-            .headers(headers)
+            .headers(auth_headers(token)?)
             .json(&payload)
             .send()
-            .map_err(CloudClientError::Http)?;
+            .map_err(|err| self.http_error(err))?;
         Self::parse_response(res)
     }
 
     fn parse_response(res: Response) -> Result<CloudResponse, CloudClientError> {
+        Self::parse_json(res)
+    }
+
+    fn parse_json<T: DeserializeOwned>(res: Response) -> Result<T, CloudClientError> {
         if res.status().is_success() {
-            res.json().map_err(CloudClientError::Http)
-        } else if res.status().as_u16() == 401 {
-            Err(CloudClientError::AuthenticationFailed)
-        } else {
-            let status = res.status();
-            let message = res
-                .text()
-                .unwrap_or_else(|_| "Unable to read response body".to_string());
-            Err(CloudClientError::Failure(format!(
-                "Server returned {}: {}",
-                status, message
-            )))
+            return res.json().map_err(CloudClientError::Http);
+        }
+        Err(Self::error_from(res))
+    }
+
+    fn error_from(res: Response) -> CloudClientError {
+        let status = res.status();
+        if status.as_u16() == 401 {
+            return auth_error(res);
+        }
+
+        let body = res
+            .text()
+            .unwrap_or_else(|_| "Unable to read response body".to_string());
+        let message = serde_json::from_str::<serde_json::Value>(&body)
+            .ok()
+            .and_then(|value| value.get("message")?.as_str().map(str::to_string))
+            .unwrap_or(body);
+        match status.as_u16() {
+            404 => CloudClientError::NotFound(message),
+            409 => CloudClientError::Conflict(message),
+            413 => CloudClientError::Failure(message),
+            _ => CloudClientError::Failure(format!("Server returned {}: {}", status, message)),
         }
     }
 }
 
-fn auth_headers(auth_hash: &str) -> Result<HeaderMap, CloudClientError> {
+fn auth_error(res: Response) -> CloudClientError {
+    let message = res
+        .json::<serde_json::Value>()
+        .ok()
+        .and_then(|body| body.get("message")?.as_str().map(str::to_string));
+    match message.as_deref() {
+        Some(PASSWORD_CHANGED) => CloudClientError::PasswordChanged,
+        Some(SESSION_EXPIRED) => CloudClientError::SessionExpired,
+        _ => CloudClientError::AuthenticationFailed,
+    }
+}
+
+fn auth_headers(token: &str) -> Result<HeaderMap, CloudClientError> {
     let mut headers = HeaderMap::new();
-    let value = HeaderValue::from_str(auth_hash)
-        .map_err(|_| CloudClientError::Failure("Invalid master password hash".into()))?;
-    headers.insert(MASTER_HASH_HEADER, value);
+    let value = HeaderValue::from_str(&format!("Bearer {}", token))
+        .map_err(|_| CloudClientError::Failure("Invalid session token".into()))?;
+    headers.insert(AUTHORIZATION, value);
     Ok(headers)
 }
 
+fn parse_key(value: &str) -> Option<Key<Aes256Gcm>> {
+    let mut key = [0u8; 32];
+    hex::decode_to_slice(value, &mut key).ok()?;
+    Some(*Key::<Aes256Gcm>::from_slice(&key))
+}
+
+fn client_label() -> String {
+    std::env::var("COMPUTERNAME")
+        .or_else(|_| std::env::var("HOSTNAME"))
+        .unwrap_or_else(|_| format!("x on {}", std::env::consts::OS))
+}
+
 pub struct RemoteSession {
     api: CloudApi,
-    auth_hash: String,
+    token: String,
+    expires_at: u64,
     encryption_key: Key<Aes256Gcm>,
+    key_wrap: Key<Aes256Gcm>,
 }
 
 impl RemoteSession {
-    pub fn new(base_url: String, master_password: String) -> Result<Self, CloudClientError> {
-        let hash = blake3::hash(master_password.as_bytes());
-        Self::from_hash_bytes(base_url, hash.to_hex().to_string(), hash.as_bytes())
+    pub fn new(api: CloudApi, master_password: String) -> Result<Self, CloudClientError> {
+        let keys = api
+            .kdf_params()?
+            .derive_keys(&master_password)
+            .map_err(CloudClientError::Failure)?;
+        Self::login(api, &keys)
     }
 
-    pub fn from_hash(base_url: String, auth_hash: String) -> Result<Self, CloudClientError> {
-        let mut bytes = [0u8; 32];
-        hex::decode_to_slice(&auth_hash, &mut bytes).map_err(|_| {
-            CloudClientError::Failure("Stored master password hash is invalid".into())
-        })?;
-        Self::from_hash_bytes(base_url, auth_hash, &bytes)
-    }
-
-    fn from_hash_bytes(
-        base_url: String,
-        auth_hash: String,
-        hash_bytes: &[u8],
-    ) -> Result<Self, CloudClientError> {
-        let api = CloudApi::new(base_url)?;
-        api.verify_master(&auth_hash)?;
+    pub fn resume(api: CloudApi, stored: &SessionConfig) -> Result<Self, CloudClientError> {
+        if stored.expires_at <= timestamp_now() {
+            return Err(CloudClientError::SessionExpired);
+        }
 
-        let encryption_key = Key::<Aes256Gcm>::from_slice(hash_bytes).clone();
+        let session = api.current_session(&stored.token)?;
+        let key_wrap = parse_key(&session.key_wrap).ok_or(CloudClientError::SessionExpired)?;
+        let encryption_key = decrypt_data(&key_wrap, &stored.wrapped_key)
+            .ok()
+            .and_then(|key| parse_key(&key))
+            .ok_or(CloudClientError::SessionExpired)?;
 
         Ok(Self {
             api,
-            auth_hash,
+            token: stored.token.clone(),
+            expires_at: stored.expires_at,
             encryption_key,
+            key_wrap,
         })
     }
 
-    pub fn auth_hash(&self) -> &str {
-        &self.auth_hash
+    fn login(api: CloudApi, keys: &MasterKeys) -> Result<Self, CloudClientError> {
+        let response = api.login(&keys.auth_hash, &client_label())?;
+        let key_wrap = response
+            .key_wrap
+            .as_deref()
+            .and_then(parse_key)
+            .ok_or_else(|| {
+                CloudClientError::Failure(
+                    "The host did not issue a session key. Is the host up to date?".into(),
+                )
+            })?;
+
+        Ok(Self {
+            api,
+            token: response.token.unwrap_or_default(),
+            expires_at: response.expires_at,
+            encryption_key: keys.encryption_key,
+            key_wrap,
+        })
+    }
+
+    // The vault key is only kept on disk wrapped under a secret the host hands
+    // out while this session is valid, so a copied config is useless once the
+    // session expires or is revoked.
+    pub fn credentials(&self) -> SessionConfig {
+        SessionConfig {
+            token: self.token.clone(),
+            expires_at: self.expires_at,
+            wrapped_key: encrypt_data(&self.key_wrap, &hex::encode(self.encryption_key)),
+        }
+    }
+
+    pub fn base_url(&self) -> &str {
+        self.api.base_url()
+    }
+
+    pub fn kdf_params(&self) -> Result<KdfParams, CloudClientError> {
+        self.api.kdf_params()
     }
 
     pub fn encryption_key(&self) -> &Key<Aes256Gcm> {
         &self.encryption_key
     }
 
-    pub fn fetch_vault(&self) -> Result<Vec<VaultEntry>, CloudClientError> {
-        self.api.fetch_vault(&self.auth_hash)
+    pub fn list_items(&self) -> Result<Vec<ItemMetadata>, CloudClientError> {
+        self.api.list_items(&self.token)
+    }
+
+    pub fn fetch_item(&self, id: &str) -> Result<VaultEntry, CloudClientError> {
+        self.api.get_item(&self.token, id)
+    }
+
+    pub fn store_item(&self, entry: &VaultEntry) -> Result<ItemMetadata, CloudClientError> {
+        self.api.put_item(&self.token, entry)
+    }
+
+    pub fn delete_item(&self, id: &str, revision: u64) -> Result<(), CloudClientError> {
+        self.api.delete_item(&self.token, id, revision, false)
+    }
+
+    pub fn quarantine_item(&self, id: &str, revision: u64) -> Result<(), CloudClientError> {
+        self.api.delete_item(&self.token, id, revision, true)
+    }
+
+    pub fn move_item(
+        &self,
+        id: &str,
+        to: &str,
+        revision: u64,
+    ) -> Result<ItemMetadata, CloudClientError> {
+        self.api.move_item(&self.token, id, to, revision)
+    }
+
+    pub fn store_attachment_chunk(
+        &self,
+        id: &str,
+        attachment: &str,
+        index: u32,
+        data: Vec<u8>,
+    ) -> Result<(), CloudClientError> {
+        self.api
+            .put_attachment_chunk(&self.token, id, attachment, index, data)
+    }
+
+    pub fn fetch_attachment_chunk(
+        &self,
+        id: &str,
+        attachment: &str,
+        index: u32,
+    ) -> Result<Vec<u8>, CloudClientError> {
+        self.api
+            .get_attachment_chunk(&self.token, id, attachment, index)
+    }
+
+    pub fn delete_attachment(&self, id: &str, attachment: &str) -> Result<(), CloudClientError> {
+        self.api.delete_attachment(&self.token, id, attachment)
+    }
+
+    pub fn list_trash(&self) -> Result<Vec<ItemMetadata>, CloudClientError> {
+        self.api.list_trash(&self.token)
+    }
+
+    pub fn fetch_trashed(&self, id: &str) -> Result<VaultEntry, CloudClientError> {
+        self.api.get_trashed(&self.token, id)
+    }
+
+    pub fn restore_trashed(
+        &self,
+        id: &str,
+        revision: u64,
+    ) -> Result<ItemMetadata, CloudClientError> {
+        self.api.restore_trashed(&self.token, id, revision)
+    }
+
+    pub fn purge_trashed(&self, id: &str, revision: u64) -> Result<(), CloudClientError> {
+        self.api.purge_trashed(&self.token, id, revision)
+    }
+
+    pub fn empty_trash(&self) -> Result<String, CloudClientError> {
+        self.api.empty_trash(&self.token)
+    }
+
+    pub fn info(&self) -> Result<CloudInfoResponse, CloudClientError> {
+        self.api.info(&self.token)
     }
 
-    pub fn save_vault(&self, vault: &[VaultEntry]) -> Result<(), CloudClientError> {
-        self.api.save_vault(&self.auth_hash, vault)
+    pub fn history(&self) -> Result<HistoryResponse, CloudClientError> {
+        self.api.history(&self.token)
+    }
+
+    pub fn restore(&self, revision: u64) -> Result<String, CloudClientError> {
+        self.api.restore(&self.token, revision)
+    }
+
+    pub fn sessions(&self) -> Result<Vec<SessionInfo>, CloudClientError> {
+        self.api.sessions(&self.token)
+    }
+
+    pub fn revoke_session(&self, id: &str) -> Result<String, CloudClientError> {
+        self.api.revoke_session(&self.token, id)
+    }
+
+    pub fn logout(&self) -> Result<(), CloudClientError> {
+        let current = self.api.current_session(&self.token)?;
+        self.api.revoke_session(&self.token, &current.id)?;
+        Ok(())
+    }
+
+    pub fn change_master(
+        &self,
+        kdf: &KdfParams,
+        keys: &MasterKeys,
+        vault: &[VaultEntry],
+    ) -> Result<(), CloudClientError> {
+        self.api
+            .change_master(&self.token, &keys.auth_hash, kdf, vault)
     }
 }
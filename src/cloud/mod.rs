@@ -1,7 +1,11 @@
+pub mod cache;
 pub mod client;
 pub mod server;
+pub mod sync;
+pub mod tls;
 pub mod types;
 
 pub use client::{CloudApi, CloudClientError, RemoteSession};
 pub use server::{run_server, ServerConfig, ServerError};
+pub use sync::VaultSession;
 pub use types::VaultEntry;
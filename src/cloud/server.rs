@@ -1,18 +1,33 @@
-use crate::cloud::types::{CloudInfoResponse, CloudResponse, VaultEntry};
+use crate::cloud::tls::HostCertificate;
+use crate::cloud::types::{
+    ChallengeResponse, ChangeKind, CloudInfoResponse, CloudResponse, HistoryChange,
+    HistoryResponse, ItemMetadata, ItemResponse, ItemsResponse, KdfResponse, LoginResponse,
+    SessionInfo, SessionsResponse, VaultEntry, ATTACHMENT_CHUNK_SIZE, INDEX_ID, PASSWORD_CHANGED,
+    SESSION_EXPIRED,
+};
+use crate::security::challenge::{random_hex, verify_proof};
+use crate::security::kdf::{KdfParams, DEFAULT_ITERATIONS, DEFAULT_MEMORY_KIB};
+use crate::utils::timestamp_now;
 use axum::{
-    extract::State,
-    http::{HeaderMap, StatusCode},
-    routing::{get, post},
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{header::AUTHORIZATION, HeaderMap, StatusCode},
+    routing::{delete, get, post},
     Json, Router,
 };
+use axum_server::Handle;
 use dirs;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use serde_json;
-use sled::{self, Tree};
+use sled::transaction::ConflictableTransactionResult;
+use sled::{self, Transactional, Tree};
+use std::collections::{BTreeMap, HashMap};
 use std::fmt;
 use std::net::SocketAddr;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::net::TcpListener;
 use tokio::sync::Mutex;
 
@@ -22,6 +37,18 @@ pub struct ServerConfig {
     pub port: u16,
     pub data_path: Option<PathBuf>,
     pub master_hash: Option<String>,
+    pub kdf_params: Option<KdfParams>,
+    pub kdf_memory_kib: u32,
+    pub kdf_iterations: u32,
+    pub session_ttl_secs: u64,
+    pub revoke_sessions: bool,
+    pub history_limit: usize,
+    pub trash_retention_days: u64,
+    pub attachment_max_bytes: u64,
+    pub attachment_quota_bytes: u64,
+    pub tls: bool,
+    pub tls_cert: Option<PathBuf>,
+    pub tls_key: Option<PathBuf>,
 }
 
 impl Default for ServerConfig {
@@ -31,6 +58,18 @@ impl Default for ServerConfig {
             port: 4000,
             data_path: None,
             master_hash: None,
+            kdf_params: None,
+            kdf_memory_kib: DEFAULT_MEMORY_KIB,
+            kdf_iterations: DEFAULT_ITERATIONS,
+            session_ttl_secs: DEFAULT_SESSION_TTL_SECS,
+            revoke_sessions: false,
+            history_limit: DEFAULT_HISTORY_LIMIT,
+            trash_retention_days: DEFAULT_TRASH_RETENTION_DAYS,
+            attachment_max_bytes: DEFAULT_ATTACHMENT_MAX_MIB * MIB,
+            attachment_quota_bytes: DEFAULT_ATTACHMENT_QUOTA_MIB * MIB,
+            tls: false,
+            tls_cert: None,
+            tls_key: None,
         }
     }
 }
@@ -47,6 +86,35 @@ impl ServerConfig {
         base
     }
 
+    pub fn tls_paths(&self) -> (PathBuf, PathBuf) {
+        let storage = self.storage_path();
+        (
+            self.tls_cert
+                .clone()
+                .unwrap_or_else(|| storage.with_extension("crt")),
+            self.tls_key
+                .clone()
+                .unwrap_or_else(|| storage.with_extension("key")),
+        )
+    }
+
+    fn load_certificate(&self) -> Result<HostCertificate, ServerError> {
+        let (cert_path, key_path) = self.tls_paths();
+        if self.tls_cert.is_some() || self.tls_key.is_some() {
+            return HostCertificate::load(&cert_path, &key_path).map_err(ServerError::Tls);
+        }
+
+        let mut names = vec!["localhost".to_string(), "127.0.0.1".to_string()];
+        if let Ok(host) = std::env::var("HOSTNAME") {
+            names.push(host);
+        }
+        if !matches!(self.bind_address.as_str(), "0.0.0.0" | "::") {
+            names.push(self.bind_address.clone());
+        }
+        names.dedup();
+        HostCertificate::load_or_generate(&cert_path, &key_path, names).map_err(ServerError::Tls)
+    }
+
     fn socket_addr(&self) -> Result<SocketAddr, ServerError> {
         let addr = format!("{}:{}", self.bind_address, self.port);
         addr.parse::<SocketAddr>()
@@ -63,7 +131,16 @@ pub enum ServerError {
     Runtime(std::io::Error),
     Address(String),
     Unauthorized,
+    SessionExpired,
+    PasswordChanged,
     MissingMasterPassword,
+    Migration(String),
+    Tls(String),
+    NotFound(String),
+    Conflict(String, u64),
+    HistoryUnavailable(u64, u64),
+    AttachmentNotFound(String),
+    TooLarge(String),
 }
 
 impl fmt::Display for ServerError {
@@ -76,9 +153,27 @@ impl fmt::Display for ServerError {
             ServerError::Runtime(err) => write!(f, "Runtime error: {}", err),
             ServerError::Address(err) => write!(f, "Invalid address: {}", err),
             ServerError::Unauthorized => write!(f, "Unauthorized"),
+            ServerError::SessionExpired => write!(f, "{}", SESSION_EXPIRED),
+            ServerError::PasswordChanged => write!(f, "{}", PASSWORD_CHANGED),
             ServerError::MissingMasterPassword => {
                 write!(f, "Master password is required to start the server")
             }
+            ServerError::Migration(err) => write!(f, "Vault migration failed: {}", err),
+            ServerError::Tls(err) => write!(f, "TLS error: {}", err),
+            ServerError::NotFound(id) => write!(f, "Item not found: {}", id),
+            ServerError::Conflict(id, 0) => write!(f, "{} was deleted by another client", id),
+            ServerError::Conflict(id, revision) => write!(
+                f,
+                "{} was changed by another client (now at revision {})",
+                id, revision
+            ),
+            ServerError::HistoryUnavailable(revision, oldest) => write!(
+                f,
+                "Revision {} is no longer kept in the history (oldest restorable revision is {})",
+                revision, oldest
+            ),
+            ServerError::AttachmentNotFound(id) => write!(f, "Attachment not found: {}", id),
+            ServerError::TooLarge(msg) => write!(f, "{}", msg),
         }
     }
 }
@@ -104,29 +199,201 @@ impl From<sled::Error> for ServerError {
 }
 
 pub const MASTER_HASH_KEY: &str = "master_hash";
-const MASTER_HASH_HEADER: &str = "x-master-hash";
-const VAULT_KEY: &str = "vault";
+pub const KDF_PARAMS_KEY: &str = "kdf_params";
+pub const VAULT_KEY: &str = "vault";
+pub const ITEMS_TREE: &str = "items";
+pub const HISTORY_TREE: &str = "history";
+pub const ATTACHMENTS_TREE: &str = "attachments";
+const REVISION_KEY: &str = "revision";
+const HISTORY_FLOOR_KEY: &str = "history_floor";
+const FIRST_REVISION: u64 = 1;
+pub const DEFAULT_SESSION_TTL_SECS: u64 = 12 * 60 * 60;
+pub const DEFAULT_HISTORY_LIMIT: usize = 200;
+pub const DEFAULT_TRASH_RETENTION_DAYS: u64 = 30;
+pub const DEFAULT_ATTACHMENT_MAX_MIB: u64 = 25;
+pub const DEFAULT_ATTACHMENT_QUOTA_MIB: u64 = 1024;
+pub const MIB: u64 = 1024 * 1024;
+const ATTACHMENT_CHUNK_OVERHEAD: usize = 1024;
+const TRASH_PURGE_INTERVAL_SECS: u64 = 60 * 60;
+const CHALLENGE_TTL_SECS: u64 = 60;
+const MAX_PENDING_CHALLENGES: usize = 1024;
+const REVOKED_PASSWORD_CHANGED: &str = "password changed";
+const REVOKED_BY_USER: &str = "revoked by user";
+const REVOKED_BY_HOST: &str = "revoked by host";
+const SESSION_ID_LEN: usize = 12;
 const AUTH_FAILURE: &str = "Authentication failed";
 
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ItemRecord {
+    pub value: String,
+    pub updated_at: u64,
+    #[serde(default = "first_revision")]
+    pub revision: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trashed_at: Option<u64>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub quarantined: bool,
+}
+
+impl ItemRecord {
+    pub fn new(value: String, revision: u64) -> Self {
+        Self {
+            value,
+            updated_at: timestamp_now(),
+            revision,
+            trashed_at: None,
+            quarantined: false,
+        }
+    }
+
+    pub fn metadata(&self, id: String) -> ItemMetadata {
+        ItemMetadata {
+            id,
+            updated_at: self.updated_at,
+            size: self.value.len(),
+            revision: self.revision,
+            trashed_at: self.trashed_at,
+            quarantined: self.quarantined,
+        }
+    }
+
+    fn is_trashed(&self) -> bool {
+        self.trashed_at.is_some()
+    }
+}
+
+fn first_revision() -> u64 {
+    FIRST_REVISION
+}
+
+#[derive(Serialize, Deserialize)]
+struct HistoryRecord {
+    id: String,
+    timestamp: u64,
+    previous: Option<ItemRecord>,
+    deleted: bool,
+    #[serde(default)]
+    trashed: bool,
+}
+
+impl HistoryRecord {
+    fn change(&self, revision: u64) -> HistoryChange {
+        let was_trashed = self.previous.as_ref().map(ItemRecord::is_trashed);
+        let kind = match (was_trashed, self.deleted, self.trashed) {
+            (_, true, _) => ChangeKind::Deleted,
+            (None, false, _) => ChangeKind::Added,
+            (Some(false), false, true) => ChangeKind::Trashed,
+            (Some(true), false, false) => ChangeKind::Restored,
+            (Some(_), false, _) => ChangeKind::Updated,
+        };
+        HistoryChange {
+            revision,
+            id: self.id.clone(),
+            timestamp: self.timestamp,
+            kind,
+        }
+    }
+}
+
+pub fn upgrade_vault_layout(db: &sled::Db) -> Result<usize, ServerError> {
+    let vault = db.open_tree("vault")?;
+    let items = db.open_tree(ITEMS_TREE)?;
+    let Some(bytes) = vault.get(VAULT_KEY)? else {
+        return Ok(0);
+    };
+
+    let entries: Vec<VaultEntry> = serde_json::from_slice(&bytes)?;
+    let mut encoded = Vec::with_capacity(entries.len());
+    for entry in &entries {
+        encoded.push((
+            entry.key.clone(),
+            serde_json::to_vec(&ItemRecord::new(entry.value.clone(), FIRST_REVISION))?,
+        ));
+    }
+    (&vault, &items)
+        .transaction(
+            |(vault, items)| -> ConflictableTransactionResult<(), sled::Error> {
+                for (key, bytes) in &encoded {
+                    items.insert(key.as_bytes(), bytes.as_slice())?;
+                }
+                vault.remove(VAULT_KEY)?;
+                Ok(())
+            },
+        )
+        .map_err(|err| ServerError::Migration(err.to_string()))?;
+    db.flush()?;
+    Ok(entries.len())
+}
+
+pub fn discard_history(db: &sled::Db) -> Result<(), ServerError> {
+    let meta = db.open_tree("meta")?;
+    let revision = meta
+        .get(REVISION_KEY)?
+        .and_then(|bytes| bytes.as_ref().try_into().ok())
+        .map(u64::from_be_bytes)
+        .unwrap_or(FIRST_REVISION);
+    db.open_tree(HISTORY_TREE)?.clear()?;
+    meta.insert(HISTORY_FLOOR_KEY, &revision.to_be_bytes())?;
+    db.flush()?;
+    Ok(())
+}
+
 struct CloudStore {
     meta: Tree,
-    vault: Tree,
+    items: Tree,
+    sessions: Tree,
+    history: Tree,
+    challenges: Mutex<HashMap<String, u64>>,
+    session_ttl_secs: u64,
+    history_limit: usize,
+    trash_retention_secs: u64,
+    attachments: Tree,
+    attachment_bytes: AtomicU64,
+    attachment_max_bytes: u64,
+    attachment_quota_bytes: u64,
     lock: Mutex<()>,
 }
 
 impl CloudStore {
-    async fn new(path: PathBuf) -> Result<Self, ServerError> {
+    async fn new(config: &ServerConfig) -> Result<Self, ServerError> {
+        let path = config.storage_path();
         if let Some(parent) = path.parent() {
             tokio::fs::create_dir_all(parent).await?;
         }
         let db = sled::open(path)?;
+        let upgraded = upgrade_vault_layout(&db)?;
+        if upgraded > 0 {
+            println!("Moved {} entries to per-item storage.", upgraded);
+        }
         let meta = db.open_tree("meta")?;
-        let vault = db.open_tree("vault")?;
-        Ok(Self {
+        let items = db.open_tree(ITEMS_TREE)?;
+        let sessions = db.open_tree("sessions")?;
+        let history = db.open_tree(HISTORY_TREE)?;
+        let attachments = db.open_tree(ATTACHMENTS_TREE)?;
+        let mut attachment_bytes = 0;
+        for entry in attachments.iter() {
+            attachment_bytes += entry?.1.len() as u64;
+        }
+        let store = Self {
             meta,
-            vault,
+            items,
+            sessions,
+            history,
+            challenges: Mutex::new(HashMap::new()),
+            session_ttl_secs: config.session_ttl_secs,
+            history_limit: config.history_limit,
+            trash_retention_secs: config.trash_retention_days * 24 * 60 * 60,
+            attachments,
+            attachment_bytes: AtomicU64::new(attachment_bytes),
+            attachment_max_bytes: config.attachment_max_bytes,
+            attachment_quota_bytes: config.attachment_quota_bytes,
             lock: Mutex::new(()),
-        })
+        };
+        if store.meta.get(HISTORY_FLOOR_KEY)?.is_none() {
+            store.set_history_floor(store.current_revision()?)?;
+        }
+        store.prune_history()?;
+        Ok(store)
     }
 
     fn master_hash(&self) -> Result<Option<String>, ServerError> {
@@ -150,36 +417,696 @@ impl CloudStore {
         }
     }
 
-    fn verify_master(&self, provided: &str) -> Result<(), ServerError> {
-        match self.master_hash()? {
-            Some(existing) if existing == provided => Ok(()),
-            _ => Err(ServerError::Unauthorized),
+    fn kdf_params(&self) -> Result<Option<KdfParams>, ServerError> {
+        match self.meta.get(KDF_PARAMS_KEY)? {
+            Some(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn ensure_kdf_params(&self, provided: &KdfParams) -> Result<(), ServerError> {
+        match self.kdf_params()? {
+            Some(existing) if &existing == provided => Ok(()),
+            Some(_) => Err(ServerError::Unauthorized),
+            None => {
+                self.meta
+                    .insert(KDF_PARAMS_KEY, serde_json::to_vec(provided)?)?;
+                self.meta.flush()?;
+                Ok(())
+            }
+        }
+    }
+
+    async fn issue_challenge(&self) -> String {
+        let now = timestamp_now();
+        let nonce = random_hex(32);
+        let mut challenges = self.challenges.lock().await;
+        challenges.retain(|_, expires_at| *expires_at > now);
+        while challenges.len() >= MAX_PENDING_CHALLENGES {
+            let Some(oldest) = challenges
+                .iter()
+                .min_by_key(|(_, expires_at)| **expires_at)
+                .map(|(nonce, _)| nonce.clone())
+            else {
+                break;
+            };
+            challenges.remove(&oldest);
+        }
+        challenges.insert(nonce.clone(), now + CHALLENGE_TTL_SECS);
+        nonce
+    }
+
+    async fn login(&self, request: &LoginRequest) -> Result<(String, SessionInfo), ServerError> {
+        let now = timestamp_now();
+        let challenge_expiry = self
+            .challenges
+            .lock()
+            .await
+            .remove(&request.nonce)
+            .ok_or(ServerError::Unauthorized)?;
+        if challenge_expiry <= now {
+            return Err(ServerError::Unauthorized);
+        }
+
+        let master_hash = self.master_hash()?.ok_or(ServerError::Unauthorized)?;
+        if !verify_proof(&master_hash, &request.nonce, &request.proof) {
+            return Err(ServerError::Unauthorized);
+        }
+
+        self.purge_expired_sessions()?;
+        let token = random_hex(32);
+        let digest = token_digest(&token);
+        let session = SessionInfo {
+            id: digest[..SESSION_ID_LEN].to_string(),
+            label: request.label.clone(),
+            created_at: now,
+            expires_at: now + self.session_ttl_secs,
+            revoked: None,
+            current: false,
+            key_wrap: random_hex(32),
+        };
+        self.sessions
+            .insert(digest.as_bytes(), serde_json::to_vec(&session)?)?;
+        self.sessions.flush()?;
+        Ok((token, session))
+    }
+
+    fn verify_session(&self, token: &str) -> Result<SessionInfo, ServerError> {
+        let bytes = self
+            .sessions
+            .get(token_digest(token).as_bytes())?
+            .ok_or(ServerError::SessionExpired)?;
+        let mut session: SessionInfo = serde_json::from_slice(&bytes)?;
+        match session.revoked.as_deref() {
+            Some(REVOKED_PASSWORD_CHANGED) => Err(ServerError::PasswordChanged),
+            Some(_) => Err(ServerError::SessionExpired),
+            None if session.expires_at <= timestamp_now() => Err(ServerError::SessionExpired),
+            None => {
+                session.current = true;
+                Ok(session)
+            }
+        }
+    }
+
+    fn list_sessions(&self) -> Result<Vec<SessionInfo>, ServerError> {
+        let mut sessions = Vec::new();
+        for item in self.sessions.iter() {
+            let (_, bytes) = item?;
+            sessions.push(serde_json::from_slice::<SessionInfo>(&bytes)?);
         }
+        sessions.sort_by_key(|session| session.created_at);
+        Ok(sessions)
     }
 
-    async fn load_vault(&self) -> Result<Vec<VaultEntry>, ServerError> {
+    fn revoked_sessions(
+        &self,
+        id: Option<&str>,
+        reason: &str,
+    ) -> Result<Vec<(sled::IVec, Vec<u8>)>, ServerError> {
+        let mut revoked = Vec::new();
+        for item in self.sessions.iter() {
+            let (key, bytes) = item?;
+            let mut session: SessionInfo = serde_json::from_slice(&bytes)?;
+            if session.revoked.is_some() || id.is_some_and(|id| session.id != id) {
+                continue;
+            }
+            session.revoked = Some(reason.to_string());
+            session.key_wrap.clear();
+            revoked.push((key, serde_json::to_vec(&session)?));
+        }
+        Ok(revoked)
+    }
+
+    fn revoke_sessions(&self, id: Option<&str>, reason: &str) -> Result<usize, ServerError> {
+        let revoked = self.revoked_sessions(id, reason)?;
+        for (key, bytes) in &revoked {
+            self.sessions.insert(key, bytes.as_slice())?;
+        }
+        self.sessions.flush()?;
+        Ok(revoked.len())
+    }
+
+    fn purge_expired_sessions(&self) -> Result<(), ServerError> {
+        let now = timestamp_now();
+        for item in self.sessions.iter() {
+            let (key, bytes) = item?;
+            let session: SessionInfo = serde_json::from_slice(&bytes)?;
+            if session.expires_at <= now {
+                self.sessions.remove(key)?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn change_master(&self, request: &PasswordChangeRequest) -> Result<(), ServerError> {
         let _guard = self.lock.lock().await;
-        let data = self.vault.get(VAULT_KEY)?;
-        if let Some(bytes) = data {
-            Ok(serde_json::from_slice(&bytes)?)
+        let stored_items = self.all_items()?;
+        if let Some(stale) = stored_items.iter().find(|stored| {
+            !request
+                .vault
+                .iter()
+                .any(|entry| entry.key == stored.id && entry.revision == stored.revision)
+        }) {
+            return Err(ServerError::Conflict(stale.id.clone(), stale.revision));
+        }
+        if let Some(deleted) = request
+            .vault
+            .iter()
+            .find(|entry| !stored_items.iter().any(|stored| stored.id == entry.key))
+        {
+            return Err(ServerError::Conflict(deleted.key.clone(), 0));
+        }
+
+        let revoked = self.revoked_sessions(None, REVOKED_PASSWORD_CHANGED)?;
+        let kdf_bytes = serde_json::to_vec(&request.kdf)?;
+        let revision = self.next_revision()?;
+        let mut encoded = Vec::with_capacity(request.vault.len());
+        for entry in &request.vault {
+            let mut stored = ItemRecord::new(entry.value.clone(), revision);
+            if let Some(item) = stored_items.iter().find(|item| item.id == entry.key) {
+                stored.trashed_at = item.trashed_at;
+                stored.quarantined = item.quarantined;
+            }
+            encoded.push((entry.key.clone(), serde_json::to_vec(&stored)?));
+        }
+        (&self.meta, &self.items, &self.sessions)
+            .transaction(
+                |(meta, items, sessions)| -> ConflictableTransactionResult<(), sled::Error> {
+                    for (id, bytes) in &encoded {
+                        items.insert(id.as_bytes(), bytes.as_slice())?;
+                    }
+                    meta.insert(KDF_PARAMS_KEY, kdf_bytes.clone())?;
+                    meta.insert(MASTER_HASH_KEY, request.auth_hash.as_bytes())?;
+                    for (key, bytes) in &revoked {
+                        sessions.insert(key, bytes.as_slice())?;
+                    }
+                    Ok(())
+                },
+            )
+            .map_err(|err| ServerError::Http(err.to_string()))?;
+        self.history.clear()?;
+        self.set_history_floor(revision)?;
+        self.meta.flush()?;
+        self.items.flush()?;
+        self.sessions.flush()?;
+        self.history.flush()?;
+        Ok(())
+    }
+
+    fn all_items(&self) -> Result<Vec<ItemMetadata>, ServerError> {
+        let mut items = Vec::new();
+        for entry in self.items.iter() {
+            let (key, bytes) = entry?;
+            let stored: ItemRecord = serde_json::from_slice(&bytes)?;
+            items.push(stored.metadata(String::from_utf8_lossy(&key).to_string()));
+        }
+        Ok(items)
+    }
+
+    fn list_items(&self) -> Result<Vec<ItemMetadata>, ServerError> {
+        let mut items = self.all_items()?;
+        items.retain(|item| item.trashed_at.is_none());
+        Ok(items)
+    }
+
+    fn list_trash(&self) -> Result<Vec<ItemMetadata>, ServerError> {
+        let mut items = self.all_items()?;
+        items.retain(|item| item.trashed_at.is_some());
+        Ok(items)
+    }
+
+    fn item_record(&self, id: &str) -> Result<Option<ItemRecord>, ServerError> {
+        match self.items.get(id)? {
+            Some(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn live_item(&self, id: &str) -> Result<ItemRecord, ServerError> {
+        self.item_record(id)?
+            .filter(|stored| !stored.is_trashed())
+            .ok_or_else(|| ServerError::NotFound(id.to_string()))
+    }
+
+    fn trashed_item(&self, id: &str) -> Result<ItemRecord, ServerError> {
+        self.item_record(id)?
+            .filter(ItemRecord::is_trashed)
+            .ok_or_else(|| ServerError::NotFound(id.to_string()))
+    }
+
+    fn load_item(&self, id: &str, trashed: bool) -> Result<VaultEntry, ServerError> {
+        let stored = if trashed {
+            self.trashed_item(id)?
         } else {
-            Ok(Vec::new())
+            self.live_item(id)?
+        };
+        Ok(VaultEntry {
+            key: id.to_string(),
+            value: stored.value,
+            revision: stored.revision,
+        })
+    }
+
+    fn current_revision(&self) -> Result<u64, ServerError> {
+        Ok(self
+            .meta
+            .get(REVISION_KEY)?
+            .and_then(|bytes| bytes.as_ref().try_into().ok())
+            .map(u64::from_be_bytes)
+            .unwrap_or(FIRST_REVISION))
+    }
+
+    fn next_revision(&self) -> Result<u64, ServerError> {
+        let next = self.current_revision()? + 1;
+        self.meta.insert(REVISION_KEY, &next.to_be_bytes())?;
+        Ok(next)
+    }
+
+    async fn save_item(
+        &self,
+        id: &str,
+        value: String,
+        base_revision: u64,
+    ) -> Result<ItemMetadata, ServerError> {
+        let _guard = self.lock.lock().await;
+        let previous = self.item_record(id)?;
+        let current = previous
+            .as_ref()
+            .filter(|stored| !stored.is_trashed())
+            .map_or(0, |stored| stored.revision);
+        if current != base_revision {
+            return Err(ServerError::Conflict(id.to_string(), current));
+        }
+
+        let metadata = self.write_item(id, value, None, false, previous)?;
+        self.flush_items()?;
+        Ok(metadata)
+    }
+
+    async fn delete_item(
+        &self,
+        id: &str,
+        base_revision: u64,
+        quarantine: bool,
+    ) -> Result<(), ServerError> {
+        let _guard = self.lock.lock().await;
+        let previous = self.live_item(id)?;
+        if previous.revision != base_revision {
+            return Err(ServerError::Conflict(id.to_string(), previous.revision));
+        }
+
+        let value = previous.value.clone();
+        self.write_item(id, value, Some(timestamp_now()), quarantine, Some(previous))?;
+        self.flush_items()
+    }
+
+    async fn move_item(
+        &self,
+        id: &str,
+        to: &str,
+        base_revision: u64,
+    ) -> Result<ItemMetadata, ServerError> {
+        let _guard = self.lock.lock().await;
+        let previous = self
+            .item_record(id)?
+            .ok_or_else(|| ServerError::NotFound(id.to_string()))?;
+        if previous.revision != base_revision {
+            return Err(ServerError::Conflict(id.to_string(), previous.revision));
+        }
+        if let Some(existing) = self.item_record(to)? {
+            return Err(ServerError::Conflict(to.to_string(), existing.revision));
+        }
+
+        self.rename_history(id, to)?;
+        self.rename_attachments(id, to)?;
+        let value = previous.value.clone();
+        let (trashed_at, quarantined) = (previous.trashed_at, previous.quarantined);
+        let metadata = self.write_item(to, value, trashed_at, quarantined, Some(previous))?;
+        self.items.remove(id)?;
+        self.flush_items()?;
+        Ok(metadata)
+    }
+
+    async fn restore_trashed(
+        &self,
+        id: &str,
+        base_revision: u64,
+    ) -> Result<ItemMetadata, ServerError> {
+        let _guard = self.lock.lock().await;
+        let previous = self.trashed_item(id)?;
+        if previous.revision != base_revision {
+            return Err(ServerError::Conflict(id.to_string(), previous.revision));
+        }
+
+        let value = previous.value.clone();
+        let metadata = self.write_item(id, value, None, false, Some(previous))?;
+        self.flush_items()?;
+        Ok(metadata)
+    }
+
+    async fn purge_trashed(&self, id: &str, base_revision: u64) -> Result<(), ServerError> {
+        let _guard = self.lock.lock().await;
+        let previous = self.trashed_item(id)?;
+        if previous.revision != base_revision {
+            return Err(ServerError::Conflict(id.to_string(), previous.revision));
+        }
+
+        self.remove_item(id, previous)?;
+        self.flush_items()
+    }
+
+    async fn empty_trash(&self) -> Result<usize, ServerError> {
+        self.purge_trash_where(|_| true).await
+    }
+
+    async fn purge_expired_trash(&self) -> Result<usize, ServerError> {
+        let cutoff = timestamp_now().saturating_sub(self.trash_retention_secs);
+        self.purge_trash_where(|item| {
+            !item.quarantined
+                && item
+                    .trashed_at
+                    .is_some_and(|trashed_at| trashed_at <= cutoff)
+        })
+        .await
+    }
+
+    async fn purge_trash_where<F>(&self, expired: F) -> Result<usize, ServerError>
+    where
+        F: Fn(&ItemMetadata) -> bool,
+    {
+        let _guard = self.lock.lock().await;
+        let mut purged = 0;
+        for item in self.list_trash()? {
+            if !expired(&item) {
+                continue;
+            }
+            let previous = self.trashed_item(&item.id)?;
+            self.remove_item(&item.id, previous)?;
+            purged += 1;
+        }
+        if purged > 0 {
+            self.flush_items()?;
+        }
+        Ok(purged)
+    }
+
+    fn write_item(
+        &self,
+        id: &str,
+        value: String,
+        trashed_at: Option<u64>,
+        quarantined: bool,
+        previous: Option<ItemRecord>,
+    ) -> Result<ItemMetadata, ServerError> {
+        let mut stored = ItemRecord::new(value, self.next_revision()?);
+        stored.trashed_at = trashed_at;
+        stored.quarantined = quarantined;
+        self.items.insert(id, serde_json::to_vec(&stored)?)?;
+        self.record_change(stored.revision, id, previous, &Some(stored.clone()))?;
+        Ok(stored.metadata(id.to_string()))
+    }
+
+    fn remove_item(&self, id: &str, previous: ItemRecord) -> Result<(), ServerError> {
+        let revision = self.next_revision()?;
+        self.items.remove(id)?;
+        self.remove_attachments(&attachment_prefix(id, None))?;
+        self.record_change(revision, id, Some(previous), &None)
+    }
+
+    fn flush_items(&self) -> Result<(), ServerError> {
+        self.meta.flush()?;
+        self.items.flush()?;
+        self.history.flush()?;
+        self.attachments.flush()?;
+        Ok(())
+    }
+
+    async fn save_attachment_chunk(
+        &self,
+        id: &str,
+        attachment: &str,
+        index: u32,
+        data: &[u8],
+    ) -> Result<(), ServerError> {
+        if data.len() > ATTACHMENT_CHUNK_SIZE + ATTACHMENT_CHUNK_OVERHEAD {
+            return Err(ServerError::TooLarge(format!(
+                "Attachment chunks are limited to {} bytes",
+                ATTACHMENT_CHUNK_SIZE
+            )));
+        }
+
+        let _guard = self.lock.lock().await;
+        self.live_item(id)?;
+        let key = attachment_chunk_key(id, attachment, index);
+        let replaced = self.attachments.get(&key)?.map_or(0, |bytes| bytes.len()) as u64;
+        let added = data.len() as u64;
+
+        let mut size = 0;
+        for entry in self
+            .attachments
+            .scan_prefix(attachment_prefix(id, Some(attachment)))
+        {
+            size += entry?.1.len() as u64;
+        }
+        if size - replaced + added > self.attachment_max_bytes {
+            return Err(ServerError::TooLarge(format!(
+                "Attachments are limited to {} MiB on this host",
+                self.attachment_max_bytes / MIB
+            )));
+        }
+        let used = self.attachment_bytes.load(Ordering::SeqCst) - replaced;
+        if used + added > self.attachment_quota_bytes {
+            return Err(ServerError::TooLarge(format!(
+                "Attachment storage on this host is full ({} of {} MiB used)",
+                used / MIB,
+                self.attachment_quota_bytes / MIB
+            )));
         }
+
+        self.attachments.insert(key, data)?;
+        self.attachments.flush()?;
+        self.attachment_bytes.store(used + added, Ordering::SeqCst);
+        Ok(())
+    }
+
+    fn load_attachment_chunk(
+        &self,
+        id: &str,
+        attachment: &str,
+        index: u32,
+    ) -> Result<Vec<u8>, ServerError> {
+        if self.item_record(id)?.is_none() {
+            return Err(ServerError::NotFound(id.to_string()));
+        }
+        self.attachments
+            .get(attachment_chunk_key(id, attachment, index))?
+            .map(|bytes| bytes.to_vec())
+            .ok_or_else(|| {
+                ServerError::AttachmentNotFound(format!("{} part {}", attachment, index))
+            })
     }
 
-    async fn save_vault(&self, entries: &[VaultEntry]) -> Result<(), ServerError> {
+    async fn delete_attachment(&self, id: &str, attachment: &str) -> Result<(), ServerError> {
         let _guard = self.lock.lock().await;
-        let data = serde_json::to_vec(entries)?;
-        self.vault.insert(VAULT_KEY, data)?;
-        self.vault.flush()?;
+        if self.remove_attachments(&attachment_prefix(id, Some(attachment)))? == 0 {
+            return Err(ServerError::AttachmentNotFound(attachment.to_string()));
+        }
+        self.attachments.flush()?;
+        Ok(())
+    }
+
+    fn remove_attachments(&self, prefix: &str) -> Result<usize, ServerError> {
+        let mut removed = 0;
+        for entry in self.attachments.scan_prefix(prefix) {
+            let (key, bytes) = entry?;
+            self.attachments.remove(key)?;
+            self.attachment_bytes
+                .fetch_sub(bytes.len() as u64, Ordering::SeqCst);
+            removed += 1;
+        }
+        Ok(removed)
+    }
+
+    fn rename_attachments(&self, id: &str, to: &str) -> Result<(), ServerError> {
+        let prefix = attachment_prefix(id, None);
+        for entry in self.attachments.scan_prefix(&prefix) {
+            let (key, bytes) = entry?;
+            let rest = String::from_utf8_lossy(&key[prefix.len()..]).to_string();
+            self.attachments
+                .insert(format!("{}{}", attachment_prefix(to, None), rest), bytes)?;
+            self.attachments.remove(key)?;
+        }
+        Ok(())
+    }
+
+    fn record_change(
+        &self,
+        revision: u64,
+        id: &str,
+        previous: Option<ItemRecord>,
+        current: &Option<ItemRecord>,
+    ) -> Result<(), ServerError> {
+        if id == INDEX_ID {
+            return Ok(());
+        }
+        let record = HistoryRecord {
+            id: id.to_string(),
+            timestamp: timestamp_now(),
+            previous,
+            deleted: current.is_none(),
+            trashed: current.as_ref().is_some_and(ItemRecord::is_trashed),
+        };
+        self.history
+            .insert(revision.to_be_bytes(), serde_json::to_vec(&record)?)?;
+        self.prune_history()
+    }
+
+    fn rename_history(&self, id: &str, to: &str) -> Result<(), ServerError> {
+        for entry in self.history.iter() {
+            let (key, bytes) = entry?;
+            let mut record: HistoryRecord = serde_json::from_slice(&bytes)?;
+            if record.id == id {
+                record.id = to.to_string();
+                self.history.insert(key, serde_json::to_vec(&record)?)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn prune_history(&self) -> Result<(), ServerError> {
+        while self.history.len() > self.history_limit {
+            match self.history.pop_min()? {
+                Some((key, _)) => self.set_history_floor(history_revision(&key))?,
+                None => break,
+            }
+        }
+        Ok(())
+    }
+
+    fn history_floor(&self) -> Result<u64, ServerError> {
+        Ok(self
+            .meta
+            .get(HISTORY_FLOOR_KEY)?
+            .and_then(|bytes| bytes.as_ref().try_into().ok())
+            .map(u64::from_be_bytes)
+            .unwrap_or(FIRST_REVISION))
+    }
+
+    fn set_history_floor(&self, revision: u64) -> Result<(), ServerError> {
+        self.meta
+            .insert(HISTORY_FLOOR_KEY, &revision.to_be_bytes())?;
         Ok(())
     }
+
+    fn list_history(&self) -> Result<HistoryResponse, ServerError> {
+        let mut changes = Vec::new();
+        for entry in self.history.iter().rev() {
+            let (key, bytes) = entry?;
+            let record: HistoryRecord = serde_json::from_slice(&bytes)?;
+            changes.push(record.change(history_revision(&key)));
+        }
+        Ok(HistoryResponse {
+            success: true,
+            message: None,
+            revision: self.current_revision()?,
+            oldest_revision: self.history_floor()?,
+            changes,
+        })
+    }
+
+    async fn restore(&self, target: u64) -> Result<usize, ServerError> {
+        let _guard = self.lock.lock().await;
+        let oldest = self.history_floor()?;
+        if target < oldest {
+            return Err(ServerError::HistoryUnavailable(target, oldest));
+        }
+
+        let mut states: BTreeMap<String, Option<ItemRecord>> = BTreeMap::new();
+        for entry in self.history.range((target + 1).to_be_bytes()..) {
+            let (_, bytes) = entry?;
+            let record: HistoryRecord = serde_json::from_slice(&bytes)?;
+            states.entry(record.id).or_insert(record.previous);
+        }
+
+        let mut restored = 0;
+        for (id, state) in states {
+            let current = self.item_record(&id)?;
+            match (state, current) {
+                (Some(past), current) => {
+                    let unchanged = current.as_ref().is_some_and(|item| {
+                        item.value == past.value
+                            && item.trashed_at == past.trashed_at
+                            && item.quarantined == past.quarantined
+                    });
+                    if unchanged {
+                        continue;
+                    }
+                    self.write_item(&id, past.value, past.trashed_at, past.quarantined, current)?;
+                }
+                (None, Some(current)) => self.remove_item(&id, current)?,
+                (None, None) => continue,
+            }
+            restored += 1;
+        }
+        self.flush_items()?;
+        Ok(restored)
+    }
+}
+
+fn attachment_prefix(id: &str, attachment: Option<&str>) -> String {
+    match attachment {
+        Some(attachment) => format!("{}/{}/", id, attachment),
+        None => format!("{}/", id),
+    }
+}
+
+fn attachment_chunk_key(id: &str, attachment: &str, index: u32) -> String {
+    format!("{}{:08}", attachment_prefix(id, Some(attachment)), index)
+}
+
+fn history_revision(key: &[u8]) -> u64 {
+    key.try_into().map(u64::from_be_bytes).unwrap_or_default()
 }
 
 type SharedStore = Arc<CloudStore>;
 
 #[derive(Deserialize)]
-struct VaultUpdateRequest {
+struct ItemUpdateRequest {
+    value: String,
+    #[serde(default)]
+    base_revision: u64,
+}
+
+#[derive(Deserialize)]
+struct ItemMoveRequest {
+    to: String,
+    base_revision: u64,
+}
+
+#[derive(Deserialize)]
+struct ItemDeleteQuery {
+    base_revision: u64,
+    #[serde(default)]
+    quarantine: bool,
+}
+
+#[derive(Deserialize)]
+struct RestoreRequest {
+    revision: u64,
+}
+
+#[derive(Deserialize)]
+struct LoginRequest {
+    nonce: String,
+    proof: String,
+    #[serde(default)]
+    label: String,
+}
+
+#[derive(Deserialize)]
+struct PasswordChangeRequest {
+    auth_hash: String,
+    kdf: KdfParams,
     vault: Vec<VaultEntry>,
 }
 
@@ -190,83 +1117,462 @@ pub fn run_server(config: ServerConfig) -> Result<(), ServerError> {
         .map_err(ServerError::Runtime)?;
 
     runtime.block_on(async move {
-        let path = config.storage_path();
         let provided_hash = config
             .master_hash
             .clone()
             .ok_or(ServerError::MissingMasterPassword)?;
-        let store = Arc::new(CloudStore::new(path).await?);
+        let provided_kdf = config
+            .kdf_params
+            .clone()
+            .ok_or(ServerError::MissingMasterPassword)?;
+        let store = Arc::new(CloudStore::new(&config).await?);
+        store.ensure_kdf_params(&provided_kdf)?;
         store.ensure_master_hash(&provided_hash)?;
+        if config.revoke_sessions {
+            let revoked = store.revoke_sessions(None, REVOKED_BY_HOST)?;
+            println!("Revoked {} active sessions.", revoked);
+        }
+        if config.trash_retention_days > 0 {
+            tokio::spawn(purge_trash_periodically(store.clone()));
+        }
         let app = build_router(store.clone());
 
+        let certificate = if config.tls {
+            Some(config.load_certificate()?)
+        } else {
+            None
+        };
+
         let addr = config.socket_addr()?;
         let listener = TcpListener::bind(addr).await?;
         let local_addr = listener.local_addr()?;
+        let scheme = if certificate.is_some() {
+            "https"
+        } else {
+            "http"
+        };
 
         println!(
-            "Cloud API server running on http://{}:{}/api/cloudsync",
+            "Cloud API server running on {}://{}:{}/api/cloudsync",
+            scheme,
             local_addr.ip(),
             local_addr.port()
         );
-        println!("Press Ctrl+C to stop the server.");
 
-        axum::serve(listener, app)
-            .with_graceful_shutdown(shutdown_signal())
-            .await
-            .map_err(|err| ServerError::Http(err.to_string()))
+        match certificate {
+            Some(certificate) => {
+                if certificate.generated {
+                    let (cert_path, _) = config.tls_paths();
+                    println!(
+                        "Generated a self-signed certificate at {}",
+                        cert_path.display()
+                    );
+                }
+                println!(
+                    "Certificate fingerprint (SHA-256): {}",
+                    certificate.fingerprint
+                );
+                println!("Press Ctrl+C to stop the server.");
+                serve_tls(listener, app, certificate).await
+            }
+            None => {
+                println!("Press Ctrl+C to stop the server.");
+                axum::serve(listener, app)
+                    .with_graceful_shutdown(shutdown_signal())
+                    .await
+                    .map_err(|err| ServerError::Http(err.to_string()))
+            }
+        }
     })
 }
 
+async fn purge_trash_periodically(store: SharedStore) {
+    let mut interval = tokio::time::interval(Duration::from_secs(TRASH_PURGE_INTERVAL_SECS));
+    loop {
+        interval.tick().await;
+        match store.purge_expired_trash().await {
+            Ok(0) => {}
+            Ok(purged) => println!("Purged {} expired item(s) from the trash.", purged),
+            Err(err) => eprintln!("Trash purge error: {}", err),
+        }
+    }
+}
+
+async fn serve_tls(
+    listener: TcpListener,
+    app: Router,
+    certificate: HostCertificate,
+) -> Result<(), ServerError> {
+    let tls_config = certificate.rustls_config().map_err(ServerError::Tls)?;
+    let handle = Handle::new();
+    let shutdown = handle.clone();
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        shutdown.graceful_shutdown(Some(Duration::from_secs(5)));
+    });
+
+    axum_server::from_tcp_rustls(listener.into_std()?, tls_config)
+        .handle(handle)
+        .serve(app.into_make_service())
+        .await
+        .map_err(|err| ServerError::Http(err.to_string()))
+}
+
 fn build_router(store: SharedStore) -> Router {
     Router::new()
+        .route("/api/cloudsync/kdf", get(kdf_handler))
+        .route("/api/cloudsync/auth/challenge", post(challenge_handler))
         .route("/api/cloudsync/auth", post(auth_handler))
-        .route("/api/cloudsync/vault", get(vault_get_handler))
-        .route("/api/cloudsync/vault", post(vault_post_handler))
+        .route("/api/cloudsync/session", get(session_handler))
+        .route("/api/cloudsync/sessions", get(sessions_handler))
+        .route(
+            "/api/cloudsync/sessions/:id",
+            delete(revoke_session_handler),
+        )
+        .route("/api/cloudsync/vault/items", get(items_list_handler))
+        .route(
+            "/api/cloudsync/vault/items/:id",
+            get(item_get_handler)
+                .put(item_put_handler)
+                .delete(item_delete_handler),
+        )
+        .route(
+            "/api/cloudsync/vault/items/:id/move",
+            post(item_move_handler),
+        )
+        .route(
+            "/api/cloudsync/vault/items/:id/attachments/:attachment",
+            delete(attachment_delete_handler),
+        )
+        .route(
+            "/api/cloudsync/vault/items/:id/attachments/:attachment/:index",
+            get(attachment_get_handler).put(attachment_put_handler),
+        )
+        .route(
+            "/api/cloudsync/vault/trash",
+            get(trash_list_handler).delete(trash_empty_handler),
+        )
+        .route(
+            "/api/cloudsync/vault/trash/:id",
+            get(trash_get_handler).delete(trash_purge_handler),
+        )
+        .route(
+            "/api/cloudsync/vault/trash/:id/restore",
+            post(trash_restore_handler),
+        )
+        .route("/api/cloudsync/vault/history", get(history_handler))
+        .route("/api/cloudsync/vault/restore", post(restore_handler))
         .route("/api/cloudsync/info", get(info_handler))
+        .route("/api/cloudsync/passwd", post(passwd_handler))
         .with_state(store)
 }
 
-async fn auth_handler(State(store): State<SharedStore>, headers: HeaderMap) -> StatusCode {
-    match authenticate(store.as_ref(), &headers) {
-        Ok(_) => StatusCode::OK,
+async fn kdf_handler(State(store): State<SharedStore>) -> (StatusCode, Json<KdfResponse>) {
+    match store.kdf_params() {
+        Ok(Some(kdf)) => (
+            StatusCode::OK,
+            Json(KdfResponse {
+                success: true,
+                message: None,
+                kdf: Some(kdf),
+            }),
+        ),
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            Json(KdfResponse {
+                success: false,
+                message: Some("Host has no key derivation parameters".to_string()),
+                kdf: None,
+            }),
+        ),
+        Err(err) => {
+            eprintln!("KDF handler error: {}", err);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(KdfResponse {
+                    success: false,
+                    message: Some(err.to_string()),
+                    kdf: None,
+                }),
+            )
+        }
+    }
+}
+
+async fn challenge_handler(State(store): State<SharedStore>) -> Json<ChallengeResponse> {
+    Json(ChallengeResponse {
+        success: true,
+        message: None,
+        nonce: Some(store.issue_challenge().await),
+    })
+}
+
+async fn auth_handler(
+    State(store): State<SharedStore>,
+    Json(payload): Json<LoginRequest>,
+) -> (StatusCode, Json<LoginResponse>) {
+    match store.login(&payload).await {
+        Ok((token, session)) => (
+            StatusCode::OK,
+            Json(LoginResponse {
+                success: true,
+                message: Some("Authenticated.".to_string()),
+                token: Some(token),
+                expires_at: session.expires_at,
+                key_wrap: Some(session.key_wrap),
+            }),
+        ),
         Err(err) => {
             eprintln!("Auth handler error: {}", err);
-            StatusCode::UNAUTHORIZED
+            let (status, message) = classify_error(&err);
+            (
+                status,
+                Json(LoginResponse {
+                    success: false,
+                    message: Some(message),
+                    token: None,
+                    expires_at: 0,
+                    key_wrap: None,
+                }),
+            )
         }
     }
 }
 
-async fn vault_get_handler(
+async fn session_handler(
     State(store): State<SharedStore>,
     headers: HeaderMap,
+) -> (StatusCode, Json<SessionsResponse>) {
+    match authenticate(store.as_ref(), &headers) {
+        Ok(session) => (
+            StatusCode::OK,
+            Json(SessionsResponse {
+                success: true,
+                message: None,
+                sessions: vec![session],
+            }),
+        ),
+        Err(err) => to_sessions_error(err),
+    }
+}
+
+async fn sessions_handler(
+    State(store): State<SharedStore>,
+    headers: HeaderMap,
+) -> (StatusCode, Json<SessionsResponse>) {
+    let current = match authenticate(store.as_ref(), &headers) {
+        Ok(session) => session,
+        Err(err) => return to_sessions_error(err),
+    };
+
+    match store.list_sessions() {
+        Ok(mut sessions) => {
+            for session in sessions.iter_mut() {
+                session.current = session.id == current.id;
+                session.key_wrap.clear();
+            }
+            (
+                StatusCode::OK,
+                Json(SessionsResponse {
+                    success: true,
+                    message: None,
+                    sessions,
+                }),
+            )
+        }
+        Err(err) => to_sessions_error(err),
+    }
+}
+
+async fn revoke_session_handler(
+    State(store): State<SharedStore>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> (StatusCode, Json<SessionsResponse>) {
+    if let Err(err) = authenticate(store.as_ref(), &headers) {
+        return to_sessions_error(err);
+    }
+
+    let target = if id == "all" { None } else { Some(id.as_str()) };
+    match store.revoke_sessions(target, REVOKED_BY_USER) {
+        Ok(count) => (
+            StatusCode::OK,
+            Json(SessionsResponse {
+                success: true,
+                message: Some(format!("Revoked {} session(s).", count)),
+                sessions: Vec::new(),
+            }),
+        ),
+        Err(err) => to_sessions_error(err),
+    }
+}
+
+async fn items_list_handler(
+    State(store): State<SharedStore>,
+    headers: HeaderMap,
+) -> (StatusCode, Json<ItemsResponse>) {
+    if let Err(err) = authenticate(store.as_ref(), &headers) {
+        return to_items_error(err);
+    }
+
+    match store.list_items() {
+        Ok(items) => (
+            StatusCode::OK,
+            Json(ItemsResponse {
+                success: true,
+                message: None,
+                items,
+            }),
+        ),
+        Err(err) => to_items_error(err),
+    }
+}
+
+async fn item_get_handler(
+    State(store): State<SharedStore>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> (StatusCode, Json<ItemResponse>) {
+    if let Err(err) = authenticate(store.as_ref(), &headers) {
+        return to_item_error(err);
+    }
+
+    match store.load_item(&id, false) {
+        Ok(item) => (
+            StatusCode::OK,
+            Json(ItemResponse {
+                success: true,
+                message: None,
+                item: Some(item),
+            }),
+        ),
+        Err(err) => to_item_error(err),
+    }
+}
+
+async fn item_put_handler(
+    State(store): State<SharedStore>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Json(payload): Json<ItemUpdateRequest>,
+) -> (StatusCode, Json<ItemsResponse>) {
+    if let Err(err) = authenticate(store.as_ref(), &headers) {
+        return to_items_error(err);
+    }
+
+    match store
+        .save_item(&id, payload.value, payload.base_revision)
+        .await
+    {
+        Ok(metadata) => (
+            StatusCode::OK,
+            Json(ItemsResponse {
+                success: true,
+                message: Some("Item saved.".to_string()),
+                items: vec![metadata],
+            }),
+        ),
+        Err(err) => to_items_error(err),
+    }
+}
+
+async fn attachment_put_handler(
+    State(store): State<SharedStore>,
+    headers: HeaderMap,
+    Path((id, attachment, index)): Path<(String, String, u32)>,
+    body: Bytes,
+) -> (StatusCode, Json<CloudResponse>) {
+    if let Err(err) = authenticate(store.as_ref(), &headers) {
+        return to_error_response(err);
+    }
+
+    match store
+        .save_attachment_chunk(&id, &attachment, index, &body)
+        .await
+    {
+        Ok(()) => (
+            StatusCode::OK,
+            Json(CloudResponse {
+                success: true,
+                message: "Attachment part saved.".to_string(),
+            }),
+        ),
+        Err(err) => to_error_response(err),
+    }
+}
+
+async fn attachment_get_handler(
+    State(store): State<SharedStore>,
+    headers: HeaderMap,
+    Path((id, attachment, index)): Path<(String, String, u32)>,
+) -> Result<Vec<u8>, (StatusCode, Json<CloudResponse>)> {
+    authenticate(store.as_ref(), &headers).map_err(to_error_response)?;
+    store
+        .load_attachment_chunk(&id, &attachment, index)
+        .map_err(to_error_response)
+}
+
+async fn attachment_delete_handler(
+    State(store): State<SharedStore>,
+    headers: HeaderMap,
+    Path((id, attachment)): Path<(String, String)>,
 ) -> (StatusCode, Json<CloudResponse>) {
     if let Err(err) = authenticate(store.as_ref(), &headers) {
         return to_error_response(err);
     }
 
-    match store.load_vault().await {
-        Ok(vault) => (
+    match store.delete_attachment(&id, &attachment).await {
+        Ok(()) => (
             StatusCode::OK,
             Json(CloudResponse {
                 success: true,
-                message: "Vault retrieved.".to_string(),
-                vault: Some(vault),
+                message: "Attachment deleted.".to_string(),
             }),
         ),
         Err(err) => to_error_response(err),
     }
 }
 
-async fn vault_post_handler(
+async fn item_move_handler(
     State(store): State<SharedStore>,
     headers: HeaderMap,
-    Json(payload): Json<VaultUpdateRequest>,
+    Path(id): Path<String>,
+    Json(payload): Json<ItemMoveRequest>,
+) -> (StatusCode, Json<ItemsResponse>) {
+    if let Err(err) = authenticate(store.as_ref(), &headers) {
+        return to_items_error(err);
+    }
+
+    match store
+        .move_item(&id, &payload.to, payload.base_revision)
+        .await
+    {
+        Ok(metadata) => (
+            StatusCode::OK,
+            Json(ItemsResponse {
+                success: true,
+                message: Some("Item moved.".to_string()),
+                items: vec![metadata],
+            }),
+        ),
+        Err(err) => to_items_error(err),
+    }
+}
+
+async fn item_delete_handler(
+    State(store): State<SharedStore>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Query(query): Query<ItemDeleteQuery>,
 ) -> (StatusCode, Json<CloudResponse>) {
     if let Err(err) = authenticate(store.as_ref(), &headers) {
         return to_error_response(err);
     }
 
-    if let Err(err) = store.save_vault(&payload.vault).await {
+    if let Err(err) = store
+        .delete_item(&id, query.base_revision, query.quarantine)
+        .await
+    {
         return to_error_response(err);
     }
 
@@ -274,8 +1580,186 @@ async fn vault_post_handler(
         StatusCode::OK,
         Json(CloudResponse {
             success: true,
-            message: "Vault updated successfully.".to_string(),
-            vault: None,
+            message: "Item moved to the trash.".to_string(),
+        }),
+    )
+}
+
+async fn trash_list_handler(
+    State(store): State<SharedStore>,
+    headers: HeaderMap,
+) -> (StatusCode, Json<ItemsResponse>) {
+    if let Err(err) = authenticate(store.as_ref(), &headers) {
+        return to_items_error(err);
+    }
+
+    match store.list_trash() {
+        Ok(items) => (
+            StatusCode::OK,
+            Json(ItemsResponse {
+                success: true,
+                message: None,
+                items,
+            }),
+        ),
+        Err(err) => to_items_error(err),
+    }
+}
+
+async fn trash_get_handler(
+    State(store): State<SharedStore>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> (StatusCode, Json<ItemResponse>) {
+    if let Err(err) = authenticate(store.as_ref(), &headers) {
+        return to_item_error(err);
+    }
+
+    match store.load_item(&id, true) {
+        Ok(item) => (
+            StatusCode::OK,
+            Json(ItemResponse {
+                success: true,
+                message: None,
+                item: Some(item),
+            }),
+        ),
+        Err(err) => to_item_error(err),
+    }
+}
+
+async fn trash_restore_handler(
+    State(store): State<SharedStore>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Query(query): Query<ItemDeleteQuery>,
+) -> (StatusCode, Json<ItemsResponse>) {
+    if let Err(err) = authenticate(store.as_ref(), &headers) {
+        return to_items_error(err);
+    }
+
+    match store.restore_trashed(&id, query.base_revision).await {
+        Ok(metadata) => (
+            StatusCode::OK,
+            Json(ItemsResponse {
+                success: true,
+                message: Some("Item restored.".to_string()),
+                items: vec![metadata],
+            }),
+        ),
+        Err(err) => to_items_error(err),
+    }
+}
+
+async fn trash_purge_handler(
+    State(store): State<SharedStore>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Query(query): Query<ItemDeleteQuery>,
+) -> (StatusCode, Json<CloudResponse>) {
+    if let Err(err) = authenticate(store.as_ref(), &headers) {
+        return to_error_response(err);
+    }
+
+    if let Err(err) = store.purge_trashed(&id, query.base_revision).await {
+        return to_error_response(err);
+    }
+
+    (
+        StatusCode::OK,
+        Json(CloudResponse {
+            success: true,
+            message: "Item deleted permanently.".to_string(),
+        }),
+    )
+}
+
+async fn trash_empty_handler(
+    State(store): State<SharedStore>,
+    headers: HeaderMap,
+) -> (StatusCode, Json<CloudResponse>) {
+    if let Err(err) = authenticate(store.as_ref(), &headers) {
+        return to_error_response(err);
+    }
+
+    match store.empty_trash().await {
+        Ok(purged) => (
+            StatusCode::OK,
+            Json(CloudResponse {
+                success: true,
+                message: format!("Deleted {} item(s) permanently.", purged),
+            }),
+        ),
+        Err(err) => to_error_response(err),
+    }
+}
+
+async fn history_handler(
+    State(store): State<SharedStore>,
+    headers: HeaderMap,
+) -> (StatusCode, Json<HistoryResponse>) {
+    if let Err(err) = authenticate(store.as_ref(), &headers) {
+        return to_history_error(err);
+    }
+
+    match store.list_history() {
+        Ok(history) => (StatusCode::OK, Json(history)),
+        Err(err) => to_history_error(err),
+    }
+}
+
+async fn restore_handler(
+    State(store): State<SharedStore>,
+    headers: HeaderMap,
+    Json(payload): Json<RestoreRequest>,
+) -> (StatusCode, Json<CloudResponse>) {
+    if let Err(err) = authenticate(store.as_ref(), &headers) {
+        return to_error_response(err);
+    }
+
+    match store.restore(payload.revision).await {
+        Ok(restored) => (
+            StatusCode::OK,
+            Json(CloudResponse {
+                success: true,
+                message: format!(
+                    "Restored {} item(s) to revision {}.",
+                    restored, payload.revision
+                ),
+            }),
+        ),
+        Err(err) => to_error_response(err),
+    }
+}
+
+async fn passwd_handler(
+    State(store): State<SharedStore>,
+    headers: HeaderMap,
+    Json(payload): Json<PasswordChangeRequest>,
+) -> (StatusCode, Json<CloudResponse>) {
+    if let Err(err) = authenticate(store.as_ref(), &headers) {
+        return to_error_response(err);
+    }
+
+    if payload.auth_hash.is_empty() || !payload.kdf.is_current() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(CloudResponse {
+                success: false,
+                message: "Invalid password change request".to_string(),
+            }),
+        );
+    }
+
+    if let Err(err) = store.change_master(&payload).await {
+        return to_error_response(err);
+    }
+
+    (
+        StatusCode::OK,
+        Json(CloudResponse {
+            success: true,
+            message: "Master password changed.".to_string(),
         }),
     )
 }
@@ -291,32 +1775,17 @@ async fn info_handler(
             Json(CloudInfoResponse {
                 success: false,
                 message: Some(AUTH_FAILURE.to_string()),
-                entry_count: 0,
             }),
         );
     }
 
-    match store.load_vault().await {
-        Ok(vault) => (
-            StatusCode::OK,
-            Json(CloudInfoResponse {
-                success: true,
-                message: Some("Vault reachable.".to_string()),
-                entry_count: vault.len(),
-            }),
-        ),
-        Err(err) => {
-            eprintln!("Info error: {}", err);
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(CloudInfoResponse {
-                    success: false,
-                    message: Some(err.to_string()),
-                    entry_count: 0,
-                }),
-            )
-        }
-    }
+    (
+        StatusCode::OK,
+        Json(CloudInfoResponse {
+            success: true,
+            message: Some("Vault reachable.".to_string()),
+        }),
+    )
 }
 
 fn to_error_response(err: ServerError) -> (StatusCode, Json<CloudResponse>) {
@@ -326,7 +1795,56 @@ fn to_error_response(err: ServerError) -> (StatusCode, Json<CloudResponse>) {
         Json(CloudResponse {
             success: false,
             message,
-            vault: None,
+        }),
+    )
+}
+
+fn to_items_error(err: ServerError) -> (StatusCode, Json<ItemsResponse>) {
+    let (status, message) = classify_error(&err);
+    (
+        status,
+        Json(ItemsResponse {
+            success: false,
+            message: Some(message),
+            items: Vec::new(),
+        }),
+    )
+}
+
+fn to_item_error(err: ServerError) -> (StatusCode, Json<ItemResponse>) {
+    let (status, message) = classify_error(&err);
+    (
+        status,
+        Json(ItemResponse {
+            success: false,
+            message: Some(message),
+            item: None,
+        }),
+    )
+}
+
+fn to_history_error(err: ServerError) -> (StatusCode, Json<HistoryResponse>) {
+    let (status, message) = classify_error(&err);
+    (
+        status,
+        Json(HistoryResponse {
+            success: false,
+            message: Some(message),
+            revision: 0,
+            oldest_revision: 0,
+            changes: Vec::new(),
+        }),
+    )
+}
+
+fn to_sessions_error(err: ServerError) -> (StatusCode, Json<SessionsResponse>) {
+    let (status, message) = classify_error(&err);
+    (
+        status,
+        Json(SessionsResponse {
+            success: false,
+            message: Some(message),
+            sessions: Vec::new(),
         }),
     )
 }
@@ -334,7 +1852,14 @@ fn to_error_response(err: ServerError) -> (StatusCode, Json<CloudResponse>) {
 fn classify_error(err: &ServerError) -> (StatusCode, String) {
     match err {
         ServerError::Address(msg) => (StatusCode::BAD_REQUEST, msg.clone()),
+        ServerError::NotFound(_) => (StatusCode::NOT_FOUND, err.to_string()),
+        ServerError::Conflict(..) => (StatusCode::CONFLICT, err.to_string()),
+        ServerError::HistoryUnavailable(..) => (StatusCode::GONE, err.to_string()),
+        ServerError::AttachmentNotFound(_) => (StatusCode::NOT_FOUND, err.to_string()),
+        ServerError::TooLarge(_) => (StatusCode::PAYLOAD_TOO_LARGE, err.to_string()),
         ServerError::Unauthorized => (StatusCode::UNAUTHORIZED, AUTH_FAILURE.to_string()),
+        ServerError::SessionExpired => (StatusCode::UNAUTHORIZED, SESSION_EXPIRED.to_string()),
+        ServerError::PasswordChanged => (StatusCode::UNAUTHORIZED, PASSWORD_CHANGED.to_string()),
         ServerError::MissingMasterPassword => (
             StatusCode::BAD_REQUEST,
             "Master password required".to_string(),
@@ -349,10 +1874,15 @@ async fn shutdown_signal() {
     }
 }
 
-fn authenticate(store: &CloudStore, headers: &HeaderMap) -> Result<(), ServerError> {
-    let hash = headers
-        .get(MASTER_HASH_HEADER)
+fn authenticate(store: &CloudStore, headers: &HeaderMap) -> Result<SessionInfo, ServerError> {
+    let token = headers
+        .get(AUTHORIZATION)
         .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
         .ok_or(ServerError::Unauthorized)?;
-    store.verify_master(hash)
+    store.verify_session(token)
+}
+
+fn token_digest(token: &str) -> String {
+    blake3::hash(token.as_bytes()).to_hex().to_string()
 }
@@ -0,0 +1,307 @@
+use crate::cloud::cache::{CachedItem, PendingOp, VaultCache};
+use crate::cloud::client::{CloudClientError, RemoteSession};
+use crate::cloud::types::{VaultEntry, INDEX_ID};
+use crate::utils::timestamp_now;
+use aes_gcm::{Aes256Gcm, Key};
+use anstream::eprintln;
+use owo_colors::OwoColorize;
+use std::cell::{Cell, RefCell};
+use uuid::Uuid;
+
+const OFFLINE_UNSUPPORTED: &str =
+    "The host is unreachable. This command needs a connection to the host.";
+
+pub struct VaultSession {
+    remote: Option<RemoteSession>,
+    offline: Cell<bool>,
+    cache: RefCell<VaultCache>,
+    encryption_key: Key<Aes256Gcm>,
+}
+
+impl VaultSession {
+    pub fn online(remote: RemoteSession) -> Result<Self, CloudClientError> {
+        let encryption_key = *remote.encryption_key();
+        let cache = match VaultCache::open(remote.base_url(), &encryption_key) {
+            Ok(cache) => cache,
+            Err(err) => {
+                let backup = VaultCache::set_aside().map_err(|cause| {
+                    CloudClientError::Failure(format!(
+                        "{}. It may hold changes that were never synced, so it was left in place: failed to move it aside: {}",
+                        err, cause
+                    ))
+                })?;
+                eprintln!(
+                    "{}",
+                    format!(
+                        "{}. Moved it to {} (it may hold changes that were never synced) and rebuilding it from the host.",
+                        err,
+                        backup.display()
+                    )
+                    .yellow()
+                );
+                VaultCache::empty(remote.base_url())
+            }
+        };
+
+        let session = Self {
+            remote: Some(remote),
+            offline: Cell::new(false),
+            cache: RefCell::new(cache),
+            encryption_key,
+        };
+        session.synchronize()?;
+        Ok(session)
+    }
+
+    pub fn offline(base_url: &str, encryption_key: Key<Aes256Gcm>) -> Result<Self, String> {
+        let cache = VaultCache::open(base_url, &encryption_key)?;
+        if cache.last_sync.is_none() {
+            return Err(
+                "The host is unreachable and this machine has no offline copy of the vault yet."
+                    .to_string(),
+            );
+        }
+
+        Ok(Self {
+            remote: None,
+            offline: Cell::new(true),
+            cache: RefCell::new(cache),
+            encryption_key,
+        })
+    }
+
+    pub fn remote(&self) -> Result<&RemoteSession, CloudClientError> {
+        self.connected()
+            .ok_or_else(|| CloudClientError::Failure(OFFLINE_UNSUPPORTED.into()))
+    }
+
+    pub fn encryption_key(&self) -> &Key<Aes256Gcm> {
+        &self.encryption_key
+    }
+
+    pub fn pending_changes(&self) -> usize {
+        self.cache.borrow().pending.len()
+    }
+
+    pub fn last_sync(&self) -> Option<u64> {
+        self.cache.borrow().last_sync
+    }
+
+    pub fn entries(&self) -> Vec<VaultEntry> {
+        let cache = self.cache.borrow();
+        cache
+            .items
+            .keys()
+            .filter_map(|id| cache.entry(id))
+            .collect()
+    }
+
+    pub fn fetch_item(&self, id: &str) -> Result<VaultEntry, CloudClientError> {
+        if let Some(remote) = self.connected() {
+            match remote.fetch_item(id) {
+                Ok(entry) => {
+                    self.remember(&entry, None);
+                    return Ok(entry);
+                }
+                Err(err @ CloudClientError::NotFound(_)) => {
+                    self.cache.borrow_mut().items.remove(id);
+                    return Err(err);
+                }
+                Err(err) if err.is_unreachable() => self.go_offline(&err),
+                Err(err) => return Err(err),
+            }
+        }
+
+        self.cache
+            .borrow()
+            .entry(id)
+            .ok_or_else(|| CloudClientError::NotFound(format!("Item not found: {}", id)))
+    }
+
+    pub fn store_item(&self, entry: &VaultEntry) -> Result<(), CloudClientError> {
+        if let Some(remote) = self.connected() {
+            match remote.store_item(entry) {
+                Ok(metadata) => {
+                    self.remember(
+                        &VaultEntry {
+                            revision: metadata.revision,
+                            ..entry.clone()
+                        },
+                        Some(metadata.updated_at),
+                    );
+                    return self.persist();
+                }
+                Err(err) if err.is_unreachable() => self.go_offline(&err),
+                Err(err) => return Err(err),
+            }
+        }
+
+        self.cache
+            .borrow_mut()
+            .queue_put(entry.clone(), timestamp_now())?;
+        self.persist()
+    }
+
+    pub fn delete_item(&self, id: &str, revision: u64) -> Result<(), CloudClientError> {
+        if let Some(remote) = self.connected() {
+            match remote.delete_item(id, revision) {
+                Ok(()) => {
+                    self.cache.borrow_mut().items.remove(id);
+                    return self.persist();
+                }
+                Err(err) if err.is_unreachable() => self.go_offline(&err),
+                Err(err) => return Err(err),
+            }
+        }
+
+        self.cache.borrow_mut().queue_delete(id, revision)?;
+        self.persist()
+    }
+
+    pub fn quarantine_item(&self, id: &str, revision: u64) -> Result<(), CloudClientError> {
+        self.remote()?.quarantine_item(id, revision)?;
+        self.cache.borrow_mut().items.remove(id);
+        self.persist()
+    }
+
+    fn connected(&self) -> Option<&RemoteSession> {
+        if self.offline.get() {
+            None
+        } else {
+            self.remote.as_ref()
+        }
+    }
+
+    fn go_offline(&self, err: &CloudClientError) {
+        eprintln!(
+            "{}",
+            format!("{}. Continuing offline; changes will sync later.", err).yellow()
+        );
+        self.offline.set(true);
+    }
+
+    fn remember(&self, entry: &VaultEntry, updated_at: Option<u64>) {
+        let mut cache = self.cache.borrow_mut();
+        let updated_at = updated_at
+            .or_else(|| cache.items.get(&entry.key).map(|item| item.updated_at))
+            .unwrap_or_default();
+        cache.items.insert(
+            entry.key.clone(),
+            CachedItem {
+                value: entry.value.clone(),
+                revision: entry.revision,
+                updated_at,
+            },
+        );
+    }
+
+    fn persist(&self) -> Result<(), CloudClientError> {
+        self.cache
+            .borrow()
+            .save(&self.encryption_key)
+            .map_err(CloudClientError::Failure)
+    }
+
+    pub fn synchronize(&self) -> Result<(), CloudClientError> {
+        let remote = self.remote()?;
+        let replayed = self.replay(remote);
+        let refreshed = replayed.and_then(|_| self.refresh(remote));
+        self.persist()?;
+        refreshed
+    }
+
+    fn replay(&self, remote: &RemoteSession) -> Result<(), CloudClientError> {
+        let pending = std::mem::take(&mut self.cache.borrow_mut().pending);
+        let total = pending.len();
+
+        for (index, op) in pending.iter().enumerate() {
+            if let Err(err) = replay_op(remote, op) {
+                self.cache.borrow_mut().pending = pending[index..].to_vec();
+                return Err(err);
+            }
+        }
+
+        if total > 0 {
+            eprintln!(
+                "{}",
+                format!("Synced {} offline change(s) to the host.", total).green()
+            );
+        }
+        Ok(())
+    }
+
+    fn refresh(&self, remote: &RemoteSession) -> Result<(), CloudClientError> {
+        let listed = remote.list_items()?;
+        let kdf = remote.kdf_params()?;
+
+        let mut cache = self.cache.borrow_mut();
+        cache
+            .items
+            .retain(|id, _| listed.iter().any(|item| &item.id == id));
+        for item in listed {
+            let current = cache
+                .items
+                .get(&item.id)
+                .is_some_and(|cached| cached.revision == item.revision);
+            if current {
+                continue;
+            }
+
+            let entry = remote.fetch_item(&item.id)?;
+            cache.items.insert(
+                item.id,
+                CachedItem {
+                    value: entry.value,
+                    revision: entry.revision,
+                    updated_at: item.updated_at,
+                },
+            );
+        }
+        cache.kdf = Some(kdf);
+        cache.last_sync = Some(timestamp_now());
+        Ok(())
+    }
+}
+
+fn replay_op(remote: &RemoteSession, op: &PendingOp) -> Result<(), CloudClientError> {
+    match op {
+        PendingOp::Put { entry } => match remote.store_item(entry) {
+            Ok(_) => Ok(()),
+            Err(CloudClientError::Conflict(_)) if entry.key == INDEX_ID => Ok(()),
+            Err(CloudClientError::Conflict(message)) => {
+                let key = Uuid::new_v4().to_string();
+                remote.store_item(&VaultEntry {
+                    key: key.clone(),
+                    value: entry.value.clone(),
+                    revision: 0,
+                })?;
+                eprintln!(
+                    "{}",
+                    format!(
+                        "{}. Your offline version was kept as a separate entry ({}).",
+                        message,
+                        &key[..8]
+                    )
+                    .yellow()
+                );
+                Ok(())
+            }
+            Err(err) => Err(err),
+        },
+        PendingOp::Delete { id, revision } => match remote.delete_item(id, *revision) {
+            Ok(()) | Err(CloudClientError::NotFound(_)) => Ok(()),
+            Err(CloudClientError::Conflict(message)) => {
+                eprintln!(
+                    "{}",
+                    format!(
+                        "{}. Kept it instead of applying the offline delete.",
+                        message
+                    )
+                    .yellow()
+                );
+                Ok(())
+            }
+            Err(err) => Err(err),
+        },
+    }
+}
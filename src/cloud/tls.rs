@@ -0,0 +1,187 @@
+use crate::utils::write_private_file;
+use axum_server::tls_rustls::RustlsConfig;
+use reqwest_rustls::client::{ServerCertVerified, ServerCertVerifier, WebPkiVerifier};
+use reqwest_rustls::{Certificate, ClientConfig, OwnedTrustAnchor, RootCertStore, ServerName};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+pub struct HostCertificate {
+    certs: Vec<CertificateDer<'static>>,
+    key: PrivateKeyDer<'static>,
+    pub fingerprint: String,
+    pub generated: bool,
+}
+
+impl HostCertificate {
+    pub fn load_or_generate(
+        cert_path: &Path,
+        key_path: &Path,
+        subject_alt_names: Vec<String>,
+    ) -> Result<Self, String> {
+        if !cert_path.exists() && !key_path.exists() {
+            Self::generate(cert_path, key_path, subject_alt_names)?;
+            let mut certificate = Self::load(cert_path, key_path)?;
+            certificate.generated = true;
+            return Ok(certificate);
+        }
+        Self::load(cert_path, key_path)
+    }
+
+    pub fn load(cert_path: &Path, key_path: &Path) -> Result<Self, String> {
+        let certs = CertificateDer::pem_file_iter(cert_path)
+            .and_then(|iter| iter.collect::<Result<Vec<_>, _>>())
+            .map_err(|err| format!("Failed to read {}: {}", cert_path.display(), err))?;
+        let leaf = certs
+            .first()
+            .ok_or_else(|| format!("No certificate found in {}", cert_path.display()))?;
+        let fingerprint = fingerprint(leaf.as_ref());
+        let key = PrivateKeyDer::from_pem_file(key_path)
+            .map_err(|err| format!("Failed to read {}: {}", key_path.display(), err))?;
+
+        Ok(Self {
+            certs,
+            key,
+            fingerprint,
+            generated: false,
+        })
+    }
+
+    fn generate(
+        cert_path: &Path,
+        key_path: &Path,
+        subject_alt_names: Vec<String>,
+    ) -> Result<(), String> {
+        let generated = rcgen::generate_simple_self_signed(subject_alt_names)
+            .map_err(|err| format!("Failed to generate certificate: {}", err))?;
+        if let Some(parent) = cert_path.parent() {
+            std::fs::create_dir_all(parent).map_err(|err| err.to_string())?;
+        }
+        std::fs::write(cert_path, generated.cert.pem())
+            .map_err(|err| format!("Failed to write {}: {}", cert_path.display(), err))?;
+        write_private_file(key_path, generated.key_pair.serialize_pem().as_bytes())
+            .map_err(|err| format!("Failed to write {}: {}", key_path.display(), err))
+    }
+
+    pub fn rustls_config(self) -> Result<RustlsConfig, String> {
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let mut config = rustls::ServerConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .map_err(|err| err.to_string())?
+            .with_no_client_auth()
+            .with_single_cert(self.certs, self.key)
+            .map_err(|err| format!("Invalid certificate or key: {}", err))?;
+        config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+        Ok(RustlsConfig::from_config(Arc::new(config)))
+    }
+}
+
+// Certificates are checked against the public CAs unless the user explicitly
+// trusted a fingerprint with `x cloud <url>`; only then is the CA check
+// replaced by comparing against that pin.
+pub struct PinnedVerifier {
+    expected: Option<String>,
+    webpki: WebPkiVerifier,
+    seen: Mutex<Option<String>>,
+    untrusted: Mutex<bool>,
+}
+
+impl PinnedVerifier {
+    pub fn new(expected: Option<String>) -> Self {
+        let mut roots = RootCertStore::empty();
+        roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|anchor| {
+            OwnedTrustAnchor::from_subject_spki_name_constraints(
+                anchor.subject,
+                anchor.spki,
+                anchor.name_constraints,
+            )
+        }));
+        Self {
+            expected,
+            webpki: WebPkiVerifier::new(roots, None),
+            seen: Mutex::new(None),
+            untrusted: Mutex::new(false),
+        }
+    }
+
+    pub fn seen(&self) -> Option<String> {
+        self.seen.lock().ok().and_then(|seen| seen.clone())
+    }
+
+    pub fn mismatch(&self) -> Option<String> {
+        let expected = self.expected.as_deref()?;
+        self.seen()
+            .filter(|actual| !same_fingerprint(expected, actual))
+    }
+
+    pub fn untrusted(&self) -> Option<String> {
+        let rejected = self.untrusted.lock().map(|flag| *flag).unwrap_or(false);
+        if rejected {
+            self.seen()
+        } else {
+            None
+        }
+    }
+}
+
+impl ServerCertVerifier for PinnedVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        server_name: &ServerName,
+        scts: &mut dyn Iterator<Item = &[u8]>,
+        ocsp_response: &[u8],
+        now: SystemTime,
+    ) -> Result<ServerCertVerified, reqwest_rustls::Error> {
+        let actual = fingerprint(&end_entity.0);
+        if let Ok(mut seen) = self.seen.lock() {
+            *seen = Some(actual.clone());
+        }
+        match &self.expected {
+            Some(expected) if same_fingerprint(expected, &actual) => {
+                Ok(ServerCertVerified::assertion())
+            }
+            Some(_) => Err(reqwest_rustls::Error::General(
+                "certificate fingerprint mismatch".into(),
+            )),
+            None => {
+                let verified = self.webpki.verify_server_cert(
+                    end_entity,
+                    intermediates,
+                    server_name,
+                    scts,
+                    ocsp_response,
+                    now,
+                );
+                if let Ok(mut untrusted) = self.untrusted.lock() {
+                    *untrusted = verified.is_err();
+                }
+                verified
+            }
+        }
+    }
+}
+
+pub fn client_config(verifier: Arc<PinnedVerifier>) -> ClientConfig {
+    ClientConfig::builder()
+        .with_safe_defaults()
+        .with_custom_certificate_verifier(verifier)
+        .with_no_client_auth()
+}
+
+pub fn fingerprint(der: &[u8]) -> String {
+    Sha256::digest(der)
+        .iter()
+        .map(|byte| format!("{:02X}", byte))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+fn same_fingerprint(a: &str, b: &str) -> bool {
+    let normalize = |value: &str| value.replace(':', "").to_ascii_lowercase();
+    normalize(a) == normalize(b)
+}
@@ -1,17 +1,85 @@
+use crate::security::kdf::KdfParams;
 use serde::{Deserialize, Serialize};
 
+pub const PASSWORD_CHANGED: &str =
+    "The master password was changed from another machine. Enter the new password to continue.";
+pub const SESSION_EXPIRED: &str = "Your session has expired or was revoked. Please log in again.";
+pub const INDEX_ID: &str = "00000000-0000-0000-0000-000000000000";
+pub const ATTACHMENT_CHUNK_SIZE: usize = 1024 * 1024;
+
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct VaultEntry {
     pub key: String,
     pub value: String,
+    #[serde(default)]
+    pub revision: u64,
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct CloudResponse {
     pub success: bool,
     pub message: String,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct ItemMetadata {
+    pub id: String,
+    pub updated_at: u64,
+    pub size: usize,
+    pub revision: u64,
+    #[serde(default)]
+    pub trashed_at: Option<u64>,
+    #[serde(default)]
+    pub quarantined: bool,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct ItemsResponse {
+    pub success: bool,
+    #[serde(default)]
+    pub message: Option<String>,
+    #[serde(default)]
+    pub items: Vec<ItemMetadata>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct ItemResponse {
+    pub success: bool,
+    #[serde(default)]
+    pub message: Option<String>,
+    #[serde(default)]
+    pub item: Option<VaultEntry>,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Added,
+    Updated,
+    Trashed,
+    Restored,
+    Deleted,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct HistoryChange {
+    pub revision: u64,
+    pub id: String,
+    pub timestamp: u64,
+    pub kind: ChangeKind,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct HistoryResponse {
+    pub success: bool,
+    #[serde(default)]
+    pub message: Option<String>,
+    #[serde(default)]
+    pub revision: u64,
+    #[serde(default)]
+    pub oldest_revision: u64,
     #[serde(default)]
-    pub vault: Option<Vec<VaultEntry>>,
+    pub changes: Vec<HistoryChange>,
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -19,6 +87,58 @@ pub struct CloudInfoResponse {
     pub success: bool,
     #[serde(default)]
     pub message: Option<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct KdfResponse {
+    pub success: bool,
+    #[serde(default)]
+    pub message: Option<String>,
+    #[serde(default)]
+    pub kdf: Option<KdfParams>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct ChallengeResponse {
+    pub success: bool,
+    #[serde(default)]
+    pub message: Option<String>,
+    #[serde(default)]
+    pub nonce: Option<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct LoginResponse {
+    pub success: bool,
+    #[serde(default)]
+    pub message: Option<String>,
+    #[serde(default)]
+    pub token: Option<String>,
+    #[serde(default)]
+    pub expires_at: u64,
+    #[serde(default)]
+    pub key_wrap: Option<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct SessionInfo {
+    pub id: String,
+    pub label: String,
+    pub created_at: u64,
+    pub expires_at: u64,
+    #[serde(default)]
+    pub revoked: Option<String>,
+    #[serde(default)]
+    pub current: bool,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub key_wrap: String,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct SessionsResponse {
+    pub success: bool,
+    #[serde(default)]
+    pub message: Option<String>,
     #[serde(default)]
-    pub entry_count: usize,
+    pub sessions: Vec<SessionInfo>,
 }
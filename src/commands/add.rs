@@ -1,73 +1,309 @@
-use crate::cloud::{RemoteSession, VaultEntry};
-use crate::utils::prompt_input;
-use crate::vault::{load_vault, save_vault, SecureEntry, VaultItem};
-use dialoguer::Select;
+use crate::cloud::VaultSession;
+use crate::commands::edit::{parse_assignment, parse_custom_field};
+use crate::error::Error;
+use crate::output::{self, OutputFormat};
+use crate::security::totp;
+use crate::utils::{
+    is_interactive, prompt_aborted, read_stdin_secret, require_interactive, value_or_prompt,
+    with_scheme,
+};
+use crate::vault::{
+    new_entry_id, store_entry, CustomField, ItemExtension, SecureEntry, StoredItem, VaultItem,
+};
+use anstream::println;
+use clap::{Args, ValueEnum};
+use dialoguer::{Input, Select};
 use owo_colors::OwoColorize;
+use serde_json::json;
 
-pub fn add_item(session: &RemoteSession) -> Result<(), String> {
-    let item_type = Select::new()
-        .with_prompt("What would you like to store?")
-        .items(&["Password", "Credit Card", "Secure Note"])
-        .interact()
-        .unwrap();
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ItemKind {
+    Password,
+    Card,
+    Note,
+    Identity,
+    Bank,
+    Api,
+    Wifi,
+    License,
+    Database,
+}
+
+#[derive(Args, Clone)]
+pub struct AddArgs {
+    #[arg(value_enum, value_name = "TYPE")]
+    kind: Option<ItemKind>,
+    #[arg(long)]
+    name: Option<String>,
+    #[arg(long)]
+    website: Option<String>,
+    #[arg(long)]
+    email: Option<String>,
+    #[arg(long)]
+    username: Option<String>,
+    #[arg(long)]
+    number: Option<String>,
+    #[arg(long, value_name = "MM/YY")]
+    expiration: Option<String>,
+    #[arg(long)]
+    note: Option<String>,
+    #[arg(long, value_name = "URI|SECRET")]
+    totp: Option<String>,
+    #[arg(long = "set", value_name = "FIELD=VALUE", value_parser = parse_assignment)]
+    changes: Vec<(String, String)>,
+    #[arg(long = "field", value_name = "[TYPE:]NAME=VALUE", value_parser = parse_custom_field)]
+    custom_fields: Vec<CustomField>,
+    #[arg(long)]
+    tag: Vec<String>,
+    #[arg(long)]
+    folder: Option<String>,
+    #[arg(long)]
+    password_stdin: bool,
+}
 
-    let (unique_key, vault_item) = match item_type {
-        0 => {
-            let name = prompt_input("Enter the name");
-            let mut website = prompt_input("Enter the website");
+pub fn add_item(session: &VaultSession, args: AddArgs) -> Result<(), Error> {
+    let kind = match args.kind {
+        Some(kind) => kind,
+        None => {
+            require_interactive("the item type")?;
+            let item_type = Select::new()
+                .with_prompt("What would you like to store?")
+                .items(&[
+                    "Password",
+                    "Credit Card",
+                    "Secure Note",
+                    "Identity",
+                    "Bank Account",
+                    "API Credential",
+                    "Wi-Fi Network",
+                    "Software License",
+                    "Database",
+                ])
+                .interact()
+                .map_err(prompt_aborted)?;
+            match item_type {
+                0 => ItemKind::Password,
+                1 => ItemKind::Card,
+                2 => ItemKind::Note,
+                3 => ItemKind::Identity,
+                4 => ItemKind::Bank,
+                5 => ItemKind::Api,
+                6 => ItemKind::Wifi,
+                7 => ItemKind::License,
+                _ => ItemKind::Database,
+            }
+        }
+    };
+    let secret = if args.password_stdin {
+        Some(read_stdin_secret()?)
+    } else {
+        None
+    };
 
-            if !website.starts_with("http://") && !website.starts_with("https://") {
-                website = format!("https://{}", website);
+    let mut changes = args.changes;
+    let mut take = |field: &str| {
+        changes
+            .iter()
+            .position(|(name, _)| name == field)
+            .map(|index| changes.remove(index).1)
+    };
+
+    let vault_item = match kind {
+        ItemKind::Password => {
+            let name = value_or_prompt(args.name, "Enter the name", "--name")?;
+            let website = value_or_prompt(args.website, "Enter the website", "--website")?;
+            let email = optional_or_prompt(args.email, "Enter the email")?;
+            let username = optional_or_prompt(args.username, "Enter the username")?;
+            let password = value_or_prompt(secret, "Enter the password", "--password-stdin")?;
+            let totp = optional_or_prompt(
+                args.totp,
+                "Enter the TOTP secret or otpauth:// URI (optional)",
+            )?;
+            let totp = totp::normalize(&totp, &name).map_err(Error::Usage)?;
+            VaultItem::Password {
+                name,
+                website: with_scheme(website),
+                email,
+                username,
+                password,
+                totp,
+            }
+        }
+        ItemKind::Card => {
+            let name = value_or_prompt(
+                args.name,
+                "Enter the card name (or cardholder's name)",
+                "--name",
+            )?;
+            let number = value_or_prompt(args.number, "Enter the credit card number", "--number")?;
+            let expiration_date = value_or_prompt(
+                args.expiration,
+                "Enter the expiration date (MM/YY)",
+                "--expiration",
+            )?;
+            let cvv = value_or_prompt(secret, "Enter the CVV", "--password-stdin")?;
+            VaultItem::CreditCard {
+                name,
+                number,
+                expiration_date,
+                cvv,
+            }
+        }
+        ItemKind::Note => {
+            let title = value_or_prompt(args.name, "Enter the title for the note", "--name")?;
+            let note = value_or_prompt(args.note.or(secret), "Enter your secure note", "--note")?;
+            VaultItem::SecureNote { title, note }
+        }
+        ItemKind::Identity => {
+            let name = value_or_prompt(args.name, "Enter the name", "--name")?;
+            VaultItem::Identity {
+                name,
+                full_name: optional_or_prompt(take("full_name"), "Enter the full name")?,
+                email: optional_or_prompt(args.email.or(take("email")), "Enter the email")?,
+                phone: optional_or_prompt(take("phone"), "Enter the phone number")?,
+                address: optional_or_prompt(take("address"), "Enter the address")?,
+                passport_number: optional_or_prompt(
+                    secret.or(take("passport_number")),
+                    "Enter the passport number",
+                )?,
+            }
+        }
+        ItemKind::Bank => {
+            let name = value_or_prompt(args.name, "Enter the name", "--name")?;
+            let account_holder =
+                optional_or_prompt(take("account_holder"), "Enter the account holder")?;
+            let iban = value_or_prompt(
+                args.number.or(take("iban")),
+                "Enter the IBAN or account number",
+                "--number",
+            )?;
+            let routing_number =
+                optional_or_prompt(take("routing_number"), "Enter the routing number (or BIC)")?;
+            let pin = optional_or_prompt(secret.or(take("pin")), "Enter the PIN")?;
+            VaultItem::BankAccount {
+                name,
+                account_holder,
+                iban,
+                routing_number,
+                pin,
             }
-            let email = prompt_input("Enter the email");
-            let username = prompt_input("Enter the username");
-            let password = prompt_input("Enter the password");
-            (
-                name.clone(),
-                VaultItem::Password {
-                    name,
-                    website,
-                    email,
-                    username,
-                    password,
-                },
-            )
         }
-        1 => {
-            let name = prompt_input("Enter the card name (or cardholder's name)");
-            let number = prompt_input("Enter the credit card number");
-            let expiration_date = prompt_input("Enter the expiration date (MM/YY)");
-            let cvv = prompt_input("Enter the CVV");
-            (
-                name.clone(),
-                VaultItem::CreditCard {
-                    name,
-                    number,
-                    expiration_date,
-                    cvv,
-                },
-            )
+        ItemKind::Api => {
+            let name = value_or_prompt(args.name, "Enter the name", "--name")?;
+            let key_id = optional_or_prompt(args.username.or(take("key_id")), "Enter the key ID")?;
+            let secret = value_or_prompt(
+                secret.or(take("secret")),
+                "Enter the secret",
+                "--password-stdin",
+            )?;
+            let endpoint =
+                optional_or_prompt(args.website.or(take("endpoint")), "Enter the endpoint")?;
+            VaultItem::ApiCredential {
+                name,
+                key_id,
+                secret,
+                endpoint,
+            }
         }
-        2 => {
-            let title = prompt_input("Enter the title for the note");
-            let note = prompt_input("Enter your secure note");
-            (title.clone(), VaultItem::SecureNote { title, note })
+        ItemKind::Wifi => {
+            let name = value_or_prompt(args.name, "Enter the name", "--name")?;
+            let ssid = value_or_prompt(take("ssid"), "Enter the SSID", "--set ssid=<SSID>")?;
+            let passphrase =
+                optional_or_prompt(secret.or(take("passphrase")), "Enter the passphrase")?;
+            let security =
+                optional_or_prompt(take("security"), "Enter the security type (e.g. WPA2)")?;
+            VaultItem::WifiNetwork {
+                name,
+                ssid,
+                passphrase,
+                security,
+            }
+        }
+        ItemKind::License => {
+            let name = value_or_prompt(args.name, "Enter the product name", "--name")?;
+            let version = optional_or_prompt(take("version"), "Enter the version")?;
+            let licensed_to = optional_or_prompt(
+                args.email.or(take("licensed_to")),
+                "Enter who it is licensed to",
+            )?;
+            let license_key = value_or_prompt(
+                secret.or(take("license_key")),
+                "Enter the license key",
+                "--password-stdin",
+            )?;
+            VaultItem::SoftwareLicense {
+                name,
+                version,
+                licensed_to,
+                license_key,
+            }
+        }
+        ItemKind::Database => {
+            let name = value_or_prompt(args.name, "Enter the name", "--name")?;
+            let host = value_or_prompt(take("host"), "Enter the host", "--set host=<HOST>")?;
+            let port = optional_or_prompt(take("port"), "Enter the port")?;
+            let database = optional_or_prompt(take("database"), "Enter the database name")?;
+            let username =
+                optional_or_prompt(args.username.or(take("username")), "Enter the username")?;
+            let password = optional_or_prompt(secret.or(take("password")), "Enter the password")?;
+            let connection_string = optional_or_prompt(
+                take("connection_string"),
+                "Enter the connection string (optional)",
+            )?;
+            VaultItem::Database {
+                name,
+                host,
+                port,
+                database,
+                username,
+                password,
+                connection_string,
+            }
         }
-        _ => return Ok(()),
     };
+    let vault_item = vault_item.with_fields(&changes)?;
 
-    let stored_item = SecureEntry::encrypt(session.encryption_key(), &vault_item)?;
-    let mut vault = load_vault(session)?;
+    let mut ext = ItemExtension::default();
+    for field in args.custom_fields {
+        ext.set_field(field);
+    }
+    for tag in &args.tag {
+        ext.add_tag(tag);
+    }
+    if let Some(folder) = &args.folder {
+        ext.set_folder(folder);
+    }
 
-    vault.retain(|entry| entry.key != unique_key);
+    let stored_item = SecureEntry::encrypt(
+        session.encryption_key(),
+        &StoredItem::new(vault_item.clone(), ext),
+    )?;
     let value_string = stored_item.serialize()?;
-    vault.push(VaultEntry {
-        key: unique_key.clone(),
-        value: value_string,
-    });
-
-    save_vault(session, &vault)?;
+    let id = new_entry_id();
+    store_entry(session, &id, &value_string, 0)?;
 
-    println!(" Successfully stored: {}", unique_key.green());
+    match output::format() {
+        OutputFormat::Json => output::print_json(&json!({
+            "id": id,
+            "name": vault_item.name(),
+            "type": vault_item.kind(),
+        }))?,
+        OutputFormat::Plain => println!("{}", id),
+        OutputFormat::Table => {
+            println!(" Successfully stored: {}", vault_item.name().green())
+        }
+    }
     Ok(())
 }
+
+fn optional_or_prompt(value: Option<String>, prompt: &str) -> Result<String, Error> {
+    match value {
+        Some(value) => Ok(value),
+        None if is_interactive() => Input::new()
+            .with_prompt(prompt)
+            .allow_empty(true)
+            .interact_text()
+            .map_err(prompt_aborted),
+        None => Ok(String::new()),
+    }
+}
@@ -0,0 +1,385 @@
+use crate::cloud::types::ATTACHMENT_CHUNK_SIZE;
+use crate::cloud::{CloudClientError, RemoteSession, VaultSession};
+use crate::error::Error;
+use crate::output::{self, OutputFormat};
+use crate::security::encryption::{decrypt_bytes, encrypt_bytes};
+use crate::utils::{confirm_or_yes, create_private_file, timestamp_now};
+use crate::vault::{
+    new_entry_id, open_item, select_entry, update_stored_entry, Attachment, NamedEntry,
+};
+use aes_gcm::{Aes256Gcm, Key};
+use anstream::println;
+use clap::Subcommand;
+use owo_colors::OwoColorize;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde_json::json;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Subcommand, Clone)]
+pub enum AttachmentsCommand {
+    List {
+        #[arg(value_name = "ITEM")]
+        item: String,
+    },
+    Get {
+        #[arg(value_name = "ITEM")]
+        item: String,
+        #[arg(value_name = "NAME")]
+        name: String,
+        #[arg(long, short, value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+    #[command(alias = "rm")]
+    Delete {
+        #[arg(value_name = "ITEM")]
+        item: String,
+        #[arg(value_name = "NAME")]
+        name: String,
+        #[arg(long, short)]
+        yes: bool,
+    },
+}
+
+pub fn handle_attachments_command(
+    session: &VaultSession,
+    command: AttachmentsCommand,
+) -> Result<(), Error> {
+    match command {
+        AttachmentsCommand::List { item } => list_attachments(session, &item),
+        AttachmentsCommand::Get { item, name, output } => {
+            get_attachment(session, &item, &name, output)
+        }
+        AttachmentsCommand::Delete { item, name, yes } => {
+            delete_attachment(session, &item, &name, yes)
+        }
+    }
+}
+
+pub fn attach_file(
+    session: &VaultSession,
+    item: &str,
+    file: &Path,
+    name: Option<String>,
+) -> Result<(), Error> {
+    let remote = session.remote()?;
+    let selected = select_item(session, item)?;
+    let name = match name {
+        Some(name) => name.trim().to_string(),
+        None => file
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default(),
+    };
+    if name.is_empty() {
+        return Err(Error::Usage(
+            "The attachment needs a name (pass --name)".to_string(),
+        ));
+    }
+
+    let (_, stored) = open_item(session, &selected.id)?;
+    if stored.ext.attachment(&name).is_some() {
+        return Err(attachment_exists(&selected, &name));
+    }
+
+    let mut reader =
+        File::open(file).map_err(|err| format!("Failed to read {}: {}", file.display(), err))?;
+    let mut key = [0u8; 32];
+    OsRng.fill_bytes(&mut key);
+    let mut attachment = Attachment {
+        id: new_entry_id(),
+        name,
+        size: 0,
+        chunks: 0,
+        key: hex::encode(key),
+        added_at: timestamp_now(),
+    };
+
+    let result = upload(remote, &selected.id, &mut attachment, &mut reader, file).and_then(|_| {
+        update_stored_entry(session, &selected.id, |mut current| {
+            if current.ext.attachment(&attachment.name).is_some() {
+                return Err(attachment_exists(&selected, &attachment.name));
+            }
+            current.ext.attachments.push(attachment.clone());
+            Ok(current)
+        })
+    });
+    if let Err(err) = result {
+        let _ = remote.delete_attachment(&selected.id, &attachment.id);
+        return Err(err);
+    }
+
+    match output::format() {
+        OutputFormat::Json => output::print_json(&json!({
+            "id": selected.id,
+            "name": selected.name,
+            "attachment": attachment_json(&attachment),
+        }))?,
+        OutputFormat::Plain => println!("{}", attachment.id),
+        OutputFormat::Table => println!(
+            " Attached {} ({}) to {}",
+            attachment.name.green(),
+            format_size(attachment.size),
+            selected.name.bold()
+        ),
+    }
+    Ok(())
+}
+
+fn upload(
+    remote: &RemoteSession,
+    id: &str,
+    attachment: &mut Attachment,
+    reader: &mut impl Read,
+    file: &Path,
+) -> Result<(), Error> {
+    let key = attachment_key(attachment)?;
+    loop {
+        let mut chunk = Vec::with_capacity(ATTACHMENT_CHUNK_SIZE);
+        reader
+            .by_ref()
+            .take(ATTACHMENT_CHUNK_SIZE as u64)
+            .read_to_end(&mut chunk)
+            .map_err(|err| format!("Failed to read {}: {}", file.display(), err))?;
+        let last = chunk.len() < ATTACHMENT_CHUNK_SIZE;
+        if last && chunk.is_empty() && attachment.chunks > 0 {
+            break;
+        }
+
+        let aad = chunk_aad(&attachment.id, attachment.chunks);
+        let encrypted = encrypt_bytes(&key, &chunk, aad.as_bytes());
+        remote.store_attachment_chunk(id, &attachment.id, attachment.chunks, encrypted)?;
+        attachment.size += chunk.len() as u64;
+        attachment.chunks += 1;
+        if last {
+            break;
+        }
+    }
+    Ok(())
+}
+
+fn list_attachments(session: &VaultSession, item: &str) -> Result<(), Error> {
+    let selected = select_item(session, item)?;
+    let (_, stored) = open_item(session, &selected.id)?;
+    let attachments = stored.ext.attachments;
+
+    match output::format() {
+        OutputFormat::Json => output::print_json(&json!({
+            "id": selected.id,
+            "name": selected.name,
+            "attachments": attachments.iter().map(attachment_json).collect::<Vec<_>>(),
+        })),
+        OutputFormat::Plain => {
+            for attachment in &attachments {
+                println!(
+                    "{}\t{}\t{}",
+                    attachment.name, attachment.size, attachment.id
+                );
+            }
+            Ok(())
+        }
+        OutputFormat::Table => {
+            if attachments.is_empty() {
+                println!("{} has no attachments.", selected.name);
+                return Ok(());
+            }
+            println!("Attachments of {}:", selected.name.bold().green());
+            for attachment in &attachments {
+                println!(
+                    "  {} {}",
+                    attachment.name.bold(),
+                    format_size(attachment.size).dimmed()
+                );
+            }
+            Ok(())
+        }
+    }
+}
+
+fn get_attachment(
+    session: &VaultSession,
+    item: &str,
+    name: &str,
+    output_path: Option<PathBuf>,
+) -> Result<(), Error> {
+    let remote = session.remote()?;
+    let selected = select_item(session, item)?;
+    let (_, stored) = open_item(session, &selected.id)?;
+    let attachment = stored
+        .ext
+        .attachment(name)
+        .ok_or_else(|| attachment_missing(&selected, name))?;
+    let key = attachment_key(attachment)?;
+
+    if output_path.as_deref() == Some(Path::new("-")) {
+        let mut stdout = io::stdout().lock();
+        return download(remote, &selected.id, attachment, &key, &mut stdout);
+    }
+
+    let path = match output_path {
+        Some(path) => path,
+        None => {
+            let path = PathBuf::from(
+                Path::new(&attachment.name)
+                    .file_name()
+                    .unwrap_or(attachment.id.as_ref()),
+            );
+            if path.exists() {
+                return Err(Error::Conflict(format!(
+                    "{} already exists. Pass -o to choose where to save the attachment.",
+                    path.display()
+                )));
+            }
+            path
+        }
+    };
+    let mut file = create_private_file(&path)
+        .map_err(|err| format!("Failed to write {}: {}", path.display(), err))?;
+    if let Err(err) = download(remote, &selected.id, attachment, &key, &mut file) {
+        drop(file);
+        let _ = fs::remove_file(&path);
+        return Err(err);
+    }
+
+    match output::format() {
+        OutputFormat::Json => output::print_json(&json!({
+            "id": selected.id,
+            "name": selected.name,
+            "attachment": attachment_json(attachment),
+            "path": path,
+        })),
+        OutputFormat::Plain => {
+            println!("{}", path.display());
+            Ok(())
+        }
+        OutputFormat::Table => {
+            output::success(&format!("Saved {} to {}", attachment.name, path.display()));
+            Ok(())
+        }
+    }
+}
+
+fn download(
+    remote: &RemoteSession,
+    id: &str,
+    attachment: &Attachment,
+    key: &Key<Aes256Gcm>,
+    writer: &mut impl Write,
+) -> Result<(), Error> {
+    let mut size = 0;
+    for index in 0..attachment.chunks {
+        let encrypted = remote.fetch_attachment_chunk(id, &attachment.id, index)?;
+        let chunk = decrypt_bytes(key, &encrypted, chunk_aad(&attachment.id, index).as_bytes())
+            .map_err(|err| {
+                Error::from(err).context(&format!("Attachment {} is damaged", attachment.name))
+            })?;
+        size += chunk.len() as u64;
+        writer
+            .write_all(&chunk)
+            .map_err(|err| format!("Failed to write {}: {}", attachment.name, err))?;
+    }
+    if size != attachment.size {
+        return Err(Error::Decrypt(format!(
+            "Attachment {} is incomplete ({} of {} bytes)",
+            attachment.name, size, attachment.size
+        )));
+    }
+    writer
+        .flush()
+        .map_err(|err| format!("Failed to write {}: {}", attachment.name, err))?;
+    Ok(())
+}
+
+fn delete_attachment(
+    session: &VaultSession,
+    item: &str,
+    name: &str,
+    yes: bool,
+) -> Result<(), Error> {
+    let remote = session.remote()?;
+    let selected = select_item(session, item)?;
+    let (_, stored) = open_item(session, &selected.id)?;
+    let attachment = stored
+        .ext
+        .attachment(name)
+        .cloned()
+        .ok_or_else(|| attachment_missing(&selected, name))?;
+    if !confirm_or_yes(
+        yes,
+        &format!("Delete {} from {}?", attachment.name, selected.name),
+    )? {
+        return Err(Error::Cancelled("Attachment kept.".to_string()));
+    }
+
+    update_stored_entry(session, &selected.id, |mut current| {
+        current
+            .ext
+            .attachments
+            .retain(|existing| existing.id != attachment.id);
+        Ok(current)
+    })?;
+    match remote.delete_attachment(&selected.id, &attachment.id) {
+        Ok(()) | Err(CloudClientError::NotFound(_)) => {}
+        Err(err) => return Err(err.into()),
+    }
+
+    output::success(&format!(
+        "Deleted {} from {}",
+        attachment.name, selected.name
+    ));
+    Ok(())
+}
+
+fn select_item(session: &VaultSession, item: &str) -> Result<NamedEntry, Error> {
+    select_entry(session, Some(item), "Select an item:")?
+        .ok_or_else(|| Error::NotFound(format!("No entry named `{}`.", item)))
+}
+
+fn attachment_key(attachment: &Attachment) -> Result<Key<Aes256Gcm>, Error> {
+    hex::decode(&attachment.key)
+        .ok()
+        .filter(|key| key.len() == 32)
+        .map(|key| *Key::<Aes256Gcm>::from_slice(&key))
+        .ok_or_else(|| Error::Decrypt(format!("Attachment {} has no valid key", attachment.name)))
+}
+
+fn chunk_aad(attachment: &str, index: u32) -> String {
+    format!("{}:{}", attachment, index)
+}
+
+pub fn attachment_json(attachment: &Attachment) -> serde_json::Value {
+    json!({
+        "id": attachment.id,
+        "name": attachment.name,
+        "size": attachment.size,
+        "added_at": attachment.added_at,
+    })
+}
+
+fn attachment_exists(item: &NamedEntry, name: &str) -> Error {
+    Error::Conflict(format!(
+        "{} already has an attachment named {}",
+        item.name, name
+    ))
+}
+
+fn attachment_missing(item: &NamedEntry, name: &str) -> Error {
+    Error::NotFound(format!("{} has no attachment named {}", item.name, name))
+}
+
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
@@ -1,17 +1,32 @@
-use crate::cloud::{CloudApi, CloudClientError};
+use crate::cloud::cache::VaultCache;
+use crate::cloud::types::SessionInfo;
+use crate::cloud::{CloudApi, CloudClientError, RemoteSession, VaultSession};
 use crate::config::AppConfig;
+use crate::error::Error;
+use crate::output::{self, OutputFormat};
 use crate::security::master_password;
+use crate::utils::{confirm_or_yes, is_interactive, timestamp_now};
+use crate::vault::{load_all_entries, refresh_index, upgrade_entries};
+use anstream::{eprintln, println};
 use owo_colors::OwoColorize;
 use reqwest::Url;
+use serde_json::json;
 
 const DEFAULT_STATUS_MESSAGE: &str =
     "No cloud endpoint configured. Use `x cloud <url>` to link to a host.";
 
-pub fn handle_cloud_command(config: &mut AppConfig, target: Option<String>) -> Result<(), String> {
-    if let Some(argument) = target {
-        match argument.trim() {
-            "remove" => return remove_cloud_link(config),
+pub fn handle_cloud_command(
+    config: &mut AppConfig,
+    target: Option<String>,
+    argument: Option<String>,
+    yes: bool,
+) -> Result<(), Error> {
+    if let Some(target) = target {
+        match target.trim() {
+            "remove" => return remove_cloud_link(config, yes),
             "info" => return cloud_info(config),
+            "sessions" => return list_sessions(config),
+            "revoke" => return revoke_session(config, argument),
             "register" | "join" | "push" | "pull" | "sync" => {
                 println!(
                     "{}",
@@ -21,89 +36,389 @@ pub fn handle_cloud_command(config: &mut AppConfig, target: Option<String>) -> R
                 return Ok(());
             }
             other if !other.is_empty() => {
-                return set_cloud_endpoint(config, other.to_string());
+                return set_cloud_endpoint(config, other.to_string(), yes);
             }
             _ => {}
         }
     }
 
-    show_status(config);
-    Ok(())
+    show_status(config)
+}
+
+pub fn cloud_api(config: &AppConfig) -> Result<CloudApi, CloudClientError> {
+    let base_url = config.base_url.clone().ok_or_else(|| {
+        CloudClientError::Failure(
+            "No cloud endpoint configured. Run `x cloud <url>` to connect to a host.".to_string(),
+        )
+    })?;
+    CloudApi::new(base_url, config.tls_fingerprint.clone())
+}
+
+pub fn open_session(config: &mut AppConfig) -> Result<RemoteSession, Error> {
+    if let Some(session) = resume(config)? {
+        return Ok(session);
+    }
+    let password = master_password::prompt_master_password("Enter the host master password")?;
+    Ok(login(config, password)?)
+}
+
+pub fn open_vault(config: &mut AppConfig) -> Result<VaultSession, Error> {
+    let mut password = None;
+    let connected = match resume(config) {
+        Ok(Some(session)) => Ok(session),
+        Ok(None) => {
+            let entered =
+                master_password::prompt_master_password("Enter the host master password")?;
+            password = Some(entered.clone());
+            login(config, entered)
+        }
+        Err(err) => Err(err),
+    };
+    let reason = match connected.and_then(VaultSession::online) {
+        Ok(session) => {
+            let upgraded = upgrade_entries(&session)?;
+            if upgraded > 0 {
+                eprintln!(
+                    "{}",
+                    format!(
+                        "Upgraded {} legacy entries so the host only sees IDs and ciphertext.",
+                        upgraded
+                    )
+                    .green()
+                );
+            }
+            refresh_index(&session)?;
+            return Ok(session);
+        }
+        Err(err) if err.is_unreachable() => err.to_string(),
+        Err(err) => return Err(err.into()),
+    };
+
+    let base_url = config.base_url.clone().unwrap_or_default();
+    let kdf = VaultCache::stored_kdf(&base_url).ok_or_else(|| reason.clone())?;
+    let password = match password {
+        Some(password) => password,
+        None => master_password::prompt_master_password(
+            "Host unreachable. Enter the master password to open the offline copy",
+        )?,
+    };
+    let key = kdf.derive_keys(&password)?.encryption_key;
+
+    let session =
+        VaultSession::offline(&base_url, key).map_err(|cause| format!("{}\n{}", reason, cause))?;
+    eprintln!(
+        "{}",
+        "Host unreachable. Working from the offline copy; changes will sync on the next connection."
+            .yellow()
+    );
+    Ok(session)
+}
+
+fn resume(config: &mut AppConfig) -> Result<Option<RemoteSession>, CloudClientError> {
+    let api = cloud_api(config)?;
+    let Some(stored) = config.session.clone() else {
+        return Ok(None);
+    };
+
+    match RemoteSession::resume(api, &stored) {
+        Ok(session) => Ok(Some(session)),
+        Err(
+            err @ (CloudClientError::SessionExpired
+            | CloudClientError::PasswordChanged
+            | CloudClientError::AuthenticationFailed),
+        ) => {
+            eprintln!("{}", err.to_string().red());
+            config.session = None;
+            if let Err(err) = config.save() {
+                eprintln!("Warning: failed to clear the expired session: {}", err);
+            }
+            Ok(None)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+fn login(config: &mut AppConfig, password: String) -> Result<RemoteSession, CloudClientError> {
+    let session = RemoteSession::new(cloud_api(config)?, password)?;
+    config.session = Some(session.credentials());
+    if let Err(err) = config.save() {
+        eprintln!("Warning: failed to persist session: {}", err);
+    }
+    Ok(session)
 }
 
-fn set_cloud_endpoint(config: &mut AppConfig, input: String) -> Result<(), String> {
+fn set_cloud_endpoint(config: &mut AppConfig, input: String, yes: bool) -> Result<(), Error> {
     let normalized = normalize_base_url(&input)?;
-    let api = CloudApi::new(normalized.clone()).map_err(to_message)?;
+    let fingerprint = trust_certificate(&normalized, yes)?;
+    let api = CloudApi::new(normalized.clone(), fingerprint.clone())?;
 
-    let password = master_password::prompt_master_password("Enter the host master password");
-    let auth_hash = master_password::hash_password(&password);
-    api.verify_master(&auth_hash).map_err(to_message)?;
+    let password = master_password::prompt_master_password("Enter the host master password")?;
+    let session = RemoteSession::new(api, password)?;
 
     config.base_url = Some(normalized.clone());
-    config.master_hash = Some(auth_hash);
+    config.tls_fingerprint = fingerprint;
+    config.session = Some(session.credentials());
     config
         .save()
         .map_err(|err| format!("Failed to save configuration: {}", err))?;
 
-    println!(
-        "{}",
-        format!("Cloud API endpoint set to {}.", normalized).green()
-    );
-    println!(
+    output::success(&format!("Cloud API endpoint set to {}.", normalized));
+    if output::is_table() {
+        println!(
+            "{}",
+            "Every CLI command will now operate directly against this host.".yellow()
+        );
+    }
+    Ok(())
+}
+
+fn trust_certificate(base_url: &str, yes: bool) -> Result<Option<String>, Error> {
+    if !base_url.starts_with("https://") {
+        return Ok(None);
+    }
+
+    // Certificates from a public CA need no pin; only a certificate that fails
+    // the CA check is offered for pinning.
+    let probe = CloudApi::new(base_url, None)?;
+    let fingerprint = match probe.kdf_params() {
+        Ok(_) => return Ok(None),
+        Err(CloudClientError::CertificateUntrusted(fingerprint)) => fingerprint,
+        Err(err) => return Err(err.into()),
+    };
+
+    eprintln!("The host certificate is not signed by a trusted authority.");
+    eprintln!("Host certificate fingerprint (SHA-256):");
+    eprintln!("  {}", fingerprint.bold());
+    eprintln!(
         "{}",
-        "Every CLI command will now operate directly against this host.".yellow()
+        "Compare it with the fingerprint printed by `x host --tls` before continuing.".yellow()
     );
-    Ok(())
+    if !yes && !is_interactive() {
+        return Err(Error::Auth(
+            "The host certificate was not confirmed. Pass --yes to trust it when not running in a terminal."
+                .to_string(),
+        ));
+    }
+    if !confirm_or_yes(yes, "Trust this certificate?")? {
+        return Err(Error::Cancelled(
+            "Certificate not trusted. The cloud endpoint was not changed.".to_string(),
+        ));
+    }
+    Ok(Some(fingerprint))
 }
 
-fn cloud_info(config: &mut AppConfig) -> Result<(), String> {
-    let base_url = config
-        .base_url
-        .clone()
-        .ok_or_else(|| DEFAULT_STATUS_MESSAGE.to_string())?;
+fn cloud_info(config: &mut AppConfig) -> Result<(), Error> {
+    if config.base_url.is_none() {
+        return Err(Error::Usage(DEFAULT_STATUS_MESSAGE.to_string()));
+    }
 
-    let auth_hash = ensure_master_hash(config, &base_url)?;
-    let api = CloudApi::new(base_url).map_err(to_message)?;
+    let session = open_vault(config)?;
+    let reachable = match session.remote() {
+        Ok(remote) => {
+            let info = remote.info()?;
+            if !info.success {
+                let message = info.message.unwrap_or_else(|| "Unknown error".into());
+                return Err(format!("Cloud info error: {}", message).into());
+            }
+            true
+        }
+        Err(_) => false,
+    };
+    let entries = load_all_entries(&session)?.len();
+    let pending = session.pending_changes();
+    let last_sync = session.last_sync();
 
-    let info = api.info(&auth_hash).map_err(to_message)?;
-    if info.success {
-        println!(
-            "{}",
-            format!(
-                "Cloud vault reachable. Stored entries: {}",
-                info.entry_count
-            )
-            .green()
-            .bold()
-        );
-    } else {
-        let message = info.message.unwrap_or_else(|| "Unknown error".into());
-        println!("{}", format!("Cloud info error: {}", message).red());
+    match output::format() {
+        OutputFormat::Json => output::print_json(&json!({
+            "endpoint": config.base_url,
+            "reachable": reachable,
+            "entries": entries,
+            "pending_changes": pending,
+            "last_sync": last_sync,
+        })),
+        OutputFormat::Plain => {
+            println!("endpoint\t{}", config.base_url.clone().unwrap_or_default());
+            println!("reachable\t{}", reachable);
+            println!("entries\t{}", entries);
+            println!("pending_changes\t{}", pending);
+            println!(
+                "last_sync\t{}",
+                last_sync.map(|ts| ts.to_string()).unwrap_or_default()
+            );
+            Ok(())
+        }
+        OutputFormat::Table => {
+            if reachable {
+                println!(
+                    "{}",
+                    format!("Cloud vault reachable. Stored entries: {}", entries)
+                        .green()
+                        .bold()
+                );
+            } else {
+                println!("{}", "Cloud vault unreachable (offline).".red().bold());
+            }
+
+            let pending_msg = format!("{}", pending);
+            println!(
+                "Pending offline changes: {}",
+                if pending > 0 {
+                    pending_msg.yellow().to_string()
+                } else {
+                    pending_msg
+                }
+            );
+            match last_sync {
+                Some(synced_at) => println!("Last sync: {}", format_elapsed(synced_at)),
+                None => println!("Last sync: never"),
+            }
+            Ok(())
+        }
+    }
+}
+
+fn list_sessions(config: &mut AppConfig) -> Result<(), Error> {
+    let session = open_session(config)?;
+    let sessions = session.sessions()?;
+
+    match output::format() {
+        OutputFormat::Json => {
+            let items: Vec<_> = sessions
+                .iter()
+                .map(|info| {
+                    json!({
+                        "id": info.id,
+                        "label": info.label,
+                        "created_at": info.created_at,
+                        "expires_at": info.expires_at,
+                        "state": session_state(info),
+                        "revoked": info.revoked,
+                        "current": info.current,
+                    })
+                })
+                .collect();
+            return output::print_json(&items);
+        }
+        OutputFormat::Plain => {
+            for info in &sessions {
+                println!(
+                    "{}\t{}\t{}\t{}\t{}",
+                    info.id,
+                    info.label,
+                    session_state(info),
+                    info.expires_at,
+                    if info.current { "current" } else { "" }
+                );
+            }
+            return Ok(());
+        }
+        OutputFormat::Table => {}
+    }
+
+    println!("{}", "Sessions:".yellow().bold());
+    for info in sessions {
+        println!("  {}", describe_session(&info));
     }
     Ok(())
 }
 
-fn remove_cloud_link(config: &mut AppConfig) -> Result<(), String> {
+fn revoke_session(config: &mut AppConfig, argument: Option<String>) -> Result<(), Error> {
+    let id = argument
+        .map(|id| id.trim().to_string())
+        .filter(|id| !id.is_empty())
+        .ok_or_else(|| Error::Usage("Usage: x cloud revoke <session id|all>".to_string()))?;
+
+    let session = open_session(config)?;
+    let message = session.revoke_session(&id)?;
+    output::success(&message);
+    Ok(())
+}
+
+fn remove_cloud_link(config: &mut AppConfig, yes: bool) -> Result<(), Error> {
+    let resumed = match (cloud_api(config), config.session.clone()) {
+        (Ok(api), Some(stored)) => RemoteSession::resume(api, &stored).ok(),
+        _ => None,
+    };
+    let discard_prompt = match (&config.base_url, &resumed) {
+        (Some(base_url), Some(session)) => VaultCache::open(base_url, session.encryption_key())
+            .map(|cache| cache.pending.len())
+            .ok()
+            .filter(|pending| *pending > 0)
+            .map(|pending| {
+                format!(
+                    "{} offline change(s) have not reached the host yet. Discard them?",
+                    pending
+                )
+            }),
+        (Some(_), None) if VaultCache::exists() => Some(
+            "The host is unreachable, so queued offline changes cannot be checked. Discard the offline copy?"
+                .to_string(),
+        ),
+        _ => None,
+    };
+    if let Some(prompt) = discard_prompt {
+        if !confirm_or_yes(yes, &prompt)? {
+            return Err(Error::Cancelled(
+                "Cloud endpoint kept. Reconnect to the host to sync first.".to_string(),
+            ));
+        }
+    }
+
+    if let Some(session) = resumed {
+        let _ = session.logout();
+    }
+
+    if let Err(err) = VaultCache::remove() {
+        eprintln!("Warning: failed to remove the offline vault copy: {}", err);
+    }
+
     let previous_endpoint = config.base_url.take();
-    config.master_hash = None;
+    config.session = None;
+    config.tls_fingerprint = None;
     config
         .save()
         .map_err(|err| format!("Failed to save configuration: {}", err))?;
 
     match previous_endpoint {
-        Some(endpoint) => {
-            println!(
-                "{}",
-                format!("Removed cloud endpoint {}.", endpoint).green()
-            );
-        }
+        Some(endpoint) => output::success(&format!("Removed cloud endpoint {}.", endpoint)),
         None => println!("{}", "No linked cloud endpoint to remove.".yellow()),
     }
 
     Ok(())
 }
 
-fn show_status(config: &AppConfig) {
+fn show_status(config: &AppConfig) -> Result<(), Error> {
+    let expires_at = config.session.as_ref().map(|session| session.expires_at);
+    let state = match expires_at {
+        Some(expires_at) if expires_at > timestamp_now() => "active",
+        Some(_) => "expired",
+        None => "none",
+    };
+    match output::format() {
+        OutputFormat::Json => {
+            return output::print_json(&json!({
+                "endpoint": config.base_url,
+                "session": state,
+                "session_expires_at": expires_at,
+                "tls_fingerprint": config.tls_fingerprint,
+            }))
+        }
+        OutputFormat::Plain => {
+            println!("endpoint\t{}", config.base_url.clone().unwrap_or_default());
+            println!("session\t{}", state);
+            println!(
+                "session_expires_at\t{}",
+                expires_at.map(|ts| ts.to_string()).unwrap_or_default()
+            );
+            println!(
+                "tls_fingerprint\t{}",
+                config.tls_fingerprint.clone().unwrap_or_default()
+            );
+            return Ok(());
+        }
+        OutputFormat::Table => {}
+    }
+
     println!("{}", "Cloud status".green().bold());
     match &config.base_url {
         Some(url) => println!("Endpoint: {}", url),
@@ -113,49 +428,80 @@ fn show_status(config: &AppConfig) {
         }
     }
 
-    let stored_msg = if config.master_hash.is_some() {
-        "yes".green().to_string()
-    } else {
-        "no".red().to_string()
+    let session_msg = match &config.session {
+        Some(session) if session.expires_at > timestamp_now() => format!(
+            "active, expires in {}",
+            format_remaining(session.expires_at)
+        )
+        .green()
+        .to_string(),
+        Some(_) => "expired".red().to_string(),
+        None => "none".red().to_string(),
     };
-    println!("Master password stored: {}", stored_msg);
+    println!("Session: {}", session_msg);
+    if let Some(fingerprint) = &config.tls_fingerprint {
+        println!("Pinned certificate: {}", fingerprint);
+    }
 
     println!();
     println!("Commands:");
-    println!("  x cloud <url>    Set or change the cloud endpoint");
-    println!("  x cloud info     Show vault statistics");
-    println!("  x cloud remove   Unlink from the cloud endpoint");
+    println!("  x cloud <url>          Set or change the cloud endpoint");
+    println!("  x cloud info           Show vault statistics and offline sync state");
+    println!("  x cloud sessions       List active sessions on the host");
+    println!("  x cloud revoke <id>    Revoke a session (or `all`)");
+    println!("  x cloud remove         Log out and unlink from the cloud endpoint");
+    Ok(())
 }
 
-fn ensure_master_hash(config: &mut AppConfig, base_url: &str) -> Result<String, String> {
-    if let Some(hash) = config.master_hash.clone() {
-        return Ok(hash);
+fn describe_session(info: &SessionInfo) -> String {
+    let state = match &info.revoked {
+        Some(reason) => format!("revoked ({})", reason).red().to_string(),
+        None if info.expires_at <= timestamp_now() => "expired".red().to_string(),
+        None => format!("expires in {}", format_remaining(info.expires_at))
+            .green()
+            .to_string(),
+    };
+    let marker = if info.current { " (this machine)" } else { "" };
+    format!("{}  {}{}  {}", info.id.bold(), info.label, marker, state)
+}
+
+fn session_state(info: &SessionInfo) -> &'static str {
+    match &info.revoked {
+        Some(_) => "revoked",
+        None if info.expires_at <= timestamp_now() => "expired",
+        None => "active",
     }
+}
 
-    let password = master_password::prompt_master_password("Enter the host master password");
-    let auth_hash = master_password::hash_password(&password);
-    let api = CloudApi::new(base_url.to_string()).map_err(to_message)?;
-    api.verify_master(&auth_hash).map_err(to_message)?;
+fn format_remaining(expires_at: u64) -> String {
+    let remaining = expires_at.saturating_sub(timestamp_now());
+    format!("{}h {}m", remaining / 3600, (remaining % 3600) / 60)
+}
 
-    config.master_hash = Some(auth_hash.clone());
-    config
-        .save()
-        .map_err(|err| format!("Failed to save configuration: {}", err))?;
-    Ok(auth_hash)
+fn format_elapsed(timestamp: u64) -> String {
+    let elapsed = timestamp_now().saturating_sub(timestamp);
+    match elapsed {
+        0..=59 => "just now".to_string(),
+        60..=3599 => format!("{}m ago", elapsed / 60),
+        3600..=86399 => format!("{}h {}m ago", elapsed / 3600, (elapsed % 3600) / 60),
+        _ => format!("{}d ago", elapsed / 86400),
+    }
 }
 
-fn normalize_base_url(input: &str) -> Result<String, String> {
+fn normalize_base_url(input: &str) -> Result<String, Error> {
     let trimmed = input.trim();
     if trimmed.is_empty() {
-        return Err("Cloud URL cannot be empty".to_string());
+        return Err(Error::Usage("Cloud URL cannot be empty".to_string()));
     }
 
     let parsed = Url::parse(trimmed)
         .or_else(|_| Url::parse(&format!("http://{}", trimmed)))
-        .map_err(|err| format!("Invalid URL: {}", err))?;
+        .map_err(|err| Error::Usage(format!("Invalid URL: {}", err)))?;
 
     if parsed.scheme() != "http" && parsed.scheme() != "https" {
-        return Err("Cloud URL must start with http:// or https://".to_string());
+        return Err(Error::Usage(
+            "Cloud URL must start with http:// or https://".to_string(),
+        ));
     }
 
     let mut normalized = parsed;
@@ -170,14 +516,3 @@ fn normalize_base_url(input: &str) -> Result<String, String> {
 
     Ok(final_url)
 }
-
-fn to_message(error: CloudClientError) -> String {
-    match error {
-        CloudClientError::Http(err) => format!("Network error: {}", err),
-        CloudClientError::Failure(msg) => msg,
-        CloudClientError::AuthenticationFailed => AUTH_FAILURE.to_string(),
-    }
-}
-
-const AUTH_FAILURE: &str =
-    "Authentication failed. Verify the master password and host configuration.";
@@ -1,36 +1,19 @@
-use crate::cloud::RemoteSession;
-use crate::vault::{load_vault, save_vault};
-use dialoguer::Select;
-use owo_colors::OwoColorize;
-
-pub fn delete_item(session: &RemoteSession) -> Result<(), String> {
-    let mut vault = load_vault(session)?;
-
-    let keys: Vec<String> = vault.iter().map(|entry| entry.key.clone()).collect();
-
-    if keys.is_empty() {
-        println!("{}", "No items found in the vault.".red());
-        return Ok(());
-    }
-
-    let selection = Select::new()
-        .with_prompt("Select item to delete")
-        .items(&keys)
-        .default(0)
-        .interact()
-        .unwrap();
-
-    let item_name = &keys[selection];
-    let initial_len = vault.len();
-    vault.retain(|entry| &entry.key != item_name);
-
-    if vault.len() == initial_len {
-        println!("{}", "Item not found!".red());
+use crate::cloud::VaultSession;
+use crate::error::Error;
+use crate::output;
+use crate::utils::confirm_or_yes;
+use crate::vault::{delete_entry, select_entry};
+
+pub fn delete_item(session: &VaultSession, name: Option<String>, yes: bool) -> Result<(), Error> {
+    let Some(entry) = select_entry(session, name.as_deref(), "Select item to delete")? else {
         return Ok(());
+    };
+    if !confirm_or_yes(yes, &format!("Move {} to the trash?", entry.name))? {
+        return Err(Error::Cancelled("Item kept.".to_string()));
     }
 
-    save_vault(session, &vault)?;
+    delete_entry(session, &entry.id, entry.revision)?;
 
-    println!("Deleted: {}", item_name.red());
+    output::success(&format!("Moved to trash: {}", entry.name));
     Ok(())
 }
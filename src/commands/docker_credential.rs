@@ -0,0 +1,167 @@
+use crate::cloud::VaultSession;
+use crate::error::Error;
+use crate::vault::{
+    delete_entry, list_named_entries, new_entry_id, open_item, store_entry, update_entry,
+    ItemExtension, SecureEntry, StoredItem, VaultItem,
+};
+use anstream::println;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::{self, Read};
+
+const REGISTRY_TAG: &str = "docker-registry";
+const CREDENTIALS_NOT_FOUND: &str = "credentials not found in native keychain";
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Credentials {
+    #[serde(rename = "ServerURL")]
+    server_url: String,
+    username: String,
+    secret: String,
+}
+
+struct Registry {
+    id: String,
+    revision: u64,
+    server_url: String,
+    username: String,
+    secret: String,
+}
+
+pub fn handle_docker_credential(session: &VaultSession, action: &str) -> Result<(), Error> {
+    match action {
+        "get" => get_credential(session),
+        "store" => store_credential(session),
+        "erase" => erase_credential(session),
+        "list" => list_credentials(session),
+        other => Err(Error::Usage(format!(
+            "Unknown docker-credential action `{}`. Use get, store, erase or list.",
+            other
+        ))),
+    }
+}
+
+fn get_credential(session: &VaultSession) -> Result<(), Error> {
+    let server_url = read_input()?.trim().to_string();
+    let Some(registry) = find_registry(session, &server_url)? else {
+        println!("{}", CREDENTIALS_NOT_FOUND);
+        return Err(Error::NotFound(CREDENTIALS_NOT_FOUND.to_string()));
+    };
+
+    print_credentials(&Credentials {
+        server_url,
+        username: registry.username,
+        secret: registry.secret,
+    })
+}
+
+fn store_credential(session: &VaultSession) -> Result<(), Error> {
+    let credentials: Credentials = serde_json::from_str(&read_input()?)
+        .map_err(|err| Error::Usage(format!("Invalid credentials: {}", err)))?;
+
+    if let Some(registry) = find_registry(session, &credentials.server_url)? {
+        let changes = [
+            ("username".to_string(), credentials.username.clone()),
+            ("password".to_string(), credentials.secret.clone()),
+        ];
+        return update_entry(session, &registry.id, |current| {
+            current.with_fields(&changes)
+        });
+    }
+
+    let item = VaultItem::Password {
+        name: format!("Docker {}", registry_key(&credentials.server_url)),
+        website: credentials.server_url,
+        email: String::new(),
+        username: credentials.username,
+        password: credentials.secret,
+        totp: String::new(),
+    };
+    let mut ext = ItemExtension::default();
+    ext.add_tag(REGISTRY_TAG);
+    let value =
+        SecureEntry::encrypt(session.encryption_key(), &StoredItem::new(item, ext))?.serialize()?;
+    store_entry(session, &new_entry_id(), &value, 0)
+}
+
+fn erase_credential(session: &VaultSession) -> Result<(), Error> {
+    let server_url = read_input()?;
+    match find_registry(session, server_url.trim())? {
+        Some(registry) => delete_entry(session, &registry.id, registry.revision),
+        None => Ok(()),
+    }
+}
+
+fn list_credentials(session: &VaultSession) -> Result<(), Error> {
+    let logins: BTreeMap<_, _> = registries(session)?
+        .into_iter()
+        .map(|registry| (registry.server_url, registry.username))
+        .collect();
+    let json = serde_json::to_string(&logins)
+        .map_err(|err| format!("Failed to encode the registry list: {}", err))?;
+    println!("{}", json);
+    Ok(())
+}
+
+// Registry logins are recognised by their tag and matched on the website
+// field, so renaming an entry keeps it working and an unrelated item whose
+// name happens to look like a registry is never handed to Docker.
+fn registries(session: &VaultSession) -> Result<Vec<Registry>, Error> {
+    let mut registries = Vec::new();
+    for entry in list_named_entries(session)? {
+        if !entry.has_tag(REGISTRY_TAG) {
+            continue;
+        }
+        let (current, stored) = open_item(session, &entry.id)?;
+        if let VaultItem::Password {
+            website,
+            username,
+            password,
+            ..
+        } = stored.item
+        {
+            if !website.trim().is_empty() {
+                registries.push(Registry {
+                    id: entry.id,
+                    revision: current.revision,
+                    server_url: website,
+                    username,
+                    secret: password,
+                });
+            }
+        }
+    }
+    Ok(registries)
+}
+
+fn find_registry(session: &VaultSession, server_url: &str) -> Result<Option<Registry>, Error> {
+    let wanted = registry_key(server_url);
+    Ok(registries(session)?
+        .into_iter()
+        .find(|registry| registry_key(&registry.server_url) == wanted))
+}
+
+fn registry_key(server_url: &str) -> String {
+    let url = server_url.trim();
+    let url = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+        .unwrap_or(url);
+    url.trim_end_matches('/').to_lowercase()
+}
+
+fn print_credentials(credentials: &Credentials) -> Result<(), Error> {
+    let json = serde_json::to_string(credentials)
+        .map_err(|err| format!("Failed to encode the credentials: {}", err))?;
+    println!("{}", json);
+    Ok(())
+}
+
+fn read_input() -> Result<String, Error> {
+    let mut input = String::new();
+    io::stdin()
+        .read_to_string(&mut input)
+        .map_err(|err| format!("Failed to read the credential request: {}", err))?;
+    Ok(input)
+}
@@ -1,47 +1,140 @@
-use crate::cloud::RemoteSession;
-use crate::vault::{load_vault, save_vault, SecureEntry, VaultItem};
-use dialoguer::Select;
-use owo_colors::OwoColorize;
+use crate::cloud::VaultSession;
+use crate::error::Error;
+use crate::output;
+use crate::security::totp;
+use crate::utils::{prompt_aborted, require_interactive, with_scheme};
+use crate::vault::{
+    merge_changes, open_item, select_entry, update_stored_entry, CustomField, CustomFieldKind,
+    ItemExtension, StoredItem, VaultItem,
+};
+use clap::{Args, ValueEnum};
+use dialoguer::Password;
 use std::io::{self, Write};
 
-fn prompt_with_default(prompt: &str, default: &str) -> String {
+fn prompt_with_default(prompt: &str, default: &str) -> Result<String, Error> {
     print!("{} [{}]: ", prompt, default);
-    io::stdout().flush().unwrap();
+    io::stdout().flush().map_err(prompt_aborted)?;
     let mut input = String::new();
-    io::stdin().read_line(&mut input).unwrap();
+    io::stdin().read_line(&mut input).map_err(prompt_aborted)?;
     let input = input.trim();
     if input.is_empty() {
-        default.to_string()
+        Ok(default.to_string())
     } else {
-        input.to_string()
+        Ok(input.to_string())
     }
 }
 
-pub fn edit_item(session: &RemoteSession) -> Result<(), String> {
-    let mut vault = load_vault(session)?;
+// Secret values are never echoed as a default; an empty entry keeps them.
+fn prompt_secret(prompt: &str, current: &str) -> Result<String, Error> {
+    let prompt = if current.is_empty() {
+        prompt.to_string()
+    } else {
+        format!("{} (leave empty to keep the current one)", prompt)
+    };
+    let input = Password::new()
+        .with_prompt(prompt)
+        .allow_empty_password(true)
+        .interact()
+        .map_err(prompt_aborted)?;
+    if input.is_empty() {
+        Ok(current.to_string())
+    } else {
+        Ok(input)
+    }
+}
+
+pub fn parse_assignment(input: &str) -> Result<(String, String), String> {
+    input
+        .split_once('=')
+        .map(|(field, value)| (field.trim().to_string(), value.to_string()))
+        .filter(|(field, _)| !field.is_empty())
+        .ok_or_else(|| format!("Expected FIELD=VALUE, got `{}`", input))
+}
 
-    let keys: Vec<String> = vault.iter().map(|entry| entry.key.clone()).collect();
+pub fn parse_custom_field(input: &str) -> Result<CustomField, String> {
+    let (field, value) = parse_assignment(input)?;
+    let (kind, name) = match field.split_once(':') {
+        Some((kind, name)) => match CustomFieldKind::from_str(kind.trim(), true) {
+            Ok(kind) => (kind, name),
+            Err(_) => (CustomFieldKind::Text, field.as_str()),
+        },
+        None => (CustomFieldKind::Text, field.as_str()),
+    };
+    CustomField::new(kind, name, &value).map_err(|err| err.message().to_string())
+}
 
-    if keys.is_empty() {
-        println!("{}", "No items found in the vault.".red());
-        return Ok(());
+#[derive(Args, Clone)]
+pub struct EditArgs {
+    #[arg(value_name = "NAME")]
+    name: Option<String>,
+    #[arg(long = "set", value_name = "FIELD=VALUE", value_parser = parse_assignment)]
+    changes: Vec<(String, String)>,
+    #[arg(long = "field", value_name = "[TYPE:]NAME=VALUE", value_parser = parse_custom_field)]
+    custom_fields: Vec<CustomField>,
+    #[arg(long, value_name = "NAME")]
+    remove_field: Vec<String>,
+    #[arg(long)]
+    tag: Vec<String>,
+    #[arg(long)]
+    untag: Vec<String>,
+    #[arg(long)]
+    folder: Option<String>,
+}
+
+impl EditArgs {
+    fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+            && self.custom_fields.is_empty()
+            && self.remove_field.is_empty()
+            && self.tag.is_empty()
+            && self.untag.is_empty()
+            && self.folder.is_none()
     }
+}
 
-    let selection = Select::new()
-        .with_prompt("Select item to update")
-        .items(&keys)
-        .default(0)
-        .interact()
-        .unwrap();
+pub fn edit_item(session: &VaultSession, args: EditArgs) -> Result<(), Error> {
+    let Some(selected) = select_entry(session, args.name.as_deref(), "Select item to update")?
+    else {
+        return Ok(());
+    };
 
-    let item_key = &keys[selection];
+    let (_, original) = open_item(session, &selected.id)?;
+    let edited = if args.is_empty() {
+        require_interactive("--set FIELD=VALUE")?;
+        StoredItem::new(
+            prompt_changes(&original.item)?,
+            prompt_extension(&original.ext)?,
+        )
+    } else {
+        let mut ext = original.ext.clone();
+        for field in args.custom_fields {
+            ext.set_field(field);
+        }
+        for name in &args.remove_field {
+            ext.remove_field(name)?;
+        }
+        for tag in &args.tag {
+            ext.add_tag(tag);
+        }
+        for tag in &args.untag {
+            ext.remove_tag(tag);
+        }
+        if let Some(folder) = &args.folder {
+            ext.set_folder(folder);
+        }
+        StoredItem::new(original.item.with_fields(&args.changes)?, ext)
+    };
 
-    let entry = vault
-        .iter_mut()
-        .find(|entry| &entry.key == item_key)
-        .ok_or_else(|| "Item not found in vault.".to_string())?;
+    update_stored_entry(session, &selected.id, |current| {
+        merge_changes(&original, &edited, current)
+    })?;
 
-    let mut vault_item = SecureEntry::decrypt(session.encryption_key(), &entry.value)?;
+    output::success(&format!("Successfully updated: {}", edited.item.name()));
+    Ok(())
+}
+
+fn prompt_changes(original: &VaultItem) -> Result<VaultItem, Error> {
+    let mut vault_item = original.clone();
 
     match &mut vault_item {
         VaultItem::Password {
@@ -50,17 +143,14 @@ pub fn edit_item(session: &RemoteSession) -> Result<(), String> {
             email,
             username,
             password,
+            totp,
         } => {
-            *name = prompt_with_default("Enter the name", name);
-            let mut new_website = prompt_with_default("Enter the website", website);
-
-            if !new_website.starts_with("http://") && !new_website.starts_with("https://") {
-                new_website = format!("https://{}", new_website);
-            }
-            *website = new_website;
-            *email = prompt_with_default("Enter the email", email);
-            *username = prompt_with_default("Enter the username", username);
-            *password = prompt_with_default("Enter the password", password);
+            *name = prompt_with_default("Enter the name", name)?;
+            *website = with_scheme(prompt_with_default("Enter the website", website)?);
+            *email = prompt_with_default("Enter the email", email)?;
+            *username = prompt_with_default("Enter the username", username)?;
+            *password = prompt_secret("Enter the password", password)?;
+            *totp = prompt_totp(totp, name)?;
         }
         VaultItem::CreditCard {
             name,
@@ -68,23 +158,130 @@ pub fn edit_item(session: &RemoteSession) -> Result<(), String> {
             expiration_date,
             cvv,
         } => {
-            *name = prompt_with_default("Enter the card name (or cardholder's name)", name);
-            *number = prompt_with_default("Enter the credit card number", number);
+            *name = prompt_with_default("Enter the card name (or cardholder's name)", name)?;
+            *number = prompt_with_default("Enter the credit card number", number)?;
             *expiration_date =
-                prompt_with_default("Enter the expiration date (MM/YY)", expiration_date);
-            *cvv = prompt_with_default("Enter the CVV", cvv);
+                prompt_with_default("Enter the expiration date (MM/YY)", expiration_date)?;
+            *cvv = prompt_secret("Enter the CVV", cvv)?;
         }
         VaultItem::SecureNote { title, note } => {
-            *title = prompt_with_default("Enter the title for the note", title);
-            *note = prompt_with_default("Enter your secure note", note);
+            *title = prompt_with_default("Enter the title for the note", title)?;
+            *note = prompt_with_default("Enter your secure note", note)?;
+        }
+        VaultItem::SshKey { name, comment, .. } => {
+            *name = prompt_with_default("Enter the name", name)?;
+            *comment = prompt_with_default("Enter the comment", comment)?;
+        }
+        VaultItem::Identity {
+            name,
+            full_name,
+            email,
+            phone,
+            address,
+            passport_number,
+        } => {
+            *name = prompt_with_default("Enter the name", name)?;
+            *full_name = prompt_with_default("Enter the full name", full_name)?;
+            *email = prompt_with_default("Enter the email", email)?;
+            *phone = prompt_with_default("Enter the phone number", phone)?;
+            *address = prompt_with_default("Enter the address", address)?;
+            *passport_number = prompt_secret("Enter the passport number", passport_number)?;
+        }
+        VaultItem::BankAccount {
+            name,
+            account_holder,
+            iban,
+            routing_number,
+            pin,
+        } => {
+            *name = prompt_with_default("Enter the name", name)?;
+            *account_holder = prompt_with_default("Enter the account holder", account_holder)?;
+            *iban = prompt_with_default("Enter the IBAN or account number", iban)?;
+            *routing_number =
+                prompt_with_default("Enter the routing number (or BIC)", routing_number)?;
+            *pin = prompt_secret("Enter the PIN", pin)?;
+        }
+        VaultItem::ApiCredential {
+            name,
+            key_id,
+            secret,
+            endpoint,
+        } => {
+            *name = prompt_with_default("Enter the name", name)?;
+            *key_id = prompt_with_default("Enter the key ID", key_id)?;
+            *secret = prompt_secret("Enter the secret", secret)?;
+            *endpoint = prompt_with_default("Enter the endpoint", endpoint)?;
+        }
+        VaultItem::WifiNetwork {
+            name,
+            ssid,
+            passphrase,
+            security,
+        } => {
+            *name = prompt_with_default("Enter the name", name)?;
+            *ssid = prompt_with_default("Enter the SSID", ssid)?;
+            *passphrase = prompt_secret("Enter the passphrase", passphrase)?;
+            *security = prompt_with_default("Enter the security type", security)?;
+        }
+        VaultItem::SoftwareLicense {
+            name,
+            version,
+            licensed_to,
+            license_key,
+        } => {
+            *name = prompt_with_default("Enter the product name", name)?;
+            *version = prompt_with_default("Enter the version", version)?;
+            *licensed_to = prompt_with_default("Enter who it is licensed to", licensed_to)?;
+            *license_key = prompt_secret("Enter the license key", license_key)?;
+        }
+        VaultItem::Database {
+            name,
+            host,
+            port,
+            database,
+            username,
+            password,
+            connection_string,
+        } => {
+            *name = prompt_with_default("Enter the name", name)?;
+            *host = prompt_with_default("Enter the host", host)?;
+            *port = prompt_with_default("Enter the port", port)?;
+            *database = prompt_with_default("Enter the database name", database)?;
+            *username = prompt_with_default("Enter the username", username)?;
+            *password = prompt_secret("Enter the password", password)?;
+            *connection_string = prompt_secret("Enter the connection string", connection_string)?;
         }
     }
 
-    let updated_item = SecureEntry::encrypt(session.encryption_key(), &vault_item)?;
-    entry.value = updated_item.serialize()?;
+    Ok(vault_item)
+}
 
-    save_vault(session, &vault)?;
+fn prompt_totp(current: &str, name: &str) -> Result<String, Error> {
+    let shown = if current.is_empty() { "none" } else { "keep" };
+    let input = prompt_with_default(
+        "Enter the TOTP secret or otpauth:// URI (- to remove)",
+        shown,
+    )?;
+    match input.as_str() {
+        "-" => Ok(String::new()),
+        input if input == shown => Ok(current.to_string()),
+        input => totp::normalize(input, name).map_err(Error::Usage),
+    }
+}
 
-    println!("Successfully updated: {}", item_key.green());
-    Ok(())
+fn prompt_extension(original: &ItemExtension) -> Result<ItemExtension, Error> {
+    let mut ext = original.clone();
+    let tags = prompt_with_default(
+        "Enter tags, separated by commas (- to clear)",
+        &ext.tags.join(", "),
+    )?;
+    ext.tags.clear();
+    if tags != "-" {
+        for tag in tags.split(',') {
+            ext.add_tag(tag);
+        }
+    }
+    let folder = prompt_with_default("Enter the folder (- to clear)", &ext.folder)?;
+    ext.set_folder(if folder == "-" { "" } else { &folder });
+    Ok(ext)
 }
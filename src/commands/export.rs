@@ -1,5 +1,9 @@
-use crate::cloud::RemoteSession;
-use crate::vault::load_vault;
+use crate::cloud::VaultSession;
+use crate::error::Error;
+use crate::output;
+use crate::utils::{prompt_aborted, require_interactive};
+use crate::vault::load_all_entries;
+use anstream::println;
 use dialoguer::Input;
 use owo_colors::OwoColorize;
 use serde::{Deserialize, Serialize};
@@ -12,16 +16,24 @@ struct ExportItem {
     value: String,
 }
 
-pub fn export_items(session: &RemoteSession) -> Result<(), String> {
-    println!("{}", "Export Vault Items".yellow().bold());
+pub fn export_items(session: &VaultSession, file: Option<String>) -> Result<(), Error> {
+    if output::is_table() {
+        println!("{}", "Export Vault Items".yellow().bold());
+    }
 
-    let file_path: String = Input::new()
-        .with_prompt("Enter file path to export vault items to")
-        .default("x_export.json".into())
-        .interact_text()
-        .unwrap();
+    let file_path: String = match file {
+        Some(file) => file,
+        None => {
+            require_interactive("--file")?;
+            Input::new()
+                .with_prompt("Enter file path to export vault items to")
+                .default("x_export.json".into())
+                .interact_text()
+                .map_err(prompt_aborted)?
+        }
+    };
 
-    let export_items: Vec<ExportItem> = load_vault(session)?
+    let export_items: Vec<ExportItem> = load_all_entries(session)?
         .into_iter()
         .map(|entry| ExportItem {
             key: entry.key,
@@ -37,9 +49,10 @@ pub fn export_items(session: &RemoteSession) -> Result<(), String> {
     file.write_all(json.as_bytes())
         .map_err(|err| format!("Failed to write to file {}: {}", file_path, err))?;
 
-    println!(
-        "{}",
-        format!("Exported {} items to {}", export_items.len(), file_path).green()
-    );
+    output::success(&format!(
+        "Exported {} items to {}",
+        export_items.len(),
+        file_path
+    ));
     Ok(())
 }
@@ -0,0 +1,92 @@
+use crate::cloud::{VaultEntry, VaultSession};
+use crate::error::Error;
+use crate::output::{self, OutputFormat};
+use crate::utils::{confirm_or_yes, is_interactive};
+use crate::vault::{load_all_entries, quarantine_entry, short_id, SecureEntry};
+use anstream::println;
+use owo_colors::OwoColorize;
+use serde_json::json;
+
+pub fn check_vault(session: &VaultSession, yes: bool) -> Result<(), Error> {
+    let entries = load_all_entries(session)?;
+    let problems: Vec<(&VaultEntry, String)> = entries
+        .iter()
+        .filter_map(|entry| {
+            SecureEntry::decrypt(session.encryption_key(), &entry.value)
+                .err()
+                .map(|err| (entry, err.message().to_string()))
+        })
+        .collect();
+
+    match output::format() {
+        OutputFormat::Json => {}
+        OutputFormat::Plain => {
+            for (entry, error) in &problems {
+                println!("{}\t{}", entry.key, error);
+            }
+        }
+        OutputFormat::Table => {
+            println!("Checked {} entries.", entries.len());
+            for (entry, error) in &problems {
+                println!("  - {}  {}", short_id(&entry.key).bold().red(), error);
+            }
+        }
+    }
+
+    let quarantined = if problems.is_empty() {
+        Ok(0)
+    } else {
+        quarantine(session, &problems, yes)
+    };
+    if output::is_json() {
+        let items: Vec<_> = problems
+            .iter()
+            .map(|(entry, error)| json!({ "id": entry.key, "error": error }))
+            .collect();
+        output::print_json(&json!({
+            "checked": entries.len(),
+            "problems": items,
+            "quarantined": quarantined.as_ref().map_or(0, |count| *count),
+        }))?;
+        return quarantined.map(|_| ());
+    }
+
+    match quarantined? {
+        0 => output::success("Every entry decrypted successfully."),
+        count => output::success(&format!(
+            "Quarantined {} entries. They stay in the trash, exempt from automatic cleanup, until `x trash restore <id>` brings one back or `x trash empty <id>` deletes it.",
+            count
+        )),
+    }
+    Ok(())
+}
+
+fn quarantine(
+    session: &VaultSession,
+    problems: &[(&VaultEntry, String)],
+    yes: bool,
+) -> Result<usize, Error> {
+    if !yes && !is_interactive() {
+        return Err(Error::Decrypt(format!(
+            "{} entries could not be read. Run `x fsck --yes` to move them to the trash.",
+            problems.len()
+        )));
+    }
+    session.remote()?;
+    if !confirm_or_yes(
+        yes,
+        &format!(
+            "Move {} unreadable entries to the trash for quarantine?",
+            problems.len()
+        ),
+    )? {
+        return Err(Error::Cancelled(
+            "Unreadable entries were left in place.".to_string(),
+        ));
+    }
+
+    for (entry, _) in problems {
+        quarantine_entry(session, &entry.key, entry.revision)?;
+    }
+    Ok(problems.len())
+}
@@ -1,35 +1,129 @@
-use crate::cloud::RemoteSession;
-use crate::vault::{load_vault, SecureEntry, VaultItem};
-use clipboard::{ClipboardContext, ClipboardProvider};
-use dialoguer::Select;
+use crate::cloud::VaultSession;
+use crate::commands::attachments::{attachment_json, format_size};
+use crate::error::Error;
+use crate::output::{self, OutputFormat};
+use crate::security::totp::Totp;
+use crate::utils::copy_to_clipboard;
+use crate::vault::{open_item, select_entry, CustomFieldKind, StoredItem, VaultItem};
+use anstream::println;
 use owo_colors::OwoColorize;
+use percent_encoding::percent_decode_str;
+use reqwest::Url;
+use serde_json::json;
 
-pub fn get_item(session: &RemoteSession) -> Result<(), String> {
-    let entries = load_vault(session)?;
-
-    let keys: Vec<String> = entries.iter().map(|entry| entry.key.clone()).collect();
+pub fn get_item(
+    session: &VaultSession,
+    name: Option<String>,
+    field: Option<String>,
+    reveal: bool,
+) -> Result<(), Error> {
+    let Some(selected) = select_entry(session, name.as_deref(), "Select an item to view:")? else {
+        return Ok(());
+    };
 
-    if keys.is_empty() {
-        println!("{}", "No items found in the vault.".red());
+    let (_, stored) = open_item(session, &selected.id)?;
+    if let Some(field) = field {
+        let value = stored.field(&field)?;
+        if output::is_json() {
+            output::print_json(&json!({
+                "id": selected.id,
+                "name": selected.name,
+                "field": field,
+                "value": value,
+            }))?;
+        } else {
+            println!("{}", value);
+        }
         return Ok(());
     }
 
-    let selection = Select::new()
-        .with_prompt("Select an item to view:")
-        .items(&keys)
-        .default(0)
-        .interact()
-        .unwrap();
+    let StoredItem {
+        item: vault_item,
+        ext,
+    } = stored;
+    let mut fields = vault_item.to_fields()?;
+    fields.remove("type");
+    let totp = fields
+        .get("totp")
+        .and_then(|uri| uri.as_str())
+        .and_then(|uri| Totp::parse(uri).ok());
 
-    let item_key = &keys[selection];
-    println!("Retrieving details for {}", item_key.bold().green());
+    // Bulk structured output leaves secrets out so they do not end up in
+    // logs or shell history by accident; `--field` or `--reveal` asks for them.
+    let mut hidden = Vec::new();
+    let mut custom_fields = ext.fields.clone();
+    if !reveal {
+        for field in vault_item.secret_fields() {
+            if fields.remove(*field).is_some() {
+                hidden.push(field.to_string());
+            }
+        }
+        if let VaultItem::Database {
+            password,
+            connection_string,
+            ..
+        } = &vault_item
+        {
+            match mask_connection_string(connection_string, password) {
+                Some(masked) => {
+                    fields.insert("connection_string".to_string(), json!(masked));
+                }
+                None => {
+                    fields.remove("connection_string");
+                    hidden.push("connection_string".to_string());
+                }
+            }
+        }
+        custom_fields.retain(|custom| {
+            let secret = custom.kind == CustomFieldKind::Hidden;
+            if secret {
+                hidden.push(custom.name.clone());
+            }
+            !secret
+        });
+    }
 
-    let entry = entries
-        .into_iter()
-        .find(|entry| &entry.key == item_key)
-        .ok_or_else(|| "Selected item not found.".to_string())?;
+    match output::format() {
+        OutputFormat::Json => {
+            let mut details = json!({
+                "id": selected.id,
+                "name": selected.name,
+                "type": selected.kind,
+                "fields": fields,
+                "custom_fields": custom_fields,
+                "hidden": hidden,
+                "tags": ext.tags,
+                "folder": ext.folder,
+                "attachments": ext.attachments.iter().map(attachment_json).collect::<Vec<_>>(),
+            });
+            if let Some(totp) = totp {
+                let (code, remaining) = totp.current();
+                details["totp_code"] = json!({ "code": code, "remaining": remaining });
+            }
+            return output::print_json(&details);
+        }
+        OutputFormat::Plain => {
+            for (field, value) in &fields {
+                println!("{}\t{}", field, value.as_str().unwrap_or_default());
+            }
+            for custom in &custom_fields {
+                println!("{}\t{}", custom.name, custom.value);
+            }
+            if !ext.tags.is_empty() {
+                println!("tags\t{}", ext.tags.join(","));
+            }
+            if !ext.folder.is_empty() {
+                println!("folder\t{}", ext.folder);
+            }
+            for attachment in &ext.attachments {
+                println!("attachment\t{}", attachment.name);
+            }
+            return Ok(());
+        }
+        OutputFormat::Table => {}
+    }
 
-    let vault_item = SecureEntry::decrypt(session.encryption_key(), &entry.value)?;
+    println!("Retrieving details for {}", selected.name.bold().green());
     let mut clipboard_contents = String::new();
 
     match vault_item {
@@ -39,6 +133,7 @@ pub fn get_item(session: &RemoteSession) -> Result<(), String> {
             email,
             username,
             password,
+            totp,
         } => {
             println!("{} {}", "Type:".cyan(), "Password".bold().green());
             println!("{} {}", "Name:".cyan(), name.bold());
@@ -46,6 +141,15 @@ pub fn get_item(session: &RemoteSession) -> Result<(), String> {
             println!("{} {}", "Email:".cyan(), email.bold());
             println!("{} {}", "Username:".cyan(), username.bold());
             println!("{} {}", "Password:".cyan(), "[hidden]".red());
+            if let Ok(totp) = Totp::parse(&totp) {
+                let (code, remaining) = totp.current();
+                println!(
+                    "{} {} {}",
+                    "One-time code:".cyan(),
+                    code.bold(),
+                    format!("({}s left)", remaining).dimmed()
+                );
+            }
             clipboard_contents = password;
         }
         VaultItem::CreditCard {
@@ -66,14 +170,178 @@ pub fn get_item(session: &RemoteSession) -> Result<(), String> {
             println!("{} {}", "Title:".cyan(), title.bold());
             println!("{} {}", "Note:".cyan(), note.bold());
         }
+        VaultItem::SshKey {
+            name,
+            public_key,
+            comment,
+            fingerprint,
+            ..
+        } => {
+            println!("{} {}", "Type:".cyan(), "SSH Key".bold().yellow());
+            println!("{} {}", "Name:".cyan(), name.bold());
+            println!("{} {}", "Comment:".cyan(), comment.bold());
+            println!("{} {}", "Fingerprint:".cyan(), fingerprint.bold());
+            println!("{} {}", "Public Key:".cyan(), public_key.bold());
+            println!("{} {}", "Private Key:".cyan(), "[hidden]".red());
+            clipboard_contents = public_key;
+        }
+        VaultItem::Identity {
+            name,
+            full_name,
+            email,
+            phone,
+            address,
+            passport_number,
+        } => {
+            println!("{} {}", "Type:".cyan(), "Identity".bold().green());
+            println!("{} {}", "Name:".cyan(), name.bold());
+            println!("{} {}", "Full Name:".cyan(), full_name.bold());
+            println!("{} {}", "Email:".cyan(), email.bold());
+            println!("{} {}", "Phone:".cyan(), phone.bold());
+            println!("{} {}", "Address:".cyan(), address.bold());
+            println!("{} {}", "Passport Number:".cyan(), "[hidden]".red());
+            clipboard_contents = passport_number;
+        }
+        VaultItem::BankAccount {
+            name,
+            account_holder,
+            iban,
+            routing_number,
+            ..
+        } => {
+            println!("{} {}", "Type:".cyan(), "Bank Account".bold().blue());
+            println!("{} {}", "Name:".cyan(), name.bold());
+            println!("{} {}", "Account Holder:".cyan(), account_holder.bold());
+            println!("{} {}", "IBAN:".cyan(), iban.bold());
+            println!("{} {}", "Routing Number:".cyan(), routing_number.bold());
+            println!("{} {}", "PIN:".cyan(), "[hidden]".red());
+            clipboard_contents = iban;
+        }
+        VaultItem::ApiCredential {
+            name,
+            key_id,
+            secret,
+            endpoint,
+        } => {
+            println!("{} {}", "Type:".cyan(), "API Credential".bold().yellow());
+            println!("{} {}", "Name:".cyan(), name.bold());
+            println!("{} {}", "Key ID:".cyan(), key_id.bold());
+            println!("{} {}", "Endpoint:".cyan(), endpoint.bold());
+            println!("{} {}", "Secret:".cyan(), "[hidden]".red());
+            clipboard_contents = secret;
+        }
+        VaultItem::WifiNetwork {
+            name,
+            ssid,
+            passphrase,
+            security,
+        } => {
+            println!("{} {}", "Type:".cyan(), "Wi-Fi Network".bold().magenta());
+            println!("{} {}", "Name:".cyan(), name.bold());
+            println!("{} {}", "SSID:".cyan(), ssid.bold());
+            println!("{} {}", "Security:".cyan(), security.bold());
+            println!("{} {}", "Passphrase:".cyan(), "[hidden]".red());
+            clipboard_contents = passphrase;
+        }
+        VaultItem::SoftwareLicense {
+            name,
+            version,
+            licensed_to,
+            license_key,
+        } => {
+            println!("{} {}", "Type:".cyan(), "Software License".bold().blue());
+            println!("{} {}", "Name:".cyan(), name.bold());
+            println!("{} {}", "Version:".cyan(), version.bold());
+            println!("{} {}", "Licensed To:".cyan(), licensed_to.bold());
+            println!("{} {}", "License Key:".cyan(), "[hidden]".red());
+            clipboard_contents = license_key;
+        }
+        VaultItem::Database {
+            name,
+            host,
+            port,
+            database,
+            username,
+            password,
+            connection_string,
+        } => {
+            let connection_string = mask_connection_string(&connection_string, &password)
+                .unwrap_or_else(|| "[hidden]".to_string());
+            println!("{} {}", "Type:".cyan(), "Database".bold().yellow());
+            println!("{} {}", "Name:".cyan(), name.bold());
+            println!("{} {}", "Host:".cyan(), host.bold());
+            println!("{} {}", "Port:".cyan(), port.bold());
+            println!("{} {}", "Database:".cyan(), database.bold());
+            println!("{} {}", "Username:".cyan(), username.bold());
+            println!("{} {}", "Password:".cyan(), "[hidden]".red());
+            println!(
+                "{} {}",
+                "Connection String:".cyan(),
+                connection_string.bold()
+            );
+            clipboard_contents = password;
+        }
     }
 
-    if !clipboard_contents.is_empty() {
-        let mut ctx: ClipboardContext = ClipboardProvider::new().map_err(|err| err.to_string())?;
-        ctx.set_contents(clipboard_contents)
-            .map_err(|err| err.to_string())?;
-        println!("Sensitive data has been copied to the clipboard.");
+    if !ext.folder.is_empty() {
+        println!("{} {}", "Folder:".cyan(), ext.folder.bold());
+    }
+    if !ext.tags.is_empty() {
+        println!("{} {}", "Tags:".cyan(), ext.tags.join(", ").bold());
+    }
+    if !ext.attachments.is_empty() {
+        let attachments: Vec<String> = ext
+            .attachments
+            .iter()
+            .map(|attachment| format!("{} ({})", attachment.name, format_size(attachment.size)))
+            .collect();
+        println!(
+            "{} {}",
+            "Attachments:".cyan(),
+            attachments.join(", ").bold()
+        );
+    }
+    for custom in &ext.fields {
+        let value = custom.display_value();
+        match custom.kind {
+            CustomFieldKind::Hidden => {
+                println!("{} {}", format!("{}:", custom.name).cyan(), value.red())
+            }
+            _ => println!("{} {}", format!("{}:", custom.name).cyan(), value.bold()),
+        }
     }
 
-    Ok(())
+    copy_to_clipboard(clipboard_contents)
+}
+
+// Only URL-style connection strings are shown, with the password in the
+// userinfo and in password query parameters masked. Anything that does not
+// parse, or still contains the password afterwards, is left out entirely.
+fn mask_connection_string(connection_string: &str, password: &str) -> Option<String> {
+    if connection_string.trim().is_empty() {
+        return Some(String::new());
+    }
+    let mut url = Url::parse(connection_string.trim()).ok()?;
+    if url.cannot_be_a_base() {
+        return None;
+    }
+    if url.password().is_some() {
+        url.set_password(Some("****")).ok()?;
+    }
+    if url.query().is_some() {
+        let pairs: Vec<(String, String)> = url
+            .query_pairs()
+            .map(|(key, value)| {
+                let secret = ["password", "pwd", "pass"]
+                    .iter()
+                    .any(|name| key.eq_ignore_ascii_case(name));
+                let value = if secret { "****".into() } else { value };
+                (key.into_owned(), value.into_owned())
+            })
+            .collect();
+        url.query_pairs_mut().clear().extend_pairs(pairs);
+    }
+    let masked = url.to_string();
+    let decoded = percent_decode_str(&masked).decode_utf8_lossy();
+    (password.is_empty() || !decoded.contains(password)).then_some(masked)
 }
@@ -0,0 +1,264 @@
+use crate::cloud::VaultSession;
+use crate::error::Error;
+use crate::vault::{
+    delete_entry, list_named_entries, new_entry_id, open_item, store_entry, update_entry,
+    ItemExtension, SecureEntry, StoredItem, VaultItem,
+};
+use reqwest::Url;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+
+const HELPER_TAG: &str = "git-credential";
+
+struct Request {
+    protocol: String,
+    host: String,
+    path: String,
+    username: Option<String>,
+    password: Option<String>,
+}
+
+struct Candidate {
+    id: String,
+    revision: u64,
+    username: String,
+    password: String,
+    path_len: usize,
+    created_by_helper: bool,
+}
+
+pub fn handle_git_credential(session: &VaultSession, action: &str) -> Result<(), Error> {
+    let Some(request) = read_request()? else {
+        return Ok(());
+    };
+
+    match action {
+        "get" => get_credential(session, &request),
+        "store" => store_credential(session, &request),
+        "erase" => erase_credential(session, &request),
+        _ => Ok(()),
+    }
+}
+
+fn get_credential(session: &VaultSession, request: &Request) -> Result<(), Error> {
+    let Some(candidate) = find_candidates(session, request)?.into_iter().next() else {
+        return Ok(());
+    };
+
+    let mut stdout = io::stdout().lock();
+    writeln!(stdout, "username={}", candidate.username)
+        .and_then(|_| writeln!(stdout, "password={}", candidate.password))
+        .map_err(|err| format!("Failed to write the credential: {}", err))?;
+    Ok(())
+}
+
+fn store_credential(session: &VaultSession, request: &Request) -> Result<(), Error> {
+    let (Some(username), Some(password)) = (&request.username, &request.password) else {
+        return Ok(());
+    };
+
+    if let Some(existing) = find_candidates(session, request)?.into_iter().next() {
+        if existing.password == *password {
+            return Ok(());
+        }
+        let changes = [("password".to_string(), password.clone())];
+        return update_entry(session, &existing.id, |current| {
+            current.with_fields(&changes)
+        });
+    }
+
+    let mut website = format!("{}://{}", request.protocol, request.host);
+    if !request.path.is_empty() {
+        website = format!("{}/{}", website, request.path);
+    }
+    let item = VaultItem::Password {
+        name: request.host.clone(),
+        website,
+        email: String::new(),
+        username: username.clone(),
+        password: password.clone(),
+        totp: String::new(),
+    };
+    let mut ext = ItemExtension::default();
+    ext.add_tag(HELPER_TAG);
+    let value =
+        SecureEntry::encrypt(session.encryption_key(), &StoredItem::new(item, ext))?.serialize()?;
+    store_entry(session, &new_entry_id(), &value, 0)
+}
+
+// Only entries the helper saved itself are removed when git rejects a
+// credential; logins the user added by hand are never deleted behind their back.
+fn erase_credential(session: &VaultSession, request: &Request) -> Result<(), Error> {
+    for candidate in find_candidates(session, request)? {
+        if !candidate.created_by_helper
+            || request
+                .password
+                .as_ref()
+                .is_some_and(|password| *password != candidate.password)
+        {
+            continue;
+        }
+        delete_entry(session, &candidate.id, candidate.revision)?;
+    }
+    Ok(())
+}
+
+fn find_candidates(session: &VaultSession, request: &Request) -> Result<Vec<Candidate>, Error> {
+    let mut candidates = Vec::new();
+    for named in list_named_entries(session)? {
+        if named.kind != "Password" {
+            continue;
+        }
+        let (entry, stored) = open_item(session, &named.id)?;
+        let VaultItem::Password {
+            website,
+            email,
+            username,
+            password,
+            ..
+        } = stored.item
+        else {
+            continue;
+        };
+        let Some(path_len) = website_matches(&website, request) else {
+            continue;
+        };
+        let username = if username.is_empty() { email } else { username };
+        if request
+            .username
+            .as_ref()
+            .is_some_and(|wanted| *wanted != username)
+        {
+            continue;
+        }
+        candidates.push(Candidate {
+            id: entry.key,
+            revision: entry.revision,
+            username,
+            password,
+            path_len,
+            created_by_helper: stored.ext.has_tag(HELPER_TAG),
+        });
+    }
+    candidates.sort_by_key(|candidate| Reverse(candidate.path_len));
+    Ok(candidates)
+}
+
+fn website_matches(website: &str, request: &Request) -> Option<usize> {
+    let url = Url::parse(website).ok()?;
+    if !url.scheme().eq_ignore_ascii_case(&request.protocol) {
+        return None;
+    }
+    let host = match (url.host_str(), url.port()) {
+        (Some(host), Some(port)) => format!("{}:{}", host, port),
+        (Some(host), None) => host.to_string(),
+        (None, _) => return None,
+    };
+    if !host.eq_ignore_ascii_case(&request.host) {
+        return None;
+    }
+
+    let path = url.path().trim_matches('/');
+    if path.is_empty() || request.path.is_empty() {
+        return Some(0);
+    }
+    let request_path = request.path.trim_matches('/');
+    let within = request_path == path
+        || request_path
+            .strip_prefix(path)
+            .is_some_and(|rest| rest.starts_with('/'));
+    within.then_some(path.len())
+}
+
+fn read_request() -> Result<Option<Request>, Error> {
+    let mut attributes = HashMap::new();
+    for line in io::stdin().lock().lines() {
+        let line = line.map_err(|err| format!("Failed to read the credential request: {}", err))?;
+        if line.is_empty() {
+            break;
+        }
+        if let Some((key, value)) = line.split_once('=') {
+            attributes.insert(key.to_string(), value.to_string());
+        }
+    }
+
+    if let Some(url) = attributes.get("url").and_then(|url| Url::parse(url).ok()) {
+        attributes
+            .entry("protocol".to_string())
+            .or_insert_with(|| url.scheme().to_string());
+        if let Some(host) = url.host_str() {
+            let host = match url.port() {
+                Some(port) => format!("{}:{}", host, port),
+                None => host.to_string(),
+            };
+            attributes.entry("host".to_string()).or_insert(host);
+        }
+        attributes
+            .entry("path".to_string())
+            .or_insert_with(|| url.path().trim_start_matches('/').to_string());
+    }
+
+    let (Some(protocol), Some(host)) = (attributes.remove("protocol"), attributes.remove("host"))
+    else {
+        return Ok(None);
+    };
+    Ok(Some(Request {
+        protocol,
+        host,
+        path: attributes.remove("path").unwrap_or_default(),
+        username: attributes.remove("username"),
+        password: attributes.remove("password"),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(protocol: &str, host: &str, path: &str) -> Request {
+        Request {
+            protocol: protocol.to_string(),
+            host: host.to_string(),
+            path: path.to_string(),
+            username: None,
+            password: None,
+        }
+    }
+
+    #[test]
+    fn matches_protocol_and_host() {
+        let github = request("https", "github.com", "");
+        assert_eq!(website_matches("https://github.com", &github), Some(0));
+        assert_eq!(website_matches("https://GitHub.com/", &github), Some(0));
+        assert_eq!(website_matches("http://github.com", &github), None);
+        assert_eq!(website_matches("https://gitlab.com", &github), None);
+        assert_eq!(website_matches("github.com", &github), None);
+    }
+
+    #[test]
+    fn ports_must_match() {
+        let local = request("https", "git.local:8443", "");
+        assert_eq!(website_matches("https://git.local:8443", &local), Some(0));
+        assert_eq!(website_matches("https://git.local", &local), None);
+    }
+
+    #[test]
+    fn paths_match_on_segment_boundaries() {
+        let repo = request("https", "github.com", "org/repo.git");
+        assert_eq!(website_matches("https://github.com/org", &repo), Some(3));
+        assert_eq!(
+            website_matches("https://github.com/org/repo.git", &repo),
+            Some(12)
+        );
+        assert_eq!(website_matches("https://github.com/or", &repo), None);
+        assert_eq!(website_matches("https://github.com/other", &repo), None);
+        assert_eq!(
+            website_matches(
+                "https://github.com/org",
+                &request("https", "github.com", "")
+            ),
+            Some(0)
+        );
+    }
+}
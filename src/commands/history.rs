@@ -0,0 +1,186 @@
+use crate::cloud::types::{ChangeKind, HistoryChange, INDEX_ID};
+use crate::cloud::VaultSession;
+use crate::error::Error;
+use crate::output::{self, OutputFormat};
+use crate::utils::{confirm_or_yes, format_timestamp};
+use crate::vault::{entry_names, short_id};
+use anstream::println;
+use owo_colors::OwoColorize;
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
+
+pub fn show_history(session: &VaultSession) -> Result<(), Error> {
+    let remote = session.remote()?;
+    let mut history = remote.history()?;
+    history.changes.retain(|change| change.id != INDEX_ID);
+    let names = entry_names(session)?;
+
+    match output::format() {
+        OutputFormat::Json => {
+            let changes: Vec<_> = history
+                .changes
+                .iter()
+                .map(|change| {
+                    json!({
+                        "revision": change.revision,
+                        "timestamp": change.timestamp,
+                        "kind": change.kind,
+                        "id": change.id,
+                        "name": names.get(&change.id),
+                    })
+                })
+                .collect();
+            return output::print_json(&json!({
+                "revision": history.revision,
+                "oldest_revision": history.oldest_revision,
+                "changes": changes,
+            }));
+        }
+        OutputFormat::Plain => {
+            for change in &history.changes {
+                println!(
+                    "{}\t{}\t{}\t{}\t{}",
+                    change.revision,
+                    change.timestamp,
+                    kind_label(change.kind),
+                    change.id,
+                    names.get(&change.id).map_or("", String::as_str)
+                );
+            }
+            return Ok(());
+        }
+        OutputFormat::Table => {}
+    }
+
+    println!(
+        "{}",
+        format!("Vault history (current revision {}):", history.revision)
+            .yellow()
+            .bold()
+    );
+    if history.changes.is_empty() {
+        println!("{}", "No changes recorded yet.".red());
+    }
+    for change in &history.changes {
+        println!(
+            "  {}  {}  {}  {}",
+            format!("{:>6}", change.revision).bold(),
+            format_timestamp(change.timestamp),
+            describe_kind(change.kind),
+            display_name(&names, &change.id)
+        );
+    }
+    println!(
+        "{}",
+        format!(
+            "Restore with `x restore <revision>` (oldest restorable revision: {}).",
+            history.oldest_revision
+        )
+        .yellow()
+    );
+    Ok(())
+}
+
+pub fn restore_revision(session: &VaultSession, revision: u64, yes: bool) -> Result<(), Error> {
+    let remote = session.remote()?;
+    let history = remote.history()?;
+    if revision < history.oldest_revision {
+        return Err(Error::NotFound(format!(
+            "Revision {} is no longer kept in the history (oldest restorable revision is {}).",
+            revision, history.oldest_revision
+        )));
+    }
+
+    let mut changes = changes_since(&history.changes, revision);
+    changes.remove(INDEX_ID);
+    let names = entry_names(session)?;
+    if changes.is_empty() {
+        output::success(&format!("The vault already matches revision {}.", revision));
+        return Ok(());
+    }
+
+    if output::is_table() {
+        print_restore_plan(revision, &changes, &names);
+    }
+
+    if !confirm_or_yes(yes, "Restore the vault to this revision?")? {
+        return Err(Error::Cancelled("Restore cancelled.".to_string()));
+    }
+
+    let message = remote.restore(revision)?;
+    session.synchronize()?;
+    output::success(&message);
+    Ok(())
+}
+
+fn print_restore_plan(
+    revision: u64,
+    changes: &BTreeMap<String, (HistoryChange, usize)>,
+    names: &HashMap<String, String>,
+) {
+    println!(
+        "{}",
+        format!("Restoring revision {} will change:", revision)
+            .yellow()
+            .bold()
+    );
+    for (id, (first, count)) in changes {
+        let action = match first.kind {
+            ChangeKind::Added => format!("{:<8}", "remove").red().to_string(),
+            ChangeKind::Updated => format!("{:<8}", "revert").yellow().to_string(),
+            ChangeKind::Trashed => format!("{:<8}", "untrash").green().to_string(),
+            ChangeKind::Restored => format!("{:<8}", "trash").red().to_string(),
+            ChangeKind::Deleted => format!("{:<8}", "recreate").green().to_string(),
+        };
+        println!(
+            "  {}  {}  ({} change(s) since {})",
+            action,
+            display_name(names, id),
+            count,
+            format_timestamp(first.timestamp)
+        );
+    }
+}
+
+fn changes_since(
+    changes: &[HistoryChange],
+    revision: u64,
+) -> BTreeMap<String, (HistoryChange, usize)> {
+    let mut grouped: BTreeMap<String, (HistoryChange, usize)> = BTreeMap::new();
+    for change in changes.iter().filter(|change| change.revision > revision) {
+        let entry = grouped
+            .entry(change.id.clone())
+            .or_insert_with(|| (change.clone(), 0));
+        if change.revision < entry.0.revision {
+            entry.0 = change.clone();
+        }
+        entry.1 += 1;
+    }
+    grouped
+}
+
+fn kind_label(kind: ChangeKind) -> &'static str {
+    match kind {
+        ChangeKind::Added => "added",
+        ChangeKind::Updated => "updated",
+        ChangeKind::Trashed => "trashed",
+        ChangeKind::Restored => "restored",
+        ChangeKind::Deleted => "deleted",
+    }
+}
+
+fn describe_kind(kind: ChangeKind) -> String {
+    let label = format!("{:<8}", kind_label(kind));
+    match kind {
+        ChangeKind::Added | ChangeKind::Restored => label.green().to_string(),
+        ChangeKind::Updated => label.yellow().to_string(),
+        ChangeKind::Trashed | ChangeKind::Deleted => label.red().to_string(),
+    }
+}
+
+fn display_name(names: &HashMap<String, String>, id: &str) -> String {
+    match names.get(id) {
+        Some(name) => format!("{} ({})", name, short_id(id)),
+        None => short_id(id).to_string(),
+    }
+}
@@ -1,20 +1,86 @@
-use crate::cloud::server::MASTER_HASH_KEY;
+use crate::cloud::server::{
+    discard_history, upgrade_vault_layout, ItemRecord, ITEMS_TREE, KDF_PARAMS_KEY, MASTER_HASH_KEY,
+};
 use crate::cloud::{run_server, ServerConfig, ServerError};
+use crate::error::Error;
+use crate::security::kdf::{KdfParams, MasterKeys};
 use crate::security::master_password;
+use crate::vault::SecureEntry;
+use aes_gcm::{Aes256Gcm, Key};
+use anstream::{eprintln, println};
 use owo_colors::OwoColorize;
-use sled;
+use sled::transaction::ConflictableTransactionResult;
+use sled::{self, Transactional};
 use std::path::Path;
 
-pub fn host_server(mut config: ServerConfig) -> Result<(), ServerError> {
+pub fn host_server(mut config: ServerConfig) -> Result<(), Error> {
     let storage_path = config.storage_path();
-    if let Some(existing_hash) = read_existing_master_hash(&storage_path) {
-        println!("{}", "Reusing stored master password hash.".yellow());
-        config.master_hash = Some(existing_hash);
-    } else {
-        let master_password =
-            master_password::prompt_master_password("Set the cloud host master password");
-        let master_hash = master_password::hash_password(&master_password);
-        config.master_hash = Some(master_hash);
+    let existing_hash = read_existing_meta(&storage_path, MASTER_HASH_KEY)
+        .map(|bytes| String::from_utf8_lossy(&bytes).to_string());
+    let existing_kdf = read_existing_meta(&storage_path, KDF_PARAMS_KEY)
+        .and_then(|bytes| serde_json::from_slice::<KdfParams>(&bytes).ok());
+
+    match (existing_hash, existing_kdf) {
+        (Some(existing_hash), Some(kdf)) if kdf.is_current() => {
+            println!("{}", "Reusing stored master password hash.".yellow());
+            config.master_hash = Some(existing_hash);
+            config.kdf_params = Some(kdf);
+        }
+        (Some(existing_hash), existing_kdf) => {
+            println!(
+                "{}",
+                "This vault uses an outdated key scheme and will be re-encrypted.".yellow()
+            );
+            let master_password =
+                master_password::prompt_master_password("Enter the current host master password")?;
+            let old_key = match previous_encryption_key(
+                &master_password,
+                &existing_hash,
+                existing_kdf.as_ref(),
+            )? {
+                Some(key) => key,
+                None => {
+                    eprintln!(
+                        "{}",
+                        "Master password does not match the stored hash.".red()
+                    );
+                    return Err(ServerError::Unauthorized.into());
+                }
+            };
+
+            let kdf = KdfParams::generate(config.kdf_memory_kib, config.kdf_iterations);
+            let keys = kdf
+                .derive_keys(&master_password)
+                .map_err(ServerError::Migration)?;
+            let (migrated, unreadable) = migrate_vault(&storage_path, &old_key, &keys, &kdf)?;
+            println!(
+                "{}",
+                format!("Re-encrypted {} entries with the new key.", migrated).green()
+            );
+            if !unreadable.is_empty() {
+                eprintln!(
+                    "{}",
+                    format!(
+                        "{} entries could not be decrypted with the old key and were kept as they are: {}. Run `x fsck` to quarantine them.",
+                        unreadable.len(),
+                        unreadable.join(", ")
+                    )
+                    .yellow()
+                );
+            }
+            config.master_hash = Some(keys.auth_hash);
+            config.kdf_params = Some(kdf);
+        }
+        (None, _) => {
+            let master_password =
+                master_password::prompt_master_password("Set the cloud host master password")?;
+            let kdf = KdfParams::generate(config.kdf_memory_kib, config.kdf_iterations);
+            let keys = kdf
+                .derive_keys(&master_password)
+                .map_err(ServerError::Migration)?;
+            config.master_hash = Some(keys.auth_hash);
+            config.kdf_params = Some(kdf);
+        }
     }
 
     println!("{}", "Launching embedded cloud API server...".green());
@@ -24,21 +90,86 @@ pub fn host_server(mut config: ServerConfig) -> Result<(), ServerError> {
                 "{}",
                 "Master password rejected. Ensure you entered the correct host password.".red()
             );
-            Err(ServerError::Unauthorized)
+            Err(ServerError::Unauthorized.into())
         }
-        other => other,
+        other => other.map_err(Error::from),
     }
 }
 
-fn read_existing_master_hash(path: &Path) -> Option<String> {
+fn read_existing_meta(path: &Path, key: &str) -> Option<Vec<u8>> {
     if !path.exists() {
         return None;
     }
 
     let db = sled::open(path).ok()?;
     let meta = db.open_tree("meta").ok()?;
-    meta.get(MASTER_HASH_KEY)
-        .ok()
-        .flatten()
-        .map(|bytes| String::from_utf8_lossy(&bytes).to_string())
+    meta.get(key).ok().flatten().map(|bytes| bytes.to_vec())
+}
+
+fn previous_encryption_key(
+    password: &str,
+    stored_hash: &str,
+    kdf: Option<&KdfParams>,
+) -> Result<Option<Key<Aes256Gcm>>, ServerError> {
+    match kdf {
+        Some(kdf) => {
+            let keys = kdf.derive_keys(password).map_err(ServerError::Migration)?;
+            Ok((keys.auth_hash == stored_hash).then_some(keys.encryption_key))
+        }
+        None if master_password::legacy_hash_password(password) == stored_hash => {
+            key_from_hash(stored_hash).map(Some)
+        }
+        None => Ok(None),
+    }
+}
+
+fn migrate_vault(
+    path: &Path,
+    old_key: &Key<Aes256Gcm>,
+    keys: &MasterKeys,
+    kdf: &KdfParams,
+) -> Result<(usize, Vec<String>), ServerError> {
+    let db = sled::open(path)?;
+    upgrade_vault_layout(&db)?;
+    let meta = db.open_tree("meta")?;
+    let items = db.open_tree(ITEMS_TREE)?;
+
+    let mut encoded = Vec::new();
+    let mut unreadable = Vec::new();
+    for entry in items.iter() {
+        let (key, bytes) = entry?;
+        let mut stored: ItemRecord = serde_json::from_slice(&bytes)?;
+        match SecureEntry::reencrypt(old_key, &keys.encryption_key, &stored.value) {
+            Ok(value) => stored.value = value,
+            Err(_) => {
+                unreadable.push(String::from_utf8_lossy(&key).to_string());
+                continue;
+            }
+        }
+        encoded.push((key, serde_json::to_vec(&stored)?));
+    }
+
+    let kdf_bytes = serde_json::to_vec(kdf)?;
+    (&items, &meta)
+        .transaction(
+            |(items, meta)| -> ConflictableTransactionResult<(), sled::Error> {
+                for (key, bytes) in &encoded {
+                    items.insert(key.clone(), bytes.as_slice())?;
+                }
+                meta.insert(KDF_PARAMS_KEY, kdf_bytes.clone())?;
+                meta.insert(MASTER_HASH_KEY, keys.auth_hash.as_bytes())?;
+                Ok(())
+            },
+        )
+        .map_err(|err| ServerError::Migration(err.to_string()))?;
+    discard_history(&db)?;
+
+    Ok((encoded.len(), unreadable))
+}
+
+fn key_from_hash(hash: &str) -> Result<Key<Aes256Gcm>, ServerError> {
+    let mut bytes = [0u8; 32];
+    hex::decode_to_slice(hash, &mut bytes)
+        .map_err(|_| ServerError::Migration("Stored master password hash is invalid".into()))?;
+    Ok(*Key::<Aes256Gcm>::from_slice(&bytes))
 }
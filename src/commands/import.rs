@@ -1,8 +1,13 @@
-use crate::cloud::{RemoteSession, VaultEntry};
-use crate::vault::{load_vault, save_vault};
+use crate::cloud::VaultSession;
+use crate::error::Error;
+use crate::output;
+use crate::utils::{prompt_aborted, require_interactive};
+use crate::vault::{is_entry_id, load_all_entries, new_entry_id, store_entry, SecureEntry};
+use anstream::{eprintln, println};
 use dialoguer::Input;
 use owo_colors::OwoColorize;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::Read;
 
@@ -12,14 +17,26 @@ struct ImportItem {
     value: String,
 }
 
-pub fn import_items(session: &RemoteSession) -> Result<(), String> {
-    println!("{}", "Import Vault Items".yellow().bold());
+pub fn import_items(
+    session: &VaultSession,
+    file: Option<String>,
+    overwrite: bool,
+) -> Result<(), Error> {
+    if output::is_table() {
+        println!("{}", "Import Vault Items".yellow().bold());
+    }
 
-    let file_path: String = Input::new()
-        .with_prompt("Enter file path to import vault items from")
-        .default("x_export.json".into())
-        .interact_text()
-        .unwrap();
+    let file_path: String = match file {
+        Some(file) => file,
+        None => {
+            require_interactive("--file")?;
+            Input::new()
+                .with_prompt("Enter file path to import vault items from")
+                .default("x_export.json".into())
+                .interact_text()
+                .map_err(prompt_aborted)?
+        }
+    };
 
     let mut file = File::open(&file_path)
         .map_err(|err| format!("Failed to open file {}: {}", file_path, err))?;
@@ -31,27 +48,58 @@ pub fn import_items(session: &RemoteSession) -> Result<(), String> {
         serde_json::from_str(&contents).map_err(|err| format!("Failed to parse JSON: {}", err))?;
 
     if items.is_empty() {
-        println!("{}", "No items found in the import file.".yellow());
+        output::success("No items found in the import file.");
         return Ok(());
     }
 
-    let mut vault = load_vault(session)?;
-
+    let existing: HashMap<String, u64> = load_all_entries(session)?
+        .into_iter()
+        .map(|entry| (entry.key, entry.revision))
+        .collect();
     let mut imported = 0usize;
+    let mut skipped = 0usize;
+    let mut kept = 0usize;
     for item in items {
-        vault.retain(|entry| entry.key != item.key);
-        vault.push(VaultEntry {
-            key: item.key,
-            value: item.value,
-        });
+        if SecureEntry::decrypt(session.encryption_key(), &item.value).is_err() {
+            skipped += 1;
+            continue;
+        }
+        let (id, revision) = if is_entry_id(&item.key) {
+            match existing.get(&item.key) {
+                Some(_) if !overwrite => {
+                    kept += 1;
+                    continue;
+                }
+                Some(revision) => (item.key, *revision),
+                None => (item.key, 0),
+            }
+        } else {
+            (new_entry_id(), 0)
+        };
+        store_entry(session, &id, &item.value, revision)?;
         imported += 1;
     }
 
-    save_vault(session, &vault)?;
-
-    println!(
-        "{}",
-        format!("Imported {} items from {}", imported, file_path).green()
-    );
+    output::success(&format!("Imported {} items from {}", imported, file_path));
+    if kept > 0 {
+        eprintln!(
+            "{}",
+            format!(
+                "Kept {} items that already exist in the vault. Pass --overwrite to replace them.",
+                kept
+            )
+            .yellow()
+        );
+    }
+    if skipped > 0 {
+        eprintln!(
+            "{}",
+            format!(
+                "Skipped {} items that this master password cannot decrypt.",
+                skipped
+            )
+            .yellow()
+        );
+    }
     Ok(())
 }
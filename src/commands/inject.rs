@@ -0,0 +1,206 @@
+use crate::cloud::VaultSession;
+use crate::error::Error;
+use crate::output::{self, OutputFormat};
+use crate::utils::write_private_file;
+use crate::vault::{SecretResolver, REFERENCE_SCHEME};
+use anstream::{eprintln, println};
+use owo_colors::OwoColorize;
+use serde_json::json;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+struct Placeholder {
+    start: usize,
+    end: usize,
+    line: usize,
+    reference: String,
+}
+
+pub fn inject_template(
+    session: &VaultSession,
+    input: Option<&Path>,
+    output_path: Option<&Path>,
+    check: bool,
+) -> Result<(), Error> {
+    let template = match input {
+        Some(path) => fs::read_to_string(path)
+            .map_err(|err| format!("Failed to read {}: {}", path.display(), err))?,
+        None => {
+            let mut template = String::new();
+            io::stdin()
+                .read_to_string(&mut template)
+                .map_err(|err| format!("Failed to read the template from stdin: {}", err))?;
+            template
+        }
+    };
+
+    let placeholders = find_placeholders(&template);
+    let mut resolver = SecretResolver::new(session)?;
+
+    if check {
+        return check_references(&mut resolver, &placeholders);
+    }
+
+    let mut rendered = String::with_capacity(template.len());
+    let mut cursor = 0;
+    for placeholder in &placeholders {
+        let value = resolver
+            .resolve(&placeholder.reference)
+            .map_err(|err| err.context(&format!("line {}", placeholder.line)))?;
+        rendered.push_str(&template[cursor..placeholder.start]);
+        rendered.push_str(&value);
+        cursor = placeholder.end;
+    }
+    rendered.push_str(&template[cursor..]);
+
+    match output_path {
+        Some(path) => {
+            write_private_file(path, rendered.as_bytes())
+                .map_err(|err| format!("Failed to write {}: {}", path.display(), err))?;
+            output::success(&format!(
+                "Rendered {} references into {}",
+                placeholders.len(),
+                path.display()
+            ));
+        }
+        None => io::stdout()
+            .write_all(rendered.as_bytes())
+            .map_err(|err| format!("Failed to write the output: {}", err))?,
+    }
+    Ok(())
+}
+
+fn check_references(
+    resolver: &mut SecretResolver,
+    placeholders: &[Placeholder],
+) -> Result<(), Error> {
+    let problems: Vec<(&Placeholder, Error)> = placeholders
+        .iter()
+        .filter_map(|placeholder| {
+            resolver
+                .resolve(&placeholder.reference)
+                .err()
+                .map(|err| (placeholder, err))
+        })
+        .collect();
+
+    match output::format() {
+        OutputFormat::Json => {
+            let items: Vec<_> = problems
+                .iter()
+                .map(|(placeholder, err)| {
+                    json!({
+                        "line": placeholder.line,
+                        "reference": placeholder.reference,
+                        "error": err.message(),
+                    })
+                })
+                .collect();
+            output::print_json(&json!({
+                "references": placeholders.len(),
+                "problems": items,
+            }))?;
+        }
+        OutputFormat::Plain => {
+            for (placeholder, err) in &problems {
+                println!(
+                    "{}\t{}\t{}",
+                    placeholder.line,
+                    placeholder.reference,
+                    err.message()
+                );
+            }
+        }
+        OutputFormat::Table => {
+            for (placeholder, err) in &problems {
+                eprintln!(
+                    "  - line {}: {}  {}",
+                    placeholder.line,
+                    placeholder.reference.bold().red(),
+                    err.message()
+                );
+            }
+        }
+    }
+
+    if !problems.is_empty() {
+        return Err(Error::NotFound(format!(
+            "{} of {} references could not be resolved.",
+            problems.len(),
+            placeholders.len()
+        )));
+    }
+    if !output::is_json() {
+        output::success(&format!("All {} references resolve.", placeholders.len()));
+    }
+    Ok(())
+}
+
+fn find_placeholders(template: &str) -> Vec<Placeholder> {
+    let mut placeholders = Vec::new();
+    let mut cursor = 0;
+    while let Some(open) = template[cursor..].find("{{") {
+        let start = cursor + open;
+        let Some(close) = template[start + 2..].find("}}") else {
+            break;
+        };
+        let end = start + 2 + close + 2;
+        let reference = template[start + 2..end - 2].trim();
+        if reference.starts_with(REFERENCE_SCHEME) {
+            placeholders.push(Placeholder {
+                start,
+                end,
+                line: template[..start].matches('\n').count() + 1,
+                reference: reference.to_string(),
+            });
+            cursor = end;
+        } else {
+            cursor = start + 2;
+        }
+    }
+    placeholders
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn references(template: &str) -> Vec<(String, usize)> {
+        find_placeholders(template)
+            .into_iter()
+            .map(|placeholder| (placeholder.reference, placeholder.line))
+            .collect()
+    }
+
+    #[test]
+    fn finds_references_with_their_lines() {
+        let template = "user={{ x://DB/username }}\npass={{x://DB/password}}\n";
+        assert_eq!(
+            references(template),
+            [
+                ("x://DB/username".to_string(), 1),
+                ("x://DB/password".to_string(), 2)
+            ]
+        );
+    }
+
+    #[test]
+    fn spans_cover_the_whole_placeholder() {
+        let template = "a {{ x://DB/password }} b";
+        let placeholder = &find_placeholders(template)[0];
+        assert_eq!(
+            &template[placeholder.start..placeholder.end],
+            "{{ x://DB/password }}"
+        );
+    }
+
+    #[test]
+    fn leaves_other_templates_alone() {
+        assert!(references("{{ .Values.image }} and {{ unclosed x://DB/password").is_empty());
+        assert_eq!(
+            references("{{ name }}{{x://DB/password}}"),
+            [("x://DB/password".to_string(), 1)]
+        );
+    }
+}
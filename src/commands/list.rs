@@ -1,22 +1,71 @@
-use crate::cloud::RemoteSession;
-use crate::vault::load_vault;
+use crate::cloud::VaultSession;
+use crate::error::Error;
+use crate::output::{self, OutputFormat};
+use crate::vault::{entry_labels, folder_contains, list_named_entries};
+use anstream::{eprintln, println};
 use owo_colors::OwoColorize;
 
-pub fn list_items(session: &RemoteSession) -> Result<(), String> {
-    println!("{}", "Stored Items:".yellow().bold());
+pub fn list_items(
+    session: &VaultSession,
+    tags: &[String],
+    folder: Option<&str>,
+) -> Result<(), Error> {
+    let filtered = !tags.is_empty() || folder.is_some();
+    let mut entries = list_named_entries(session)?;
+    entries.retain(|entry| {
+        tags.iter().all(|tag| entry.has_tag(tag))
+            && folder.is_none_or(|folder| folder_contains(&entry.folder, folder))
+    });
 
-    let mut keys: Vec<String> = load_vault(session)?
-        .into_iter()
-        .map(|entry| entry.key)
-        .collect();
-
-    keys.sort();
-
-    if keys.is_empty() {
-        println!("{}", "No items found in your vault.".red());
-    } else {
-        for key in keys {
-            println!("  - {}", key.bold().green());
+    match output::format() {
+        OutputFormat::Json => output::print_json(&entries)?,
+        OutputFormat::Plain => {
+            for entry in &entries {
+                println!(
+                    "{}\t{}\t{}\t{}\t{}",
+                    entry.id,
+                    entry.name,
+                    entry.kind,
+                    entry.folder,
+                    entry.tags.join(",")
+                );
+            }
+        }
+        OutputFormat::Table => {
+            println!("{}", "Stored Items:".yellow().bold());
+            if entries.is_empty() && filtered {
+                println!("{}", "No items match the filter.".red());
+            } else if entries.is_empty() {
+                println!("{}", "No items found in your vault.".red());
+            } else {
+                for (entry, label) in entries.iter().zip(entry_labels(&entries)) {
+                    let mut details = Vec::new();
+                    if !entry.folder.is_empty() {
+                        details.push(entry.folder.clone());
+                    }
+                    details.extend(entry.tags.iter().map(|tag| format!("#{}", tag)));
+                    if details.is_empty() {
+                        println!("  - {}", label.bold().green());
+                    } else {
+                        println!(
+                            "  - {}  {}",
+                            label.bold().green(),
+                            details.join(" ").dimmed()
+                        );
+                    }
+                }
+            }
+            let unreadable = entries.iter().filter(|entry| entry.is_unreadable()).count();
+            if unreadable > 0 {
+                eprintln!(
+                    "{}",
+                    format!(
+                        "{} entries could not be decrypted. Run `x fsck` to review them.",
+                        unreadable
+                    )
+                    .yellow()
+                );
+            }
         }
     }
 
@@ -1,11 +1,23 @@
 pub mod add;
+pub mod attachments;
 pub mod cloud;
 pub mod delete;
+pub mod docker_credential;
 pub mod edit;
 pub mod export;
+pub mod fsck;
 pub mod get;
+pub mod git_credential;
+pub mod history;
 pub mod host;
 pub mod import;
+pub mod inject;
 pub mod list;
+pub mod passwd;
 pub mod password_generator;
+pub mod run;
+pub mod ssh;
+pub mod ssh_agent;
+pub mod totp;
+pub mod trash;
 pub mod update;
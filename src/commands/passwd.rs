@@ -0,0 +1,109 @@
+use crate::cloud::cache::VaultCache;
+use crate::cloud::types::INDEX_ID;
+use crate::cloud::{RemoteSession, VaultEntry, VaultSession};
+use crate::commands::cloud::cloud_api;
+use crate::config::AppConfig;
+use crate::error::Error;
+use crate::security::kdf::KdfParams;
+use crate::security::master_password;
+use crate::utils::{read_stdin_secret, require_interactive};
+use crate::vault::{retry_on_conflict, SecureEntry};
+use anstream::{eprintln, println};
+use owo_colors::OwoColorize;
+
+pub fn change_master_password(
+    config: &mut AppConfig,
+    new_password_stdin: bool,
+) -> Result<(), Error> {
+    let api = cloud_api(config)?;
+    if !new_password_stdin {
+        require_interactive("--new-password-stdin")?;
+    }
+
+    let current_password =
+        master_password::prompt_master_password("Enter the current master password")?;
+    let session =
+        RemoteSession::new(api.clone(), current_password).and_then(VaultSession::online)?;
+
+    let new_password = if new_password_stdin {
+        read_stdin_secret()?
+    } else {
+        master_password::prompt_new_master_password("Enter the new master password")?
+    };
+    if new_password.is_empty() {
+        return Err(Error::Usage(
+            "The new master password cannot be empty".to_string(),
+        ));
+    }
+    let current_kdf = api.kdf_params()?;
+    let kdf = KdfParams::generate(current_kdf.memory_kib, current_kdf.iterations);
+    let keys = kdf.derive_keys(&new_password)?;
+
+    let (reencrypted, unreadable) = retry_on_conflict(|| {
+        session.synchronize()?;
+        let remote = session.remote()?;
+        let mut vault = session.entries();
+        for trashed in remote.list_trash()? {
+            vault.push(remote.fetch_trashed(&trashed.id)?);
+        }
+        let mut reencrypted = Vec::with_capacity(vault.len());
+        let mut unreadable = 0;
+        for entry in vault {
+            let value = match SecureEntry::reencrypt(
+                session.encryption_key(),
+                &keys.encryption_key,
+                &entry.value,
+            ) {
+                Ok(value) => value,
+                Err(_) => {
+                    if entry.key != INDEX_ID {
+                        unreadable += 1;
+                    }
+                    entry.value.clone()
+                }
+            };
+            reencrypted.push(VaultEntry { value, ..entry });
+        }
+        remote.change_master(&kdf, &keys, &reencrypted)?;
+        let count = reencrypted
+            .iter()
+            .filter(|entry| entry.key != INDEX_ID)
+            .count();
+        Ok::<_, Error>((count - unreadable, unreadable))
+    })?;
+
+    if let Err(err) = VaultCache::remove() {
+        eprintln!("Warning: failed to remove the offline vault copy: {}", err);
+    }
+
+    let session = RemoteSession::new(api, new_password)?;
+    config.session = Some(session.credentials());
+    if let Err(err) = config.save() {
+        eprintln!("Warning: failed to persist session: {}", err);
+    }
+
+    println!(
+        "{}",
+        format!(
+            "Master password changed. Re-encrypted {} entries.",
+            reencrypted
+        )
+        .green()
+    );
+    if unreadable > 0 {
+        println!(
+            "{}",
+            format!(
+                "{} unreadable entries were kept as they were. Run `x fsck` to review them.",
+                unreadable
+            )
+            .yellow()
+        );
+    }
+    println!(
+        "{}",
+        "All other sessions were revoked; those machines will be asked for the new password."
+            .yellow()
+    );
+    Ok(())
+}
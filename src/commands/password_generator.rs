@@ -1,49 +1,85 @@
+use crate::error::Error;
+use crate::output::{self, OutputFormat};
+use crate::utils::{is_interactive, prompt_aborted};
+use anstream::{eprintln, println};
+use clap::ValueEnum;
 use clipboard::{ClipboardContext, ClipboardProvider};
 use dialoguer::{Input, MultiSelect};
 use owo_colors::OwoColorize;
 use rand::Rng;
+use serde_json::json;
+use std::io::{self, IsTerminal};
 
-pub fn generate_password() {
+const DEFAULT_LENGTH: usize = 16;
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum CharClass {
+    Lower,
+    Upper,
+    Digits,
+    Symbols,
+}
+
+pub fn generate_password(length: Option<usize>, classes: Vec<CharClass>) -> Result<(), Error> {
     let lowercase: &str = "abcdefghijklmnopqrstuvwxyz";
     let uppercase: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
     let numbers: &str = "0123456789";
     let symbols: &str = "!@#$%^&*()-_=+[]{};:,.<>?";
 
-    let length: usize = Input::new()
-        .with_prompt("Enter desired password length")
-        .default(16)
-        .interact_text()
-        .unwrap();
+    let length = match length {
+        Some(length) => length,
+        None if is_interactive() => Input::new()
+            .with_prompt("Enter desired password length")
+            .default(DEFAULT_LENGTH)
+            .interact_text()
+            .map_err(prompt_aborted)?,
+        None => DEFAULT_LENGTH,
+    };
 
-    let selections: Vec<usize> = MultiSelect::new()
-        .with_prompt("Select character types to include")
-        .items(&["Lowercase", "Uppercase", "Numbers", "Symbols"])
-        .defaults(&[true, true, true, false])
-        .interact()
-        .unwrap();
+    let all = [
+        CharClass::Lower,
+        CharClass::Upper,
+        CharClass::Digits,
+        CharClass::Symbols,
+    ];
+    let selections: Vec<CharClass> = if !classes.is_empty() {
+        classes
+    } else if is_interactive() {
+        MultiSelect::new()
+            .with_prompt("Select character types to include")
+            .items(&["Lowercase", "Uppercase", "Numbers", "Symbols"])
+            .defaults(&[true, true, true, false])
+            .interact()
+            .map_err(prompt_aborted)?
+            .into_iter()
+            .map(|index| all[index])
+            .collect()
+    } else {
+        all[..3].to_vec()
+    };
 
     let mut charset: String = String::new();
-    if selections.is_empty() {
-        charset.push_str(lowercase);
-        charset.push_str(uppercase);
-        charset.push_str(numbers);
-        charset.push_str(symbols);
+    let selections = if selections.is_empty() {
+        all.to_vec()
     } else {
-        for i in selections {
-            match i {
-                0 => charset.push_str(lowercase),
-                1 => charset.push_str(uppercase),
-                2 => charset.push_str(numbers),
-                3 => charset.push_str(symbols),
-                _ => (),
-            }
+        selections
+    };
+    for class in all {
+        if selections.contains(&class) {
+            charset.push_str(match class {
+                CharClass::Lower => lowercase,
+                CharClass::Upper => uppercase,
+                CharClass::Digits => numbers,
+                CharClass::Symbols => symbols,
+            });
         }
     }
 
     let charset: Vec<char> = charset.chars().collect();
     if charset.is_empty() {
-        println!("{}", "No character types selected. Aborting.".red());
-        return;
+        return Err(Error::Usage(
+            "No character types selected. Aborting.".to_string(),
+        ));
     }
 
     let mut rng = rand::thread_rng();
@@ -54,9 +90,29 @@ pub fn generate_password() {
         })
         .collect();
 
+    match output::format() {
+        OutputFormat::Json => {
+            println!("{}", json!({ "password": password }));
+            return Ok(());
+        }
+        OutputFormat::Plain => {
+            println!("{}", password);
+            return Ok(());
+        }
+        OutputFormat::Table if !io::stdout().is_terminal() => {
+            println!("{}", password);
+            return Ok(());
+        }
+        OutputFormat::Table => {}
+    }
+
     println!("Generated Password: {}", password.green());
 
-    let mut ctx: ClipboardContext = ClipboardProvider::new().unwrap();
-    ctx.set_contents(password.clone()).unwrap();
-    println!("{}", "Password copied to clipboard.".green());
+    match ClipboardProvider::new()
+        .and_then(|mut ctx: ClipboardContext| ctx.set_contents(password.clone()))
+    {
+        Ok(()) => println!("{}", "Password copied to clipboard.".green()),
+        Err(err) => eprintln!("Warning: failed to copy to the clipboard: {}", err),
+    }
+    Ok(())
 }
@@ -0,0 +1,73 @@
+use crate::cloud::VaultSession;
+use crate::error::Error;
+use crate::vault::{SecretResolver, REFERENCE_SCHEME};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process::{self, Command};
+
+pub fn parse_variable(input: &str) -> Result<(String, String), String> {
+    input
+        .split_once('=')
+        .map(|(key, reference)| (key.trim().to_string(), reference.trim().to_string()))
+        .filter(|(key, reference)| !key.is_empty() && !reference.is_empty())
+        .ok_or_else(|| format!("Expected KEY=ITEM/FIELD, got `{}`", input))
+}
+
+pub fn run_command(
+    session: &VaultSession,
+    variables: Vec<(String, String)>,
+    env_file: Option<&Path>,
+    command: Vec<String>,
+) -> Result<(), Error> {
+    let mut resolver = SecretResolver::new(session)?;
+    let mut environment = Vec::new();
+
+    if let Some(path) = env_file {
+        let contents = fs::read_to_string(path)
+            .map_err(|err| format!("Failed to read {}: {}", path.display(), err))?;
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let line = line.strip_prefix("export ").unwrap_or(line);
+            let (key, value) = parse_variable(line).map_err(|err| {
+                Error::Usage(format!("{}:{}: {}", path.display(), number + 1, err))
+            })?;
+            let value = if value.starts_with(REFERENCE_SCHEME) {
+                resolver.resolve(&value).map_err(|err| err.context(&key))?
+            } else {
+                value
+            };
+            environment.push((key, value));
+        }
+    }
+
+    for (key, reference) in variables {
+        let value = resolver
+            .resolve(&reference)
+            .map_err(|err| err.context(&key))?;
+        environment.push((key, value));
+    }
+
+    let (program, args) = command
+        .split_first()
+        .ok_or_else(|| Error::Usage("Missing the command to run after `--`.".to_string()))?;
+    let status = Command::new(program)
+        .args(args)
+        .envs(environment)
+        .status()
+        .map_err(|err| {
+            let message = format!("Failed to start `{}`: {}", program, err);
+            match err.kind() {
+                io::ErrorKind::NotFound => Error::NotFound(message),
+                _ => Error::Failure(message),
+            }
+        })?;
+
+    if !status.success() {
+        process::exit(status.code().unwrap_or(1));
+    }
+    Ok(())
+}
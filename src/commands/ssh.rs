@@ -0,0 +1,152 @@
+use crate::cloud::VaultSession;
+use crate::error::Error;
+use crate::output::{self, OutputFormat};
+use crate::utils::{prompt_aborted, read_stdin_secret, require_interactive};
+use crate::vault::{new_entry_id, store_entry, SecureEntry, VaultItem};
+use anstream::println;
+use clap::{Subcommand, ValueEnum};
+use dialoguer::Password;
+use owo_colors::OwoColorize;
+use rand::rngs::OsRng;
+use serde_json::json;
+use ssh_key::{Algorithm, HashAlg, LineEnding, PrivateKey};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum KeyType {
+    Ed25519,
+    Rsa,
+}
+
+#[derive(Subcommand, Clone)]
+pub enum SshCommand {
+    Import {
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+        #[arg(long)]
+        name: Option<String>,
+        #[arg(long)]
+        passphrase_stdin: bool,
+    },
+    Generate {
+        #[arg(long = "type", value_enum, default_value_t = KeyType::Ed25519)]
+        key_type: KeyType,
+        #[arg(long)]
+        name: Option<String>,
+        #[arg(long)]
+        comment: Option<String>,
+    },
+}
+
+pub fn handle_ssh_command(session: &VaultSession, command: SshCommand) -> Result<(), Error> {
+    match command {
+        SshCommand::Import {
+            file,
+            name,
+            passphrase_stdin,
+        } => import_key(session, &file, name, passphrase_stdin),
+        SshCommand::Generate {
+            key_type,
+            name,
+            comment,
+        } => generate_key(session, key_type, name, comment),
+    }
+}
+
+fn import_key(
+    session: &VaultSession,
+    file: &Path,
+    name: Option<String>,
+    passphrase_stdin: bool,
+) -> Result<(), Error> {
+    let pem = fs::read_to_string(file)
+        .map_err(|err| format!("Failed to read {}: {}", file.display(), err))?;
+    let mut key = PrivateKey::from_openssh(pem.trim()).map_err(|err| {
+        Error::Usage(format!(
+            "{} is not an OpenSSH private key: {}",
+            file.display(),
+            err
+        ))
+    })?;
+
+    if key.is_encrypted() {
+        let passphrase = if passphrase_stdin {
+            read_stdin_secret()?
+        } else {
+            require_interactive("--passphrase-stdin")?;
+            Password::new()
+                .with_prompt("Enter the key passphrase")
+                .interact()
+                .map_err(prompt_aborted)?
+        };
+        key = key
+            .decrypt(passphrase.as_bytes())
+            .map_err(|_| Error::Auth("Wrong passphrase for the SSH key".to_string()))?;
+    }
+
+    let name = name.unwrap_or_else(|| {
+        file.file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| "SSH key".to_string())
+    });
+    store_key(session, name, &key)
+}
+
+fn generate_key(
+    session: &VaultSession,
+    key_type: KeyType,
+    name: Option<String>,
+    comment: Option<String>,
+) -> Result<(), Error> {
+    let algorithm = match key_type {
+        KeyType::Ed25519 => Algorithm::Ed25519,
+        KeyType::Rsa => Algorithm::Rsa { hash: None },
+    };
+    let mut key = PrivateKey::random(&mut OsRng, algorithm)
+        .map_err(|err| format!("Failed to generate the SSH key: {}", err))?;
+    let name = match name {
+        Some(name) => name,
+        None => format!("{} key", key.algorithm().as_str()),
+    };
+    key.set_comment(comment.unwrap_or_else(|| name.clone()));
+    store_key(session, name, &key)
+}
+
+fn store_key(session: &VaultSession, name: String, key: &PrivateKey) -> Result<(), Error> {
+    let private_key = key
+        .to_openssh(LineEnding::LF)
+        .map_err(|err| format!("Failed to encode the SSH key: {}", err))?;
+    let public_key = key
+        .public_key()
+        .to_openssh()
+        .map_err(|err| format!("Failed to encode the SSH key: {}", err))?;
+    let fingerprint = key.fingerprint(HashAlg::Sha256).to_string();
+    let item = VaultItem::SshKey {
+        name: name.clone(),
+        private_key: private_key.to_string(),
+        public_key: public_key.clone(),
+        comment: key.comment().to_string(),
+        fingerprint: fingerprint.clone(),
+    };
+
+    let stored = SecureEntry::encrypt(session.encryption_key(), &item)?.serialize()?;
+    let id = new_entry_id();
+    store_entry(session, &id, &stored, 0)?;
+
+    match output::format() {
+        OutputFormat::Json => output::print_json(&json!({
+            "id": id,
+            "name": name,
+            "public_key": public_key,
+            "fingerprint": fingerprint,
+        }))?,
+        OutputFormat::Plain => println!("{}", public_key),
+        OutputFormat::Table => {
+            println!(" Successfully stored: {}", name.green());
+            println!("{} {}", "Fingerprint:".cyan(), fingerprint);
+            println!("{}", public_key);
+        }
+    }
+    Ok(())
+}
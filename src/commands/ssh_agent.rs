@@ -0,0 +1,365 @@
+use crate::cloud::VaultSession;
+use crate::error::Error;
+use crate::vault::{load_all_entries, SecureEntry, VaultItem};
+use anstream::{eprintln, println};
+use owo_colors::OwoColorize;
+use ssh_key::{
+    private::{KeypairData, RsaKeypair},
+    Algorithm, HashAlg, Mpint, PrivateKey, Signature,
+};
+use std::path::PathBuf;
+
+const SSH_AGENT_FAILURE: u8 = 5;
+const SSH_AGENTC_REQUEST_IDENTITIES: u8 = 11;
+const SSH_AGENT_IDENTITIES_ANSWER: u8 = 12;
+const SSH_AGENTC_SIGN_REQUEST: u8 = 13;
+const SSH_AGENT_SIGN_RESPONSE: u8 = 14;
+const SSH_AGENT_RSA_SHA2_256: u32 = 2;
+const SSH_AGENT_RSA_SHA2_512: u32 = 4;
+const MAX_MESSAGE_LEN: usize = 256 * 1024;
+
+struct AgentKey {
+    blob: Vec<u8>,
+    comment: String,
+    key: PrivateKey,
+}
+
+pub fn run_agent(session: &VaultSession, socket: Option<PathBuf>) -> Result<(), Error> {
+    let keys = load_keys(session)?;
+    if keys.is_empty() {
+        eprintln!(
+            "{}",
+            "No SSH keys in the vault yet. Add one with `x ssh generate` or `x ssh import`."
+                .yellow()
+        );
+    }
+    serve(keys, socket.unwrap_or_else(default_socket_path))
+}
+
+fn load_keys(session: &VaultSession) -> Result<Vec<AgentKey>, Error> {
+    let mut keys = Vec::new();
+    for entry in load_all_entries(session)? {
+        let Ok(VaultItem::SshKey {
+            name,
+            private_key,
+            comment,
+            ..
+        }) = SecureEntry::decrypt(session.encryption_key(), &entry.value)
+        else {
+            continue;
+        };
+        let loaded = PrivateKey::from_openssh(&private_key).and_then(|key| {
+            let blob = key.public_key().to_bytes()?;
+            Ok((blob, key))
+        });
+        match loaded {
+            Ok((blob, key)) => keys.push(AgentKey {
+                blob,
+                comment: if comment.is_empty() { name } else { comment },
+                key,
+            }),
+            Err(err) => eprintln!("Warning: skipping SSH key {}: {}", name, err),
+        }
+    }
+    Ok(keys)
+}
+
+fn default_socket_path() -> PathBuf {
+    let mut path = dirs::runtime_dir()
+        .or_else(dirs::data_dir)
+        .unwrap_or_else(|| PathBuf::from("."));
+    path.push("x_cli");
+    path.push("ssh-agent.sock");
+    path
+}
+
+#[cfg(unix)]
+fn serve(keys: Vec<AgentKey>, socket: PathBuf) -> Result<(), Error> {
+    use std::fs;
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+    use std::os::unix::net::UnixListener;
+    use std::path::Path;
+    use std::sync::Arc;
+    use std::thread;
+
+    // The socket is only as private as its directory: it is created with the
+    // process umask before it can be chmod'ed, so the directory must already
+    // keep other users out when it is bound.
+    let parent = socket
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(parent)
+        .map_err(|err| format!("Failed to create {}: {}", parent.display(), err))?;
+    if socket == default_socket_path() {
+        fs::set_permissions(parent, fs::Permissions::from_mode(0o700))
+            .map_err(|err| format!("Failed to protect {}: {}", parent.display(), err))?;
+    }
+    let mode = fs::metadata(parent)
+        .map_err(|err| format!("Failed to inspect {}: {}", parent.display(), err))?
+        .permissions()
+        .mode();
+    if mode & 0o077 != 0 {
+        return Err(Error::Usage(format!(
+            "{} is accessible to other users. Pass a --socket path inside a private directory (mode 700).",
+            parent.display()
+        )));
+    }
+    if socket.exists() {
+        fs::remove_file(&socket).map_err(|err| {
+            format!(
+                "Failed to remove the old socket {}: {}",
+                socket.display(),
+                err
+            )
+        })?;
+    }
+    let listener = UnixListener::bind(&socket)
+        .map_err(|err| format!("Failed to listen on {}: {}", socket.display(), err))?;
+    fs::set_permissions(&socket, fs::Permissions::from_mode(0o600))
+        .map_err(|err| format!("Failed to protect {}: {}", socket.display(), err))?;
+
+    println!("SSH_AUTH_SOCK={}; export SSH_AUTH_SOCK;", socket.display());
+    eprintln!(
+        "{}",
+        format!(
+            "Serving {} SSH key(s). Keys added later are picked up after a restart.",
+            keys.len()
+        )
+        .green()
+    );
+
+    let keys = Arc::new(keys);
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else {
+            continue;
+        };
+        let keys = Arc::clone(&keys);
+        thread::spawn(move || {
+            if let Err(err) = handle_client(stream, &keys) {
+                eprintln!("Warning: SSH agent client error: {}", err);
+            }
+        });
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn serve(_keys: Vec<AgentKey>, _socket: PathBuf) -> Result<(), Error> {
+    Err(Error::Usage(
+        "`x ssh-agent` needs Unix domain sockets and is not available on this platform."
+            .to_string(),
+    ))
+}
+
+#[cfg(unix)]
+fn handle_client(
+    mut stream: std::os::unix::net::UnixStream,
+    keys: &[AgentKey],
+) -> std::io::Result<()> {
+    use std::io::{ErrorKind, Read, Write};
+
+    loop {
+        let mut length = [0u8; 4];
+        match stream.read_exact(&mut length) {
+            Ok(()) => {}
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(()),
+            Err(err) => return Err(err),
+        }
+        let length = u32::from_be_bytes(length) as usize;
+        if length == 0 || length > MAX_MESSAGE_LEN {
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidData,
+                "invalid agent message length",
+            ));
+        }
+        let mut message = vec![0u8; length];
+        stream.read_exact(&mut message)?;
+
+        let reply = respond(&message, keys).unwrap_or_else(|| vec![SSH_AGENT_FAILURE]);
+        stream.write_all(&(reply.len() as u32).to_be_bytes())?;
+        stream.write_all(&reply)?;
+    }
+}
+
+fn respond(message: &[u8], keys: &[AgentKey]) -> Option<Vec<u8>> {
+    let (&kind, mut payload) = message.split_first()?;
+    match kind {
+        SSH_AGENTC_REQUEST_IDENTITIES => {
+            let mut reply = vec![SSH_AGENT_IDENTITIES_ANSWER];
+            reply.extend_from_slice(&(keys.len() as u32).to_be_bytes());
+            for key in keys {
+                put_string(&mut reply, &key.blob);
+                put_string(&mut reply, key.comment.as_bytes());
+            }
+            Some(reply)
+        }
+        SSH_AGENTC_SIGN_REQUEST => {
+            let blob = take_string(&mut payload)?;
+            let data = take_string(&mut payload)?;
+            let flags = take_u32(&mut payload).unwrap_or(0);
+            let key = keys.iter().find(|key| key.blob == blob)?;
+            let signature = sign(&key.key, data, flags)?;
+
+            let mut encoded = Vec::new();
+            put_string(&mut encoded, signature.algorithm().as_str().as_bytes());
+            put_string(&mut encoded, signature.as_bytes());
+            let mut reply = vec![SSH_AGENT_SIGN_RESPONSE];
+            put_string(&mut reply, &encoded);
+            Some(reply)
+        }
+        _ => None,
+    }
+}
+
+fn sign(key: &PrivateKey, data: &[u8], flags: u32) -> Option<Signature> {
+    use signature::{SignatureEncoding, Signer};
+
+    match key.key_data() {
+        KeypairData::Rsa(keypair) if flags & SSH_AGENT_RSA_SHA2_512 != 0 => {
+            let signer =
+                rsa::pkcs1v15::SigningKey::<ssh_key::sha2::Sha512>::new(rsa_private_key(keypair)?);
+            rsa_signature(HashAlg::Sha512, signer.try_sign(data).ok()?.to_vec())
+        }
+        KeypairData::Rsa(keypair) if flags & SSH_AGENT_RSA_SHA2_256 != 0 => {
+            let signer =
+                rsa::pkcs1v15::SigningKey::<ssh_key::sha2::Sha256>::new(rsa_private_key(keypair)?);
+            rsa_signature(HashAlg::Sha256, signer.try_sign(data).ok()?.to_vec())
+        }
+        // A request without either flag asks for a legacy SHA-1 `ssh-rsa`
+        // signature, which the agent refuses rather than answering with a
+        // different algorithm.
+        KeypairData::Rsa(_) => None,
+        _ => key.try_sign(data).ok(),
+    }
+}
+
+// ssh-key's own conversion passes `p` twice instead of `p` and `q`, so the
+// private key is rebuilt from its components here.
+fn rsa_private_key(keypair: &RsaKeypair) -> Option<rsa::RsaPrivateKey> {
+    let uint = |value: &Mpint| value.as_positive_bytes().map(rsa::BigUint::from_bytes_be);
+    rsa::RsaPrivateKey::from_components(
+        uint(&keypair.public.n)?,
+        uint(&keypair.public.e)?,
+        uint(&keypair.private.d)?,
+        vec![uint(&keypair.private.p)?, uint(&keypair.private.q)?],
+    )
+    .ok()
+}
+
+fn rsa_signature(hash: HashAlg, signature: Vec<u8>) -> Option<Signature> {
+    Signature::new(Algorithm::Rsa { hash: Some(hash) }, signature).ok()
+}
+
+fn put_string(buffer: &mut Vec<u8>, value: &[u8]) {
+    buffer.extend_from_slice(&(value.len() as u32).to_be_bytes());
+    buffer.extend_from_slice(value);
+}
+
+fn take_u32(payload: &mut &[u8]) -> Option<u32> {
+    let (bytes, rest) = payload.split_first_chunk::<4>()?;
+    *payload = rest;
+    Some(u32::from_be_bytes(*bytes))
+}
+
+fn take_string<'a>(payload: &mut &'a [u8]) -> Option<&'a [u8]> {
+    let length = take_u32(payload)? as usize;
+    if payload.len() < length {
+        return None;
+    }
+    let (value, rest) = payload.split_at(length);
+    *payload = rest;
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+    use signature::Verifier;
+
+    fn agent_key() -> AgentKey {
+        let key = PrivateKey::random(&mut OsRng, Algorithm::Ed25519).unwrap();
+        AgentKey {
+            blob: key.public_key().to_bytes().unwrap(),
+            comment: "me@laptop".to_string(),
+            key,
+        }
+    }
+
+    fn sign_request(blob: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut message = vec![SSH_AGENTC_SIGN_REQUEST];
+        put_string(&mut message, blob);
+        put_string(&mut message, data);
+        message.extend_from_slice(&0u32.to_be_bytes());
+        message
+    }
+
+    #[test]
+    fn reads_length_prefixed_strings() {
+        let mut buffer = Vec::new();
+        put_string(&mut buffer, b"ssh-ed25519");
+        buffer.extend_from_slice(&7u32.to_be_bytes());
+
+        let mut payload = buffer.as_slice();
+        assert_eq!(take_string(&mut payload), Some(&b"ssh-ed25519"[..]));
+        assert_eq!(take_u32(&mut payload), Some(7));
+        assert_eq!(take_u32(&mut payload), None);
+
+        let mut truncated: &[u8] = &[0, 0, 0, 9, b'a'];
+        assert_eq!(take_string(&mut truncated), None);
+    }
+
+    #[test]
+    fn lists_identities() {
+        let key = agent_key();
+        let reply = respond(&[SSH_AGENTC_REQUEST_IDENTITIES], std::slice::from_ref(&key)).unwrap();
+
+        let (&kind, mut payload) = reply.split_first().unwrap();
+        assert_eq!(kind, SSH_AGENT_IDENTITIES_ANSWER);
+        assert_eq!(take_u32(&mut payload), Some(1));
+        assert_eq!(take_string(&mut payload), Some(key.blob.as_slice()));
+        assert_eq!(take_string(&mut payload), Some(&b"me@laptop"[..]));
+        assert!(payload.is_empty());
+    }
+
+    #[test]
+    fn signs_with_the_requested_key() {
+        let key = agent_key();
+        let reply = respond(
+            &sign_request(&key.blob, b"challenge"),
+            std::slice::from_ref(&key),
+        )
+        .unwrap();
+
+        let (&kind, mut payload) = reply.split_first().unwrap();
+        assert_eq!(kind, SSH_AGENT_SIGN_RESPONSE);
+        let mut encoded = take_string(&mut payload).unwrap();
+        let algorithm = take_string(&mut encoded).unwrap();
+        let signature = take_string(&mut encoded).unwrap();
+        assert_eq!(algorithm, b"ssh-ed25519");
+
+        let signature = Signature::new(Algorithm::Ed25519, signature.to_vec()).unwrap();
+        assert!(key
+            .key
+            .public_key()
+            .key_data()
+            .verify(b"challenge", &signature)
+            .is_ok());
+    }
+
+    #[test]
+    fn fails_unknown_keys_and_malformed_requests() {
+        let key = agent_key();
+        let keys = std::slice::from_ref(&key);
+        let other = agent_key();
+
+        assert!(respond(&sign_request(&other.blob, b"challenge"), keys).is_none());
+        assert!(respond(&[SSH_AGENTC_SIGN_REQUEST, 0, 0, 0, 4], keys).is_none());
+        assert!(respond(&[], keys).is_none());
+        assert!(respond(&[17], keys).is_none());
+    }
+}
@@ -0,0 +1,51 @@
+use crate::cloud::VaultSession;
+use crate::error::Error;
+use crate::output::{self, OutputFormat};
+use crate::security::totp::Totp;
+use crate::utils::copy_to_clipboard;
+use crate::vault::{open_item, select_entry, VaultItem};
+use anstream::println;
+use owo_colors::OwoColorize;
+use serde_json::json;
+
+pub fn show_code(session: &VaultSession, name: Option<String>) -> Result<(), Error> {
+    let Some(selected) = select_entry(session, name.as_deref(), "Select an item:")? else {
+        return Ok(());
+    };
+
+    let totp = match open_item(session, &selected.id)?.1.item {
+        VaultItem::Password { totp, .. } if !totp.is_empty() => totp,
+        _ => {
+            return Err(Error::NotFound(format!(
+                "{} has no TOTP secret. Add one with `x edit \"{}\" --set totp=<secret>`.",
+                selected.name, selected.name
+            )))
+        }
+    };
+    let totp = Totp::parse(&totp)
+        .map_err(|err| Error::from(format!("Stored TOTP secret is invalid: {}", err)))?;
+    let (code, remaining) = totp.current();
+
+    match output::format() {
+        OutputFormat::Json => output::print_json(&json!({
+            "id": selected.id,
+            "name": selected.name,
+            "code": code,
+            "remaining": remaining,
+            "period": totp.period(),
+        })),
+        OutputFormat::Plain => {
+            println!("{}", code);
+            Ok(())
+        }
+        OutputFormat::Table => {
+            println!(
+                "{} {} {}",
+                "One-time code:".cyan(),
+                code.bold().green(),
+                format!("({}s left)", remaining).dimmed()
+            );
+            copy_to_clipboard(code)
+        }
+    }
+}
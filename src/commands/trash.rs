@@ -0,0 +1,158 @@
+use crate::cloud::{RemoteSession, VaultSession};
+use crate::error::Error;
+use crate::output::{self, OutputFormat};
+use crate::utils::{confirm_or_yes, format_timestamp};
+use crate::vault::{
+    choose_entry, entry_labels, list_trashed_entries, retry_on_conflict, NamedEntry,
+};
+use anstream::println;
+use clap::Subcommand;
+use owo_colors::OwoColorize;
+use serde_json::json;
+
+#[derive(Subcommand, Clone)]
+pub enum TrashCommand {
+    List,
+    Restore {
+        #[arg(value_name = "NAME")]
+        name: Option<String>,
+    },
+    Empty {
+        #[arg(value_name = "NAME")]
+        name: Option<String>,
+        #[arg(long, short)]
+        yes: bool,
+    },
+}
+
+pub fn handle_trash_command(session: &VaultSession, command: TrashCommand) -> Result<(), Error> {
+    let remote = session.remote()?;
+
+    match command {
+        TrashCommand::List => list_trash(session),
+        TrashCommand::Restore { name } => restore_item(session, remote, name),
+        TrashCommand::Empty { name, yes } => empty_trash(session, remote, name, yes),
+    }
+}
+
+fn list_trash(session: &VaultSession) -> Result<(), Error> {
+    let trashed = list_trashed_entries(session)?;
+
+    match output::format() {
+        OutputFormat::Json => {
+            let items: Vec<_> = trashed
+                .iter()
+                .map(|(entry, deleted_at)| {
+                    json!({
+                        "id": entry.id,
+                        "name": entry.name,
+                        "type": entry.kind,
+                        "deleted_at": deleted_at,
+                    })
+                })
+                .collect();
+            return output::print_json(&items);
+        }
+        OutputFormat::Plain => {
+            for (entry, deleted_at) in &trashed {
+                println!(
+                    "{}\t{}\t{}\t{}",
+                    entry.id, entry.name, entry.kind, deleted_at
+                );
+            }
+            return Ok(());
+        }
+        OutputFormat::Table => {}
+    }
+
+    println!("{}", "Trash:".yellow().bold());
+    if trashed.is_empty() {
+        println!("{}", "The trash is empty.".red());
+        return Ok(());
+    }
+
+    let (entries, deleted_at): (Vec<NamedEntry>, Vec<u64>) = trashed.into_iter().unzip();
+    for (label, deleted_at) in entry_labels(&entries).into_iter().zip(deleted_at) {
+        println!(
+            "  - {}  deleted {}",
+            label.bold().red(),
+            format_timestamp(deleted_at)
+        );
+    }
+    Ok(())
+}
+
+fn restore_item(
+    session: &VaultSession,
+    remote: &RemoteSession,
+    argument: Option<String>,
+) -> Result<(), Error> {
+    let Some(entry) = pick_trashed(session, argument, "Select item to restore")? else {
+        return Ok(());
+    };
+
+    retry_on_conflict(|| {
+        let revision = remote.fetch_trashed(&entry.id)?.revision;
+        remote.restore_trashed(&entry.id, revision)
+    })?;
+    session.synchronize()?;
+
+    output::success(&format!("Restored: {}", entry.name));
+    Ok(())
+}
+
+fn empty_trash(
+    session: &VaultSession,
+    remote: &RemoteSession,
+    argument: Option<String>,
+    yes: bool,
+) -> Result<(), Error> {
+    if argument
+        .as_deref()
+        .is_some_and(|name| !name.trim().is_empty())
+    {
+        let Some(entry) = pick_trashed(session, argument, "Select item to delete")? else {
+            return Ok(());
+        };
+        if !confirm_or_yes(yes, &format!("Permanently delete {}?", entry.name))? {
+            return Err(Error::Cancelled("Trash kept.".to_string()));
+        }
+        retry_on_conflict(|| {
+            let revision = remote.fetch_trashed(&entry.id)?.revision;
+            remote.purge_trashed(&entry.id, revision)
+        })?;
+        output::success(&format!("Deleted permanently: {}", entry.name));
+        return Ok(());
+    }
+
+    let count = remote.list_trash()?.len();
+    if count == 0 {
+        output::success("The trash is already empty.");
+        return Ok(());
+    }
+
+    if !confirm_or_yes(yes, &format!("Permanently delete {} item(s)?", count))? {
+        return Err(Error::Cancelled("Trash kept.".to_string()));
+    }
+
+    let message = remote.empty_trash()?;
+    output::success(&message);
+    Ok(())
+}
+
+fn pick_trashed(
+    session: &VaultSession,
+    argument: Option<String>,
+    prompt: &str,
+) -> Result<Option<NamedEntry>, Error> {
+    let entries: Vec<NamedEntry> = list_trashed_entries(session)?
+        .into_iter()
+        .map(|(entry, _)| entry)
+        .collect();
+
+    if entries.is_empty() {
+        println!("{}", "The trash is empty.".red());
+        return Ok(None);
+    }
+    choose_entry(entries, argument.as_deref(), prompt).map(Some)
+}
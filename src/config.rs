@@ -1,3 +1,4 @@
+use crate::utils::write_private_file;
 use dirs::data_dir;
 use serde::{Deserialize, Serialize};
 use std::fs;
@@ -7,7 +8,18 @@ use std::path::PathBuf;
 #[derive(Debug, Serialize, Deserialize, Default, Clone)]
 pub struct AppConfig {
     pub base_url: Option<String>,
-    pub master_hash: Option<String>,
+    #[serde(default)]
+    pub session: Option<SessionConfig>,
+    #[serde(default)]
+    pub tls_fingerprint: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SessionConfig {
+    pub token: String,
+    pub expires_at: u64,
+    #[serde(default)]
+    pub wrapped_key: String,
 }
 
 impl AppConfig {
@@ -25,13 +37,9 @@ impl AppConfig {
         if let Some(parent) = path.parent() {
             fs::create_dir_all(parent)?;
         }
-        let data = serde_json::to_vec_pretty(self).map_err(|err| {
-            io::Error::new(
-                io::ErrorKind::Other,
-                format!("Failed to serialize config: {}", err),
-            )
-        })?;
-        fs::write(path, data)
+        let data = serde_json::to_vec_pretty(self)
+            .map_err(|err| io::Error::other(format!("Failed to serialize config: {}", err)))?;
+        write_private_file(&path, &data)
     }
 }
 
@@ -0,0 +1,128 @@
+use crate::cloud::{CloudClientError, ServerError};
+use crate::security::encryption::DecryptError;
+use std::fmt;
+
+#[derive(Debug)]
+pub enum Error {
+    Failure(String),
+    Usage(String),
+    Auth(String),
+    Network(String),
+    NotFound(String),
+    Conflict(String),
+    Decrypt(String),
+    Cancelled(String),
+}
+
+impl Error {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Failure(_) => 1,
+            Error::Usage(_) => 2,
+            Error::Auth(_) => 3,
+            Error::Network(_) => 4,
+            Error::NotFound(_) => 5,
+            Error::Conflict(_) => 6,
+            Error::Decrypt(_) => 7,
+            Error::Cancelled(_) => 8,
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::Failure(_) => "failure",
+            Error::Usage(_) => "usage",
+            Error::Auth(_) => "auth",
+            Error::Network(_) => "network",
+            Error::NotFound(_) => "not_found",
+            Error::Conflict(_) => "conflict",
+            Error::Decrypt(_) => "decrypt",
+            Error::Cancelled(_) => "cancelled",
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            Error::Failure(msg)
+            | Error::Usage(msg)
+            | Error::Auth(msg)
+            | Error::Network(msg)
+            | Error::NotFound(msg)
+            | Error::Conflict(msg)
+            | Error::Decrypt(msg)
+            | Error::Cancelled(msg) => msg,
+        }
+    }
+
+    pub fn context(self, prefix: &str) -> Self {
+        let wrap = |msg: String| format!("{}: {}", prefix, msg);
+        match self {
+            Error::Failure(msg) => Error::Failure(wrap(msg)),
+            Error::Usage(msg) => Error::Usage(wrap(msg)),
+            Error::Auth(msg) => Error::Auth(wrap(msg)),
+            Error::Network(msg) => Error::Network(wrap(msg)),
+            Error::NotFound(msg) => Error::NotFound(wrap(msg)),
+            Error::Conflict(msg) => Error::Conflict(wrap(msg)),
+            Error::Decrypt(msg) => Error::Decrypt(wrap(msg)),
+            Error::Cancelled(msg) => Error::Cancelled(wrap(msg)),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message())
+    }
+}
+
+impl From<String> for Error {
+    fn from(value: String) -> Self {
+        Error::Failure(value)
+    }
+}
+
+impl From<&str> for Error {
+    fn from(value: &str) -> Self {
+        Error::Failure(value.to_string())
+    }
+}
+
+impl From<DecryptError> for Error {
+    fn from(value: DecryptError) -> Self {
+        Error::Decrypt(format!("Decryption failed: {}", value))
+    }
+}
+
+impl From<CloudClientError> for Error {
+    fn from(value: CloudClientError) -> Self {
+        let message = value.to_string();
+        match value {
+            CloudClientError::Http(_) => Error::Network(message),
+            CloudClientError::AuthenticationFailed
+            | CloudClientError::SessionExpired
+            | CloudClientError::PasswordChanged
+            | CloudClientError::CertificateChanged(_)
+            | CloudClientError::CertificateUntrusted(_) => Error::Auth(message),
+            CloudClientError::NotFound(_) => Error::NotFound(message),
+            CloudClientError::Conflict(_) => Error::Conflict(message),
+            CloudClientError::Failure(_) => Error::Failure(message),
+        }
+    }
+}
+
+impl From<ServerError> for Error {
+    fn from(value: ServerError) -> Self {
+        let message = value.to_string();
+        match value {
+            ServerError::Unauthorized
+            | ServerError::SessionExpired
+            | ServerError::PasswordChanged
+            | ServerError::MissingMasterPassword => Error::Auth(message),
+            ServerError::NotFound(_) | ServerError::HistoryUnavailable(..) => {
+                Error::NotFound(message)
+            }
+            ServerError::Conflict(..) => Error::Conflict(message),
+            _ => Error::Failure(message),
+        }
+    }
+}
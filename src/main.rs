@@ -1,37 +1,148 @@
 mod cloud;
 mod commands;
 mod config;
+mod error;
+mod output;
 mod security;
 mod utils;
 mod vault;
 
-use crate::cloud::{CloudClientError, RemoteSession};
 use crate::config::AppConfig;
-use crate::security::master_password;
+use crate::error::Error;
+use crate::output::OutputFormat;
 use clap::{Parser, Subcommand};
-use owo_colors::OwoColorize;
+use std::env;
+use std::ffi::OsString;
+use std::path::Path;
+use std::process;
+
+const GIT_CREDENTIAL_HELPER: &str = "git-credential-x";
+const DOCKER_CREDENTIAL_HELPER: &str = "docker-credential-x";
 
 #[derive(Parser)]
 #[command(name = "x", version = "0.1.6", about = "X CLI")]
 struct Cli {
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Table)]
+    format: OutputFormat,
     #[command(subcommand)]
     command: Commands,
 }
 
 #[derive(Subcommand, Clone)]
 enum Commands {
-    Add,
-    Get,
-    List,
-    Delete,
-    Edit,
-    Passgen,
-    Import,
-    Export,
+    Add(commands::add::AddArgs),
+    Get {
+        #[arg(value_name = "NAME")]
+        name: Option<String>,
+        #[arg(long)]
+        field: Option<String>,
+        #[arg(long)]
+        reveal: bool,
+    },
+    List {
+        #[arg(long)]
+        tag: Vec<String>,
+        #[arg(long)]
+        folder: Option<String>,
+    },
+    Delete {
+        #[arg(value_name = "NAME")]
+        name: Option<String>,
+        #[arg(long, short)]
+        yes: bool,
+    },
+    Edit(commands::edit::EditArgs),
+    Attach {
+        #[arg(value_name = "ITEM")]
+        item: String,
+        #[arg(value_name = "FILE")]
+        file: std::path::PathBuf,
+        #[arg(long)]
+        name: Option<String>,
+    },
+    Attachments {
+        #[command(subcommand)]
+        command: commands::attachments::AttachmentsCommand,
+    },
+    Passgen {
+        #[arg(long)]
+        length: Option<usize>,
+        #[arg(long, value_enum, value_delimiter = ',')]
+        chars: Vec<commands::password_generator::CharClass>,
+    },
+    Passwd {
+        #[arg(long)]
+        new_password_stdin: bool,
+    },
+    Import {
+        #[arg(long)]
+        file: Option<String>,
+        #[arg(long)]
+        overwrite: bool,
+    },
+    Export {
+        #[arg(long)]
+        file: Option<String>,
+    },
+    History,
+    Run {
+        #[arg(long = "env", value_name = "KEY=ITEM/FIELD", value_parser = commands::run::parse_variable)]
+        variables: Vec<(String, String)>,
+        #[arg(long, value_name = "FILE")]
+        env_file: Option<std::path::PathBuf>,
+        #[arg(last = true, required = true, value_name = "COMMAND")]
+        command: Vec<String>,
+    },
+    Inject {
+        #[arg(long, short, value_name = "TEMPLATE")]
+        input: Option<std::path::PathBuf>,
+        #[arg(long, short, value_name = "FILE")]
+        output: Option<std::path::PathBuf>,
+        #[arg(long)]
+        check: bool,
+    },
+    Ssh {
+        #[command(subcommand)]
+        command: commands::ssh::SshCommand,
+    },
+    SshAgent {
+        #[arg(long, value_name = "PATH")]
+        socket: Option<std::path::PathBuf>,
+    },
+    DockerCredential {
+        #[arg(value_name = "ACTION")]
+        action: String,
+    },
+    GitCredential {
+        #[arg(value_name = "ACTION")]
+        action: String,
+    },
+    Totp {
+        #[arg(value_name = "NAME")]
+        name: Option<String>,
+    },
+    Fsck {
+        #[arg(long, short)]
+        yes: bool,
+    },
+    Restore {
+        #[arg(value_name = "REVISION")]
+        revision: u64,
+        #[arg(long, short)]
+        yes: bool,
+    },
+    Trash {
+        #[command(subcommand)]
+        command: Option<commands::trash::TrashCommand>,
+    },
     Update,
     Cloud {
         #[arg(value_name = "TARGET")]
         target: Option<String>,
+        #[arg(value_name = "ARGUMENT")]
+        argument: Option<String>,
+        #[arg(long, short)]
+        yes: bool,
     },
     Host {
         #[arg(long, default_value = "0.0.0.0")]
@@ -40,78 +151,188 @@ enum Commands {
         port: u16,
         #[arg(long)]
         data: Option<std::path::PathBuf>,
+        #[arg(long, value_name = "KIB", default_value_t = security::kdf::DEFAULT_MEMORY_KIB)]
+        kdf_memory: u32,
+        #[arg(long, default_value_t = security::kdf::DEFAULT_ITERATIONS)]
+        kdf_iterations: u32,
+        #[arg(long, value_name = "SECONDS", default_value_t = cloud::server::DEFAULT_SESSION_TTL_SECS)]
+        session_ttl: u64,
+        #[arg(long)]
+        revoke_sessions: bool,
+        #[arg(long, value_name = "REVISIONS", default_value_t = cloud::server::DEFAULT_HISTORY_LIMIT)]
+        history: usize,
+        #[arg(long, value_name = "DAYS", default_value_t = cloud::server::DEFAULT_TRASH_RETENTION_DAYS)]
+        trash_days: u64,
+        #[arg(long, value_name = "MIB", default_value_t = cloud::server::DEFAULT_ATTACHMENT_MAX_MIB)]
+        attachment_max: u64,
+        #[arg(long, value_name = "MIB", default_value_t = cloud::server::DEFAULT_ATTACHMENT_QUOTA_MIB)]
+        attachment_quota: u64,
+        #[arg(long)]
+        tls: bool,
+        #[arg(long, value_name = "PEM", requires = "tls_key")]
+        tls_cert: Option<std::path::PathBuf>,
+        #[arg(long, value_name = "PEM", requires = "tls_cert")]
+        tls_key: Option<std::path::PathBuf>,
     },
 }
 
 fn main() {
-    let cli = Cli::parse();
+    let cli = Cli::parse_from(command_line());
+    output::init(cli.format);
     let mut app_config = AppConfig::load();
 
     match cli.command {
-        Commands::Host { bind, port, data } => {
-            let mut config = cloud::ServerConfig::default();
-            config.bind_address = bind;
-            config.port = port;
-            config.data_path = data;
+        Commands::Host {
+            bind,
+            port,
+            data,
+            kdf_memory,
+            kdf_iterations,
+            session_ttl,
+            revoke_sessions,
+            history,
+            trash_days,
+            attachment_max,
+            attachment_quota,
+            tls,
+            tls_cert,
+            tls_key,
+        } => {
+            let config = cloud::ServerConfig {
+                bind_address: bind,
+                port,
+                data_path: data,
+                kdf_memory_kib: kdf_memory,
+                kdf_iterations,
+                session_ttl_secs: session_ttl,
+                revoke_sessions,
+                history_limit: history,
+                trash_retention_days: trash_days,
+                attachment_max_bytes: attachment_max * cloud::server::MIB,
+                attachment_quota_bytes: attachment_quota * cloud::server::MIB,
+                tls: tls || tls_cert.is_some(),
+                tls_cert,
+                tls_key,
+                ..Default::default()
+            };
 
-            if let Err(e) = commands::host::host_server(config) {
-                eprintln!("Failed to host cloud API server: {}", e);
-            }
+            report(
+                commands::host::host_server(config)
+                    .map_err(|err| err.context("Failed to host cloud API server")),
+            );
         }
-        Commands::Cloud { target } => {
-            if let Err(err) = commands::cloud::handle_cloud_command(&mut app_config, target) {
-                eprintln!("{}", err.red());
-            }
+        Commands::Cloud {
+            target,
+            argument,
+            yes,
+        } => {
+            report(commands::cloud::handle_cloud_command(
+                &mut app_config,
+                target,
+                argument,
+                yes,
+            ));
         }
-        Commands::Passgen => commands::password_generator::generate_password(),
-        Commands::Update => commands::update::update_program(),
-        command => match build_session(&mut app_config) {
+        Commands::Passgen { length, chars } => report(
+            commands::password_generator::generate_password(length, chars),
+        ),
+        Commands::Passwd { new_password_stdin } => report(
+            commands::passwd::change_master_password(&mut app_config, new_password_stdin),
+        ),
+        Commands::Update => report(commands::update::update_program()),
+        command => match commands::cloud::open_vault(&mut app_config) {
             Ok(session) => match command {
-                Commands::Add => report(commands::add::add_item(&session)),
-                Commands::List => report(commands::list::list_items(&session)),
-                Commands::Get => report(commands::get::get_item(&session)),
-                Commands::Delete => report(commands::delete::delete_item(&session)),
-                Commands::Edit => report(commands::edit::edit_item(&session)),
-                Commands::Import => report(commands::import::import_items(&session)),
-                Commands::Export => report(commands::export::export_items(&session)),
+                Commands::Add(args) => report(commands::add::add_item(&session, args)),
+                Commands::List { tag, folder } => report(commands::list::list_items(
+                    &session,
+                    &tag,
+                    folder.as_deref(),
+                )),
+                Commands::Get {
+                    name,
+                    field,
+                    reveal,
+                } => report(commands::get::get_item(&session, name, field, reveal)),
+                Commands::Delete { name, yes } => {
+                    report(commands::delete::delete_item(&session, name, yes))
+                }
+                Commands::Edit(args) => report(commands::edit::edit_item(&session, args)),
+                Commands::Attach { item, file, name } => report(
+                    commands::attachments::attach_file(&session, &item, &file, name),
+                ),
+                Commands::Attachments { command } => report(
+                    commands::attachments::handle_attachments_command(&session, command),
+                ),
+                Commands::Import { file, overwrite } => {
+                    report(commands::import::import_items(&session, file, overwrite))
+                }
+                Commands::Export { file } => report(commands::export::export_items(&session, file)),
+                Commands::History => report(commands::history::show_history(&session)),
+                Commands::Run {
+                    variables,
+                    env_file,
+                    command,
+                } => report(commands::run::run_command(
+                    &session,
+                    variables,
+                    env_file.as_deref(),
+                    command,
+                )),
+                Commands::Inject {
+                    input,
+                    output,
+                    check,
+                } => report(commands::inject::inject_template(
+                    &session,
+                    input.as_deref(),
+                    output.as_deref(),
+                    check,
+                )),
+                Commands::Ssh { command } => {
+                    report(commands::ssh::handle_ssh_command(&session, command))
+                }
+                Commands::SshAgent { socket } => {
+                    report(commands::ssh_agent::run_agent(&session, socket))
+                }
+                Commands::Totp { name } => report(commands::totp::show_code(&session, name)),
+                Commands::DockerCredential { action } => report(
+                    commands::docker_credential::handle_docker_credential(&session, &action),
+                ),
+                Commands::GitCredential { action } => report(
+                    commands::git_credential::handle_git_credential(&session, &action),
+                ),
+                Commands::Fsck { yes } => report(commands::fsck::check_vault(&session, yes)),
+                Commands::Restore { revision, yes } => {
+                    report(commands::history::restore_revision(&session, revision, yes))
+                }
+                Commands::Trash { command } => report(commands::trash::handle_trash_command(
+                    &session,
+                    command.unwrap_or(commands::trash::TrashCommand::List),
+                )),
                 _ => unreachable!(),
             },
-            Err(err) => eprintln!("{}", err.red()),
+            Err(err) => report(Err(err)),
         },
     }
 }
 
-fn build_session(app_config: &mut AppConfig) -> Result<RemoteSession, String> {
-    let base_url = app_config.base_url.clone().ok_or_else(|| {
-        "No cloud endpoint configured. Run `x cloud <url>` to connect to a host.".to_string()
-    })?;
-
-    if let Some(hash) = app_config.master_hash.clone() {
-        match RemoteSession::from_hash(base_url.clone(), hash.clone()) {
-            Ok(session) => return Ok(session),
-            Err(CloudClientError::AuthenticationFailed) => {
-                eprintln!(
-                    "{}",
-                    "Stored master password hash no longer matches the host. Please re-enter the password."
-                        .red()
-                );
-                app_config.master_hash = None;
-            }
-            Err(err) => return Err(err.to_string()),
-        }
-    }
-
-    let password = master_password::prompt_master_password("Enter the host master password");
-    let session = RemoteSession::new(base_url, password).map_err(|err| err.to_string())?;
-    app_config.master_hash = Some(session.auth_hash().to_string());
-    if let Err(err) = app_config.save() {
-        eprintln!("Warning: failed to persist master password hash: {}", err);
+fn command_line() -> Vec<OsString> {
+    let mut args: Vec<OsString> = env::args_os().collect();
+    let invoked_as = args
+        .first()
+        .and_then(|arg| Path::new(arg).file_stem())
+        .and_then(|stem| stem.to_str());
+    match invoked_as {
+        Some(GIT_CREDENTIAL_HELPER) => args.insert(1, "git-credential".into()),
+        Some(DOCKER_CREDENTIAL_HELPER) => args.insert(1, "docker-credential".into()),
+        _ => {}
     }
-    Ok(session)
+    args
 }
 
-fn report(result: Result<(), String>) {
+fn report(result: Result<(), Error>) {
     if let Err(err) = result {
-        eprintln!("{}", err.red());
+        output::error(&err);
+        process::exit(err.exit_code());
     }
 }
@@ -0,0 +1,69 @@
+use crate::error::Error;
+use anstream::{eprintln, println, ColorChoice};
+use clap::ValueEnum;
+use owo_colors::OwoColorize;
+use serde::Serialize;
+use serde_json::json;
+use std::sync::OnceLock;
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    #[default]
+    Table,
+    Json,
+    Plain,
+}
+
+static FORMAT: OnceLock<OutputFormat> = OnceLock::new();
+
+pub fn init(format: OutputFormat) {
+    let _ = FORMAT.set(format);
+    if format != OutputFormat::Table {
+        ColorChoice::Never.write_global();
+    }
+}
+
+pub fn format() -> OutputFormat {
+    FORMAT.get().copied().unwrap_or_default()
+}
+
+pub fn is_table() -> bool {
+    format() == OutputFormat::Table
+}
+
+pub fn is_json() -> bool {
+    format() == OutputFormat::Json
+}
+
+pub fn print_json<T: Serialize>(value: &T) -> Result<(), Error> {
+    let json = serde_json::to_string_pretty(value)
+        .map_err(|err| format!("Failed to encode output: {}", err))?;
+    println!("{}", json);
+    Ok(())
+}
+
+pub fn success(message: &str) {
+    match format() {
+        OutputFormat::Json => println!("{}", json!({ "success": true, "message": message })),
+        OutputFormat::Plain => println!("{}", message),
+        OutputFormat::Table => println!("{}", message.green()),
+    }
+}
+
+pub fn error(err: &Error) {
+    if is_json() {
+        eprintln!(
+            "{}",
+            json!({
+                "success": false,
+                "error": err.message(),
+                "kind": err.kind(),
+                "exit_code": err.exit_code(),
+            })
+        );
+    } else if let Error::Cancelled(message) = err {
+        eprintln!("{}", message.yellow());
+    } else {
+        eprintln!("{}", err.message().red());
+    }
+}
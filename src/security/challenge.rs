@@ -0,0 +1,71 @@
+use hmac::{Hmac, Mac};
+use rand::Rng;
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+pub fn random_hex(len: usize) -> String {
+    let mut bytes = vec![0u8; len];
+    rand::thread_rng().fill(bytes.as_mut_slice());
+    hex::encode(bytes)
+}
+
+pub fn compute_proof(auth_hash: &str, nonce: &str) -> Result<String, String> {
+    let mac = keyed_mac(auth_hash, nonce)?;
+    Ok(hex::encode(mac.finalize().into_bytes()))
+}
+
+pub fn verify_proof(auth_hash: &str, nonce: &str, proof: &str) -> bool {
+    let Ok(proof) = hex::decode(proof) else {
+        return false;
+    };
+    keyed_mac(auth_hash, nonce)
+        .map(|mac| mac.verify_slice(&proof).is_ok())
+        .unwrap_or(false)
+}
+
+fn keyed_mac(auth_hash: &str, nonce: &str) -> Result<HmacSha256, String> {
+    let key = hex::decode(auth_hash).map_err(|_| "Invalid authentication key".to_string())?;
+    let mut mac =
+        HmacSha256::new_from_slice(&key).map_err(|_| "Invalid authentication key".to_string())?;
+    mac.update(nonce.as_bytes());
+    Ok(mac)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RFC 4231 test case 2: the proof is HMAC-SHA256 keyed by the auth hash.
+    const KEY: &str = "4a656665";
+    const NONCE: &str = "what do ya want for nothing?";
+    const PROOF: &str = "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843";
+
+    #[test]
+    fn proof_matches_rfc4231_vector() {
+        assert_eq!(compute_proof(KEY, NONCE).unwrap(), PROOF);
+    }
+
+    #[test]
+    fn verifies_only_the_matching_proof() {
+        assert!(verify_proof(KEY, NONCE, PROOF));
+        assert!(!verify_proof(KEY, "another nonce", PROOF));
+        assert!(!verify_proof("4a656666", NONCE, PROOF));
+        assert!(!verify_proof(KEY, NONCE, &PROOF[..62]));
+        assert!(!verify_proof(KEY, NONCE, "not hex"));
+    }
+
+    #[test]
+    fn rejects_an_auth_hash_that_is_not_hex() {
+        assert!(compute_proof("xyz", NONCE).is_err());
+        assert!(!verify_proof("xyz", NONCE, PROOF));
+    }
+
+    #[test]
+    fn random_hex_has_the_requested_length() {
+        let nonce = random_hex(16);
+        assert_eq!(nonce.len(), 32);
+        assert!(nonce.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(nonce, random_hex(16));
+    }
+}
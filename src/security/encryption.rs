@@ -1,8 +1,45 @@
-use aes_gcm::{aead::Aead, Aes256Gcm, Key, KeyInit, Nonce};
+use aes_gcm::{
+    aead::{Aead, Payload},
+    Aes256Gcm, Key, KeyInit, Nonce,
+};
 use base64::{engine::general_purpose, Engine as _};
 use rand::Rng;
+use std::fmt;
+
+#[derive(Debug)]
+pub enum DecryptError {
+    InvalidBase64,
+    TooShort,
+    Authentication,
+    InvalidUtf8,
+}
+
+impl fmt::Display for DecryptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecryptError::InvalidBase64 => write!(f, "ciphertext is not valid base64"),
+            DecryptError::TooShort => write!(f, "ciphertext is too short"),
+            DecryptError::Authentication => {
+                write!(f, "wrong key or the ciphertext was modified")
+            }
+            DecryptError::InvalidUtf8 => write!(f, "plaintext is not valid UTF-8"),
+        }
+    }
+}
 
 pub fn encrypt_data(key: &Key<Aes256Gcm>, plaintext: &str) -> String {
+    general_purpose::STANDARD.encode(encrypt_bytes(key, plaintext.as_bytes(), &[]))
+}
+
+pub fn decrypt_data(key: &Key<Aes256Gcm>, ciphertext: &str) -> Result<String, DecryptError> {
+    let decoded = general_purpose::STANDARD
+        .decode(ciphertext)
+        .map_err(|_| DecryptError::InvalidBase64)?;
+    let plaintext = decrypt_bytes(key, &decoded, &[])?;
+    String::from_utf8(plaintext).map_err(|_| DecryptError::InvalidUtf8)
+}
+
+pub fn encrypt_bytes(key: &Key<Aes256Gcm>, plaintext: &[u8], aad: &[u8]) -> Vec<u8> {
     let cipher = Aes256Gcm::new(key);
 
     let mut nonce_bytes = [0u8; 12];
@@ -10,27 +47,40 @@ pub fn encrypt_data(key: &Key<Aes256Gcm>, plaintext: &str) -> String {
     let nonce = Nonce::from_slice(&nonce_bytes);
 
     let ciphertext = cipher
-        .encrypt(nonce, plaintext.as_bytes())
+        .encrypt(
+            nonce,
+            Payload {
+                msg: plaintext,
+                aad,
+            },
+        )
         .expect("Encryption failure");
 
     let mut combined = nonce_bytes.to_vec();
     combined.extend(ciphertext);
-
-    general_purpose::STANDARD.encode(&combined)
+    combined
 }
 
-pub fn decrypt_data(key: &Key<Aes256Gcm>, ciphertext: &str) -> String {
+pub fn decrypt_bytes(
+    key: &Key<Aes256Gcm>,
+    data: &[u8],
+    aad: &[u8],
+) -> Result<Vec<u8>, DecryptError> {
     let cipher = Aes256Gcm::new(key);
+    if data.len() < 12 {
+        return Err(DecryptError::TooShort);
+    }
 
-    let decoded = general_purpose::STANDARD
-        .decode(ciphertext)
-        .expect("Invalid base64");
-
-    let (nonce_bytes, ciphertext) = decoded.split_at(12);
+    let (nonce_bytes, ciphertext) = data.split_at(12);
     let nonce = Nonce::from_slice(nonce_bytes);
 
-    let plaintext = cipher
-        .decrypt(nonce, ciphertext)
-        .expect("Decryption failure");
-    String::from_utf8(plaintext).expect("Invalid UTF-8")
+    cipher
+        .decrypt(
+            nonce,
+            Payload {
+                msg: ciphertext,
+                aad,
+            },
+        )
+        .map_err(|_| DecryptError::Authentication)
 }
@@ -0,0 +1,178 @@
+use aes_gcm::{Aes256Gcm, Key};
+use argon2::{Algorithm, Argon2, Params, Version};
+use hkdf::Hkdf;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+pub const KDF_ALGORITHM: &str = "argon2id";
+pub const KDF_VERSION: u32 = 2;
+pub const DEFAULT_MEMORY_KIB: u32 = 64 * 1024;
+pub const DEFAULT_ITERATIONS: u32 = 3;
+pub const DEFAULT_PARALLELISM: u32 = 1;
+const SALT_LEN: usize = 16;
+const KEY_LEN: usize = 32;
+const AUTH_INFO: &[u8] = b"x-auth-v2";
+const ENCRYPTION_INFO: &[u8] = b"x-encryption-v2";
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct KdfParams {
+    #[serde(default = "legacy_version")]
+    pub version: u32,
+    pub algorithm: String,
+    pub salt: String,
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+pub struct MasterKeys {
+    pub auth_hash: String,
+    pub encryption_key: Key<Aes256Gcm>,
+}
+
+impl KdfParams {
+    pub fn generate(memory_kib: u32, iterations: u32) -> Self {
+        let mut salt = [0u8; SALT_LEN];
+        rand::thread_rng().fill(&mut salt);
+        Self {
+            version: KDF_VERSION,
+            algorithm: KDF_ALGORITHM.to_string(),
+            salt: hex::encode(salt),
+            memory_kib,
+            iterations,
+            parallelism: DEFAULT_PARALLELISM,
+        }
+    }
+
+    pub fn is_current(&self) -> bool {
+        self.version == KDF_VERSION
+    }
+
+    pub fn derive_key(&self, password: &str) -> Result<[u8; KEY_LEN], String> {
+        if self.algorithm != KDF_ALGORITHM {
+            return Err(format!("Unsupported key derivation: {}", self.algorithm));
+        }
+        let salt = hex::decode(&self.salt).map_err(|_| "Invalid key derivation salt")?;
+        let params = Params::new(
+            self.memory_kib,
+            self.iterations,
+            self.parallelism,
+            Some(KEY_LEN),
+        )
+        .map_err(|err| format!("Invalid key derivation parameters: {}", err))?;
+
+        let mut key = [0u8; KEY_LEN];
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(password.as_bytes(), &salt, &mut key)
+            .map_err(|err| format!("Key derivation failed: {}", err))?;
+        Ok(key)
+    }
+
+    pub fn derive_keys(&self, password: &str) -> Result<MasterKeys, String> {
+        let master = self.derive_key(password)?;
+        self.split_key(&master)
+    }
+
+    fn split_key(&self, master: &[u8; KEY_LEN]) -> Result<MasterKeys, String> {
+        match self.version {
+            1 => Ok(MasterKeys {
+                auth_hash: hex::encode(master),
+                encryption_key: *Key::<Aes256Gcm>::from_slice(master),
+            }),
+            2 => {
+                let hkdf = Hkdf::<Sha256>::new(None, master);
+                let mut auth = [0u8; KEY_LEN];
+                let mut encryption = [0u8; KEY_LEN];
+                hkdf.expand(AUTH_INFO, &mut auth)
+                    .and_then(|_| hkdf.expand(ENCRYPTION_INFO, &mut encryption))
+                    .map_err(|_| "Key expansion failed".to_string())?;
+                Ok(MasterKeys {
+                    auth_hash: hex::encode(auth),
+                    encryption_key: *Key::<Aes256Gcm>::from_slice(&encryption),
+                })
+            }
+            other => Err(format!("Unsupported key scheme version: {}", other)),
+        }
+    }
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        Self::generate(DEFAULT_MEMORY_KIB, DEFAULT_ITERATIONS)
+    }
+}
+
+fn legacy_version() -> u32 {
+    1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(version: u32) -> KdfParams {
+        KdfParams {
+            version,
+            algorithm: KDF_ALGORITHM.to_string(),
+            salt: "00112233445566778899aabbccddeeff".to_string(),
+            memory_kib: 8,
+            iterations: 1,
+            parallelism: 1,
+        }
+    }
+
+    fn master() -> [u8; KEY_LEN] {
+        std::array::from_fn(|i| i as u8)
+    }
+
+    #[test]
+    fn version_one_uses_the_master_key_directly() {
+        let keys = params(1).split_key(&master()).unwrap();
+        assert_eq!(keys.auth_hash, hex::encode(master()));
+        assert_eq!(keys.encryption_key.as_slice(), master().as_slice());
+    }
+
+    #[test]
+    fn version_two_expands_separate_keys_with_hkdf() {
+        let keys = params(2).split_key(&master()).unwrap();
+        assert_eq!(
+            keys.auth_hash,
+            "7267e12aac0604af8c33a744708e4e98cab495952015bb857a767aad7c2cab0e"
+        );
+        assert_eq!(
+            hex::encode(keys.encryption_key),
+            "ded7380b0b59274044867134248b1db2ffb88a8347e69812f0181a6da25acf08"
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_versions_and_algorithms() {
+        assert!(params(3).split_key(&master()).is_err());
+
+        let mut scrypt = params(2);
+        scrypt.algorithm = "scrypt".to_string();
+        assert!(scrypt.derive_key("password").is_err());
+    }
+
+    #[test]
+    fn derivation_depends_on_password_and_salt() {
+        let first = params(2).derive_key("password").unwrap();
+        assert_eq!(first, params(2).derive_key("password").unwrap());
+        assert_ne!(first, params(2).derive_key("Password").unwrap());
+
+        let mut salted = params(2);
+        salted.salt = "ffeeddccbbaa99887766554433221100".to_string();
+        assert_ne!(first, salted.derive_key("password").unwrap());
+    }
+
+    #[test]
+    fn params_without_a_version_are_legacy() {
+        let parsed: KdfParams = serde_json::from_str(
+            r#"{"algorithm":"argon2id","salt":"00","memory_kib":8,"iterations":1,"parallelism":1}"#,
+        )
+        .unwrap();
+        assert_eq!(parsed.version, 1);
+        assert!(!parsed.is_current());
+    }
+}
@@ -1,10 +1,35 @@
+use crate::error::Error;
+use crate::utils::{is_interactive, prompt_aborted};
 use blake3;
 use dialoguer::Password;
+use std::env;
 
-pub fn prompt_master_password(prompt: &str) -> String {
-    Password::new().with_prompt(prompt).interact().unwrap()
+pub const MASTER_PASSWORD_ENV: &str = "X_MASTER_PASSWORD";
+
+pub fn prompt_master_password(prompt: &str) -> Result<String, Error> {
+    if let Ok(password) = env::var(MASTER_PASSWORD_ENV) {
+        return Ok(password);
+    }
+    if !is_interactive() {
+        return Err(Error::Usage(format!(
+            "The master password is required. Set {} when not running in a terminal.",
+            MASTER_PASSWORD_ENV
+        )));
+    }
+    Password::new()
+        .with_prompt(prompt)
+        .interact()
+        .map_err(prompt_aborted)
 }
 
-pub fn hash_password(password: &str) -> String {
+pub fn legacy_hash_password(password: &str) -> String {
     blake3::hash(password.as_bytes()).to_hex().to_string()
 }
+
+pub fn prompt_new_master_password(prompt: &str) -> Result<String, Error> {
+    Password::new()
+        .with_prompt(prompt)
+        .with_confirmation("Confirm the new master password", "Passwords do not match")
+        .interact()
+        .map_err(prompt_aborted)
+}
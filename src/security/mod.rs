@@ -1,2 +1,5 @@
+pub mod challenge;
 pub mod encryption;
+pub mod kdf;
 pub mod master_password;
+pub mod totp;
@@ -0,0 +1,261 @@
+use crate::utils::timestamp_now;
+use hmac::{Hmac, Mac};
+use reqwest::Url;
+use sha1::Sha1;
+use sha2::{Sha256, Sha512};
+
+const OTPAUTH_SCHEME: &str = "otpauth";
+const DEFAULT_DIGITS: u32 = 6;
+const DEFAULT_PERIOD: u64 = 30;
+
+#[derive(Clone, Copy)]
+enum Algorithm {
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+pub struct Totp {
+    secret: Vec<u8>,
+    algorithm: Algorithm,
+    digits: u32,
+    period: u64,
+}
+
+impl Totp {
+    pub fn parse(uri: &str) -> Result<Self, String> {
+        let url = Url::parse(uri.trim()).map_err(|err| format!("Invalid otpauth URI: {}", err))?;
+        if url.scheme() != OTPAUTH_SCHEME {
+            return Err("The URI must start with otpauth://".to_string());
+        }
+        if url.host_str() != Some("totp") {
+            return Err("Only time-based (otpauth://totp/) secrets are supported".to_string());
+        }
+
+        let mut secret = None;
+        let mut algorithm = Algorithm::Sha1;
+        let mut digits = DEFAULT_DIGITS;
+        let mut period = DEFAULT_PERIOD;
+        for (key, value) in url.query_pairs() {
+            match key.to_ascii_lowercase().as_str() {
+                "secret" => secret = Some(decode_secret(&value)?),
+                "algorithm" => {
+                    algorithm = match value.to_ascii_uppercase().as_str() {
+                        "SHA1" => Algorithm::Sha1,
+                        "SHA256" => Algorithm::Sha256,
+                        "SHA512" => Algorithm::Sha512,
+                        other => return Err(format!("Unsupported TOTP algorithm {}", other)),
+                    }
+                }
+                "digits" => {
+                    digits = value
+                        .parse()
+                        .ok()
+                        .filter(|digits| (6..=8).contains(digits))
+                        .ok_or_else(|| format!("Unsupported TOTP digits {}", value))?
+                }
+                "period" => {
+                    period = value
+                        .parse()
+                        .ok()
+                        .filter(|period| *period > 0)
+                        .ok_or_else(|| format!("Invalid TOTP period {}", value))?
+                }
+                _ => {}
+            }
+        }
+
+        Ok(Self {
+            secret: secret.ok_or("The otpauth URI has no secret")?,
+            algorithm,
+            digits,
+            period,
+        })
+    }
+
+    pub fn code_at(&self, timestamp: u64) -> String {
+        let counter = (timestamp / self.period).to_be_bytes();
+        let digest = match self.algorithm {
+            Algorithm::Sha1 => hmac_digest::<Hmac<Sha1>>(&self.secret, &counter),
+            Algorithm::Sha256 => hmac_digest::<Hmac<Sha256>>(&self.secret, &counter),
+            Algorithm::Sha512 => hmac_digest::<Hmac<Sha512>>(&self.secret, &counter),
+        };
+
+        let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+        let binary = u32::from_be_bytes([
+            digest[offset] & 0x7f,
+            digest[offset + 1],
+            digest[offset + 2],
+            digest[offset + 3],
+        ]);
+        let code = binary % 10u32.pow(self.digits);
+        format!("{:0width$}", code, width = self.digits as usize)
+    }
+
+    pub fn current(&self) -> (String, u64) {
+        let now = timestamp_now();
+        (self.code_at(now), self.remaining_at(now))
+    }
+
+    pub fn remaining_at(&self, timestamp: u64) -> u64 {
+        self.period - timestamp % self.period
+    }
+
+    pub fn period(&self) -> u64 {
+        self.period
+    }
+}
+
+// Accepts either an otpauth:// URI or a bare base32 secret and returns the URI
+// that gets stored on the item.
+pub fn normalize(input: &str, label: &str) -> Result<String, String> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Ok(String::new());
+    }
+    if input.starts_with("otpauth://") {
+        Totp::parse(input)?;
+        return Ok(input.to_string());
+    }
+
+    let secret: String = input
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .collect::<String>()
+        .to_ascii_uppercase();
+    let secret = secret.trim_end_matches('=');
+    decode_secret(secret)?;
+
+    let mut url = Url::parse("otpauth://totp/").map_err(|err| err.to_string())?;
+    if let Ok(mut segments) = url.path_segments_mut() {
+        segments.pop_if_empty().push(label);
+    }
+    url.query_pairs_mut().append_pair("secret", secret);
+    Ok(url.to_string())
+}
+
+fn decode_secret(secret: &str) -> Result<Vec<u8>, String> {
+    let secret = secret.trim_end_matches('=').to_ascii_uppercase();
+    base32::decode(base32::Alphabet::Rfc4648 { padding: false }, &secret)
+        .filter(|bytes| !bytes.is_empty())
+        .ok_or_else(|| "The TOTP secret is not valid base32".to_string())
+}
+
+fn hmac_digest<M: Mac + hmac::digest::KeyInit>(key: &[u8], message: &[u8]) -> Vec<u8> {
+    let mut mac = <M as Mac>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(message);
+    mac.finalize().into_bytes().to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RFC_TIMES: [u64; 6] = [
+        59,
+        1111111109,
+        1111111111,
+        1234567890,
+        2000000000,
+        20000000000,
+    ];
+
+    fn rfc_totp(seed: &[u8], algorithm: &str) -> Totp {
+        let secret = base32::encode(base32::Alphabet::Rfc4648 { padding: false }, seed);
+        Totp::parse(&format!(
+            "otpauth://totp/rfc6238?secret={}&algorithm={}&digits=8&period=30",
+            secret, algorithm
+        ))
+        .unwrap()
+    }
+
+    fn assert_codes(totp: &Totp, expected: [&str; 6]) {
+        for (time, code) in RFC_TIMES.iter().zip(expected) {
+            assert_eq!(totp.code_at(*time), code, "time {}", time);
+        }
+    }
+
+    #[test]
+    fn rfc6238_sha1_vectors() {
+        let totp = rfc_totp(b"12345678901234567890", "SHA1");
+        assert_codes(
+            &totp,
+            [
+                "94287082", "07081804", "14050471", "89005924", "69279037", "65353130",
+            ],
+        );
+    }
+
+    #[test]
+    fn rfc6238_sha256_vectors() {
+        let totp = rfc_totp(b"12345678901234567890123456789012", "SHA256");
+        assert_codes(
+            &totp,
+            [
+                "46119246", "68084774", "67062674", "91819424", "90698825", "77737706",
+            ],
+        );
+    }
+
+    #[test]
+    fn rfc6238_sha512_vectors() {
+        let seed = b"1234567890123456789012345678901234567890123456789012345678901234";
+        let totp = rfc_totp(seed, "SHA512");
+        assert_codes(
+            &totp,
+            [
+                "90693936", "25091201", "99943326", "93441116", "38618901", "47863826",
+            ],
+        );
+    }
+
+    #[test]
+    fn decodes_base32_secrets_loosely() {
+        assert_eq!(decode_secret("MZXW6YTBOI").unwrap(), b"foobar");
+        assert_eq!(decode_secret("mzxw6ytboi======").unwrap(), b"foobar");
+        assert!(decode_secret("not base32!").is_err());
+        assert!(decode_secret("").is_err());
+    }
+
+    #[test]
+    fn normalizes_bare_secrets_into_uris() {
+        let uri = normalize("mzxw 6ytb-oi==", "GitHub").unwrap();
+        assert_eq!(uri, "otpauth://totp/GitHub?secret=MZXW6YTBOI");
+        assert_eq!(normalize("  ", "GitHub").unwrap(), "");
+        assert!(normalize("not base32!", "GitHub").is_err());
+
+        let existing = "otpauth://totp/GitHub:me?secret=MZXW6YTBOI&issuer=GitHub";
+        assert_eq!(normalize(existing, "ignored").unwrap(), existing);
+    }
+
+    #[test]
+    fn parses_otpauth_parameters() {
+        let totp = Totp::parse("otpauth://totp/x?secret=MZXW6YTBOI").unwrap();
+        assert_eq!(totp.digits, DEFAULT_DIGITS);
+        assert_eq!(totp.period(), DEFAULT_PERIOD);
+        assert!(matches!(totp.algorithm, Algorithm::Sha1));
+
+        let totp =
+            Totp::parse("otpauth://totp/x?SECRET=MZXW6YTBOI&Algorithm=sha256&digits=7&period=60")
+                .unwrap();
+        assert_eq!(totp.digits, 7);
+        assert_eq!(totp.period(), 60);
+        assert!(matches!(totp.algorithm, Algorithm::Sha256));
+        assert_eq!(totp.remaining_at(61), 59);
+        assert_eq!(totp.code_at(0).len(), 7);
+    }
+
+    #[test]
+    fn rejects_invalid_otpauth_uris() {
+        for uri in [
+            "https://totp/x?secret=MZXW6YTBOI",
+            "otpauth://hotp/x?secret=MZXW6YTBOI",
+            "otpauth://totp/x",
+            "otpauth://totp/x?secret=MZXW6YTBOI&algorithm=MD5",
+            "otpauth://totp/x?secret=MZXW6YTBOI&digits=4",
+            "otpauth://totp/x?secret=MZXW6YTBOI&period=0",
+        ] {
+            assert!(Totp::parse(uri).is_err(), "{} should be rejected", uri);
+        }
+    }
+}
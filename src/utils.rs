@@ -1,5 +1,143 @@
-use dialoguer::Input;
+use crate::error::Error;
+use anstream::println;
+use clipboard::{ClipboardContext, ClipboardProvider};
+use dialoguer::{Confirm, Input};
+use std::fs;
+use std::io::{self, IsTerminal, Read, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-pub fn prompt_input(prompt: &str) -> String {
-    Input::new().with_prompt(prompt).interact_text().unwrap()
+pub fn prompt_input(prompt: &str) -> Result<String, Error> {
+    Input::new()
+        .with_prompt(prompt)
+        .interact_text()
+        .map_err(prompt_aborted)
+}
+
+pub fn prompt_aborted(err: io::Error) -> Error {
+    Error::Cancelled(format!("Prompt aborted: {}", err))
+}
+
+pub fn confirm(prompt: &str) -> bool {
+    Confirm::new()
+        .with_prompt(prompt)
+        .default(false)
+        .interact()
+        .unwrap_or(false)
+}
+
+pub fn is_interactive() -> bool {
+    io::stdin().is_terminal() && io::stderr().is_terminal()
+}
+
+pub fn require_interactive(missing: &str) -> Result<(), Error> {
+    if is_interactive() {
+        Ok(())
+    } else {
+        Err(Error::Usage(format!(
+            "Missing {}. Pass it as an argument when not running in a terminal.",
+            missing
+        )))
+    }
+}
+
+pub fn value_or_prompt(value: Option<String>, prompt: &str, flag: &str) -> Result<String, Error> {
+    match value {
+        Some(value) => Ok(value),
+        None => {
+            require_interactive(flag)?;
+            prompt_input(prompt)
+        }
+    }
+}
+
+pub fn confirm_or_yes(yes: bool, prompt: &str) -> Result<bool, Error> {
+    if yes {
+        return Ok(true);
+    }
+    require_interactive("--yes")?;
+    Ok(confirm(prompt))
+}
+
+pub fn read_stdin_secret() -> Result<String, Error> {
+    let mut input = String::new();
+    io::stdin()
+        .read_to_string(&mut input)
+        .map_err(|err| format!("Failed to read from stdin: {}", err))?;
+    let trimmed = input.strip_suffix('\n').unwrap_or(&input);
+    Ok(trimmed.strip_suffix('\r').unwrap_or(trimmed).to_string())
+}
+
+pub fn copy_to_clipboard(contents: String) -> Result<(), Error> {
+    if contents.is_empty() || !io::stdout().is_terminal() {
+        return Ok(());
+    }
+    let mut ctx: ClipboardContext =
+        ClipboardProvider::new().map_err(|err| Error::from(err.to_string()))?;
+    ctx.set_contents(contents)
+        .map_err(|err| Error::from(err.to_string()))?;
+    println!("Sensitive data has been copied to the clipboard.");
+    Ok(())
+}
+
+pub fn with_scheme(website: String) -> String {
+    if website.starts_with("http://") || website.starts_with("https://") {
+        website
+    } else {
+        format!("https://{}", website)
+    }
+}
+
+pub fn timestamp_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+pub fn format_timestamp(timestamp: u64) -> String {
+    let days = (timestamp / 86_400) as i64;
+    let seconds = timestamp % 86_400;
+
+    // Convert days since the Unix epoch to a proleptic Gregorian date.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        (seconds % 3600) / 60
+    )
+}
+
+pub fn write_private_file(path: &Path, data: &[u8]) -> io::Result<()> {
+    create_private_file(path)?.write_all(data)
+}
+
+pub fn create_private_file(path: &Path) -> io::Result<fs::File> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let file = options.open(path)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    }
+    Ok(file)
 }
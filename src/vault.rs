@@ -1,9 +1,25 @@
-use crate::cloud::types::VaultEntry;
-use crate::cloud::RemoteSession;
+use crate::cloud::types::{VaultEntry, INDEX_ID};
+use crate::cloud::{CloudClientError, VaultSession};
+use crate::error::Error;
 use crate::security::encryption::{decrypt_data, encrypt_data};
+use crate::security::totp;
+use crate::utils::{
+    is_interactive, prompt_aborted, require_interactive, timestamp_now, with_scheme,
+};
 use aes_gcm::{Aes256Gcm, Key};
+use anstream::{eprintln, println};
+use clap::ValueEnum;
+use dialoguer::Select;
+use owo_colors::OwoColorize;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use uuid::Uuid;
+
+const MAX_CONFLICT_ATTEMPTS: usize = 3;
+const PADDING_BLOCK: usize = 256;
+pub const UNREADABLE_KIND: &str = "Unreadable";
 
 #[derive(Clone, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
@@ -14,6 +30,8 @@ pub enum VaultItem {
         email: String,
         username: String,
         password: String,
+        #[serde(default)]
+        totp: String,
     },
     CreditCard {
         name: String,
@@ -25,48 +43,1166 @@ pub enum VaultItem {
         title: String,
         note: String,
     },
+    SshKey {
+        name: String,
+        private_key: String,
+        public_key: String,
+        comment: String,
+        fingerprint: String,
+    },
+    Identity {
+        name: String,
+        full_name: String,
+        email: String,
+        phone: String,
+        address: String,
+        passport_number: String,
+    },
+    BankAccount {
+        name: String,
+        account_holder: String,
+        iban: String,
+        routing_number: String,
+        pin: String,
+    },
+    ApiCredential {
+        name: String,
+        key_id: String,
+        secret: String,
+        endpoint: String,
+    },
+    WifiNetwork {
+        name: String,
+        ssid: String,
+        passphrase: String,
+        security: String,
+    },
+    SoftwareLicense {
+        name: String,
+        version: String,
+        licensed_to: String,
+        license_key: String,
+    },
+    Database {
+        name: String,
+        host: String,
+        port: String,
+        database: String,
+        username: String,
+        password: String,
+        connection_string: String,
+    },
+}
+
+impl VaultItem {
+    pub fn name(&self) -> &str {
+        match self {
+            VaultItem::Password { name, .. }
+            | VaultItem::CreditCard { name, .. }
+            | VaultItem::SshKey { name, .. }
+            | VaultItem::Identity { name, .. }
+            | VaultItem::BankAccount { name, .. }
+            | VaultItem::ApiCredential { name, .. }
+            | VaultItem::WifiNetwork { name, .. }
+            | VaultItem::SoftwareLicense { name, .. }
+            | VaultItem::Database { name, .. } => name,
+            VaultItem::SecureNote { title, .. } => title,
+        }
+    }
+
+    pub fn secret_fields(&self) -> &'static [&'static str] {
+        match self {
+            VaultItem::Password { .. } => &["password", "totp"],
+            VaultItem::CreditCard { .. } => &["cvv"],
+            VaultItem::SecureNote { .. } => &[],
+            VaultItem::SshKey { .. } => &["private_key"],
+            VaultItem::Identity { .. } => &["passport_number"],
+            VaultItem::BankAccount { .. } => &["pin"],
+            VaultItem::ApiCredential { .. } => &["secret"],
+            VaultItem::WifiNetwork { .. } => &["passphrase"],
+            VaultItem::SoftwareLicense { .. } => &["license_key"],
+            VaultItem::Database { .. } => &["password"],
+        }
+    }
+
+    pub fn field(&self, field: &str) -> Result<String, Error> {
+        let fields = self.to_fields()?;
+        match fields.get(self.field_name(field)) {
+            Some(serde_json::Value::String(value)) => Ok(value.clone()),
+            _ => Err(self.unknown_field(field, &fields)),
+        }
+    }
+
+    pub fn with_fields(&self, changes: &[(String, String)]) -> Result<VaultItem, Error> {
+        let mut fields = self.to_fields()?;
+        for (field, value) in changes {
+            let field = self.field_name(field);
+            if field == "type" || !fields.contains_key(field) {
+                return Err(self.unknown_field(field, &fields));
+            }
+            if matches!(self, VaultItem::SshKey { .. }) && !matches!(field, "name" | "comment") {
+                return Err(Error::Usage(format!(
+                    "The SSH key field `{}` is derived from the key and cannot be edited",
+                    field
+                )));
+            }
+            let value = match field {
+                "website" => with_scheme(value.clone()),
+                "totp" => totp::normalize(value, self.name()).map_err(Error::Usage)?,
+                _ => value.clone(),
+            };
+            fields.insert(field.to_string(), serde_json::Value::String(value));
+        }
+        serde_json::from_value(serde_json::Value::Object(fields))
+            .map_err(|err| Error::Usage(format!("Invalid value: {}", err)))
+    }
+
+    pub fn to_fields(&self) -> Result<serde_json::Map<String, serde_json::Value>, Error> {
+        match serde_json::to_value(self) {
+            Ok(serde_json::Value::Object(fields)) => Ok(fields),
+            Ok(_) => Err("Encode error: item is not an object".into()),
+            Err(err) => Err(format!("Encode error: {}", err).into()),
+        }
+    }
+
+    fn field_name<'a>(&self, field: &'a str) -> &'a str {
+        match (self, field) {
+            (VaultItem::SecureNote { .. }, "name") => "title",
+            _ => field,
+        }
+    }
+
+    fn unknown_field(
+        &self,
+        field: &str,
+        fields: &serde_json::Map<String, serde_json::Value>,
+    ) -> Error {
+        let available: Vec<&str> = fields
+            .keys()
+            .map(String::as_str)
+            .filter(|name| *name != "type")
+            .collect();
+        Error::Usage(format!(
+            "{} items have no field `{}`. Available fields: {}",
+            self.kind(),
+            field,
+            available.join(", ")
+        ))
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            VaultItem::Password { .. } => "Password",
+            VaultItem::CreditCard { .. } => "Credit Card",
+            VaultItem::SecureNote { .. } => "Secure Note",
+            VaultItem::SshKey { .. } => "SSH Key",
+            VaultItem::Identity { .. } => "Identity",
+            VaultItem::BankAccount { .. } => "Bank Account",
+            VaultItem::ApiCredential { .. } => "API Credential",
+            VaultItem::WifiNetwork { .. } => "Wi-Fi Network",
+            VaultItem::SoftwareLicense { .. } => "Software License",
+            VaultItem::Database { .. } => "Database",
+        }
+    }
+}
+
+pub const EXTENSION_VERSION: u32 = 2;
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum CustomFieldKind {
+    Text,
+    Hidden,
+    Url,
+    Date,
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct CustomField {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: CustomFieldKind,
+    pub value: String,
+}
+
+impl CustomField {
+    pub fn new(kind: CustomFieldKind, name: &str, value: &str) -> Result<Self, Error> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(Error::Usage("Custom fields need a name".to_string()));
+        }
+        let value = match kind {
+            CustomFieldKind::Url => with_scheme(value.trim().to_string()),
+            CustomFieldKind::Date => {
+                let date = value.trim();
+                if !is_date(date) {
+                    return Err(Error::Usage(format!(
+                        "The date field `{}` must look like YYYY-MM-DD, got `{}`",
+                        name, date
+                    )));
+                }
+                date.to_string()
+            }
+            CustomFieldKind::Text | CustomFieldKind::Hidden => value.to_string(),
+        };
+        Ok(Self {
+            name: name.to_string(),
+            kind,
+            value,
+        })
+    }
+
+    pub fn display_value(&self) -> &str {
+        match self.kind {
+            CustomFieldKind::Hidden => "[hidden]",
+            _ => &self.value,
+        }
+    }
+}
+
+// Only the reference lives on the item. The encrypted chunks are kept by the
+// host in a separate tree, and each file has its own key so re-encrypting the
+// vault (e.g. on a password change) keeps attachments readable.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct Attachment {
+    pub id: String,
+    pub name: String,
+    pub size: u64,
+    pub chunks: u32,
+    pub key: String,
+    #[serde(default)]
+    pub added_at: u64,
+}
+
+// Extra data shared by every item type. It is stored under `ext` next to the
+// typed fields, so entries written before it existed still decode and older
+// clients simply ignore it. Keys added by newer versions are carried along.
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ItemExtension {
+    #[serde(rename = "v", default)]
+    pub version: u32,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<CustomField>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub folder: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<Attachment>,
+    #[serde(flatten)]
+    pub unknown: serde_json::Map<String, serde_json::Value>,
+}
+
+impl ItemExtension {
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+            && self.tags.is_empty()
+            && self.folder.is_empty()
+            && self.attachments.is_empty()
+            && self.unknown.is_empty()
+    }
+
+    pub fn custom_field(&self, name: &str) -> Option<&CustomField> {
+        self.fields
+            .iter()
+            .find(|field| field.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn set_field(&mut self, field: CustomField) {
+        match self
+            .fields
+            .iter_mut()
+            .find(|existing| existing.name.eq_ignore_ascii_case(&field.name))
+        {
+            Some(existing) => *existing = field,
+            None => self.fields.push(field),
+        }
+    }
+
+    pub fn remove_field(&mut self, name: &str) -> Result<(), Error> {
+        let before = self.fields.len();
+        self.fields
+            .retain(|field| !field.name.eq_ignore_ascii_case(name));
+        if self.fields.len() == before {
+            return Err(Error::Usage(format!("There is no custom field `{}`", name)));
+        }
+        Ok(())
+    }
+
+    pub fn add_tag(&mut self, tag: &str) {
+        let tag = tag.trim();
+        if !tag.is_empty() && !self.has_tag(tag) {
+            self.tags.push(tag.to_string());
+        }
+    }
+
+    pub fn remove_tag(&mut self, tag: &str) {
+        self.tags
+            .retain(|existing| !existing.eq_ignore_ascii_case(tag.trim()));
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags
+            .iter()
+            .any(|existing| existing.eq_ignore_ascii_case(tag.trim()))
+    }
+
+    pub fn set_folder(&mut self, folder: &str) {
+        self.folder = normalize_folder(folder);
+    }
+
+    pub fn attachment(&self, name: &str) -> Option<&Attachment> {
+        self.attachments
+            .iter()
+            .find(|attachment| attachment.name == name)
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct StoredItem {
+    #[serde(flatten)]
+    pub item: VaultItem,
+    #[serde(default, skip_serializing_if = "ItemExtension::is_empty")]
+    pub ext: ItemExtension,
+}
+
+impl StoredItem {
+    pub fn new(item: VaultItem, mut ext: ItemExtension) -> Self {
+        if !ext.is_empty() {
+            ext.version = ext.version.max(EXTENSION_VERSION);
+        }
+        Self { item, ext }
+    }
+
+    pub fn field(&self, field: &str) -> Result<String, Error> {
+        self.item.field(field).or_else(|err| {
+            self.ext
+                .custom_field(field)
+                .map(|custom| custom.value.clone())
+                .ok_or(err)
+        })
+    }
+}
+
+pub fn normalize_folder(folder: &str) -> String {
+    folder
+        .split('/')
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+pub fn folder_contains(folder: &str, wanted: &str) -> bool {
+    let wanted = normalize_folder(wanted);
+    let folder = folder.to_lowercase();
+    let wanted = wanted.to_lowercase();
+    folder == wanted
+        || folder
+            .strip_prefix(&wanted)
+            .is_some_and(|rest| rest.starts_with('/'))
+}
+
+fn is_date(value: &str) -> bool {
+    let parts: Vec<&str> = value.split('-').collect();
+    let [year, month, day] = parts.as_slice() else {
+        return false;
+    };
+    let number = |part: &str, len: usize| {
+        (part.len() == len && part.bytes().all(|b| b.is_ascii_digit()))
+            .then(|| part.parse::<u32>().ok())
+            .flatten()
+    };
+    matches!(
+        (number(year, 4), number(month, 2), number(day, 2)),
+        (Some(_), Some(1..=12), Some(1..=31))
+    )
+}
+
+#[derive(Clone, Serialize)]
+pub struct NamedEntry {
+    pub id: String,
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub tags: Vec<String>,
+    pub folder: String,
+    #[serde(skip)]
+    pub revision: u64,
+}
+
+impl NamedEntry {
+    pub fn decrypt(key: &Key<Aes256Gcm>, entry: &VaultEntry) -> Result<Self, Error> {
+        let stored = SecureEntry::decrypt_stored(key, &entry.value)?;
+        Ok(Self {
+            id: entry.key.clone(),
+            name: stored.item.name().to_string(),
+            kind: stored.item.kind().to_string(),
+            tags: stored.ext.tags,
+            folder: stored.ext.folder,
+            revision: entry.revision,
+        })
+    }
+
+    pub fn decrypt_or_flag(key: &Key<Aes256Gcm>, entry: &VaultEntry) -> Self {
+        Self::decrypt(key, entry).unwrap_or_else(|_| Self {
+            id: entry.key.clone(),
+            name: format!("unreadable-{}", short_id(&entry.key)),
+            kind: UNREADABLE_KIND.to_string(),
+            tags: Vec::new(),
+            folder: String::new(),
+            revision: entry.revision,
+        })
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags
+            .iter()
+            .any(|existing| existing.eq_ignore_ascii_case(tag.trim()))
+    }
+
+    pub fn is_unreadable(&self) -> bool {
+        self.kind == UNREADABLE_KIND
+    }
+
+    pub fn short_id(&self) -> &str {
+        short_id(&self.id)
+    }
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct SecureEntry {
     pub data: String,
-    pub created_at: u64,
 }
 
 impl SecureEntry {
-    pub fn encrypt(key: &Key<Aes256Gcm>, item: &VaultItem) -> Result<Self, String> {
+    pub fn encrypt<T: Serialize>(key: &Key<Aes256Gcm>, item: &T) -> Result<Self, Error> {
         let plaintext =
             serde_json::to_string(item).map_err(|err| format!("Encode error: {}", err))?;
-        let encrypted = encrypt_data(key, &plaintext);
         Ok(Self {
-            data: encrypted,
-            created_at: timestamp_now(),
+            data: encrypt_data(key, &padded(plaintext)),
+        })
+    }
+
+    pub fn decrypt(key: &Key<Aes256Gcm>, value: &str) -> Result<VaultItem, Error> {
+        Self::open(key, value)
+    }
+
+    pub fn decrypt_stored(key: &Key<Aes256Gcm>, value: &str) -> Result<StoredItem, Error> {
+        Self::open(key, value)
+    }
+
+    fn open<T: DeserializeOwned>(key: &Key<Aes256Gcm>, value: &str) -> Result<T, Error> {
+        let stored: SecureEntry = serde_json::from_str(value).map_err(decode_error)?;
+        let plaintext = decrypt_data(key, &stored.data)?;
+        serde_json::from_str(&plaintext).map_err(decode_error)
+    }
+
+    pub fn reencrypt(
+        old_key: &Key<Aes256Gcm>,
+        new_key: &Key<Aes256Gcm>,
+        value: &str,
+    ) -> Result<String, Error> {
+        let mut stored: SecureEntry = serde_json::from_str(value).map_err(decode_error)?;
+        let plaintext = decrypt_data(old_key, &stored.data)?;
+        stored.data = encrypt_data(new_key, &padded(plaintext));
+        stored.serialize()
+    }
+
+    pub fn serialize(&self) -> Result<String, Error> {
+        serde_json::to_string(self).map_err(|err| format!("Encode error: {}", err).into())
+    }
+
+    fn has_plaintext_metadata(value: &str) -> bool {
+        serde_json::from_str::<serde_json::Value>(value)
+            .ok()
+            .and_then(|stored| stored.as_object().map(|fields| fields.len() > 1))
+            .unwrap_or(false)
+    }
+}
+
+// Entries are padded with trailing whitespace (which JSON ignores) to a
+// multiple of PADDING_BLOCK before encryption, so the ciphertext size the
+// host sees only reveals a rough size class instead of the exact length.
+fn padded(mut plaintext: String) -> String {
+    let target = plaintext.len().div_ceil(PADDING_BLOCK).max(1) * PADDING_BLOCK;
+    let padding = " ".repeat(target - plaintext.len());
+    plaintext.push_str(&padding);
+    plaintext
+}
+
+const INDEX_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Default)]
+struct VaultIndex {
+    #[serde(default)]
+    version: u32,
+    #[serde(default)]
+    entries: BTreeMap<String, IndexRecord>,
+    // Set once the legacy entry upgrade has run against this vault, so later
+    // sessions skip scanning every entry and the trash for it.
+    #[serde(default)]
+    upgraded: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct IndexRecord {
+    name: String,
+    kind: String,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    folder: String,
+    revision: u64,
+    created_at: u64,
+    updated_at: u64,
+}
+
+impl VaultIndex {
+    fn load(session: &VaultSession) -> (Self, u64) {
+        match session
+            .entries()
+            .into_iter()
+            .find(|entry| entry.key == INDEX_ID)
+        {
+            Some(entry) => {
+                let mut index: Self =
+                    SecureEntry::open(session.encryption_key(), &entry.value).unwrap_or_default();
+                if index.version < INDEX_VERSION {
+                    index.entries.clear();
+                }
+                (index, entry.revision)
+            }
+            None => (Self::default(), 0),
+        }
+    }
+
+    fn store(&self, session: &VaultSession, revision: u64) -> Result<(), Error> {
+        let value = SecureEntry::encrypt(session.encryption_key(), self)
+            .and_then(|stored| stored.serialize())?;
+        session.store_item(&VaultEntry {
+            key: INDEX_ID.to_string(),
+            value,
+            revision,
+        })?;
+        Ok(())
+    }
+
+    fn named_entries(&self, session: &VaultSession) -> Result<Vec<NamedEntry>, Error> {
+        load_all_entries(session)?
+            .iter()
+            .map(|entry| match self.entries.get(&entry.key) {
+                Some(record) if record.revision == entry.revision => Ok(NamedEntry {
+                    id: entry.key.clone(),
+                    name: record.name.clone(),
+                    kind: record.kind.clone(),
+                    tags: record.tags.clone(),
+                    folder: record.folder.clone(),
+                    revision: entry.revision,
+                }),
+                _ => Ok(NamedEntry::decrypt_or_flag(session.encryption_key(), entry)),
+            })
+            .collect()
+    }
+
+    fn reconcile(&mut self, session: &VaultSession) -> Result<bool, Error> {
+        let entries = load_all_entries(session)?;
+        let before = self.entries.len();
+        self.entries
+            .retain(|id, _| entries.iter().any(|entry| &entry.key == id));
+        let mut changed = self.entries.len() != before || self.version != INDEX_VERSION;
+        self.version = INDEX_VERSION;
+
+        let now = timestamp_now();
+        for entry in &entries {
+            let existing = self.entries.get(&entry.key);
+            if existing.is_some_and(|record| record.revision == entry.revision) {
+                continue;
+            }
+            let Ok(named) = NamedEntry::decrypt(session.encryption_key(), entry) else {
+                changed |= self.entries.remove(&entry.key).is_some();
+                continue;
+            };
+            // An edit that leaves the listing untouched does not rewrite the
+            // index on its own; the entry is decrypted directly until the next
+            // real index change stores its new revision.
+            let listed = existing.is_some_and(|record| {
+                record.name == named.name
+                    && record.kind == named.kind
+                    && record.tags == named.tags
+                    && record.folder == named.folder
+            });
+            if listed {
+                if let Some(record) = self.entries.get_mut(&entry.key) {
+                    record.revision = entry.revision;
+                }
+                continue;
+            }
+            let record = IndexRecord {
+                name: named.name,
+                kind: named.kind,
+                tags: named.tags,
+                folder: named.folder,
+                revision: entry.revision,
+                created_at: existing.map_or(now, |record| record.created_at),
+                updated_at: now,
+            };
+            self.entries.insert(entry.key.clone(), record);
+            changed = true;
+        }
+        Ok(changed)
+    }
+}
+
+pub fn load_entry(session: &VaultSession, id: &str) -> Result<VaultEntry, Error> {
+    Ok(session.fetch_item(id)?)
+}
+
+pub fn open_item(session: &VaultSession, id: &str) -> Result<(VaultEntry, StoredItem), Error> {
+    let entry = load_entry(session, id)?;
+    let item =
+        SecureEntry::decrypt_stored(session.encryption_key(), &entry.value).map_err(|err| {
+            err.context(&format!(
+                "Entry {} cannot be read (run `x fsck` to review it)",
+                short_id(id)
+            ))
+        })?;
+    Ok((entry, item))
+}
+
+pub fn load_all_entries(session: &VaultSession) -> Result<Vec<VaultEntry>, Error> {
+    Ok(session
+        .entries()
+        .into_iter()
+        .filter(|entry| entry.key != INDEX_ID)
+        .collect())
+}
+
+pub fn list_named_entries(session: &VaultSession) -> Result<Vec<NamedEntry>, Error> {
+    let (index, _) = VaultIndex::load(session);
+    let mut entries = index.named_entries(session)?;
+    entries.sort_by(compare_named);
+    Ok(entries)
+}
+
+pub fn list_trashed_entries(session: &VaultSession) -> Result<Vec<(NamedEntry, u64)>, Error> {
+    let remote = session.remote()?;
+    let mut trashed = Vec::new();
+    for item in remote.list_trash()? {
+        let entry = remote.fetch_trashed(&item.id)?;
+        let named = NamedEntry::decrypt_or_flag(session.encryption_key(), &entry);
+        trashed.push((named, item.trashed_at.unwrap_or(item.updated_at)));
+    }
+    trashed.sort_by(|a, b| compare_named(&a.0, &b.0));
+    Ok(trashed)
+}
+
+pub fn entry_names(session: &VaultSession) -> Result<HashMap<String, String>, Error> {
+    let mut names: HashMap<String, String> = list_trashed_entries(session)?
+        .into_iter()
+        .map(|(entry, _)| (entry.id, entry.name))
+        .collect();
+    for entry in list_named_entries(session)? {
+        names.insert(entry.id, entry.name);
+    }
+    Ok(names)
+}
+
+fn compare_named(a: &NamedEntry, b: &NamedEntry) -> Ordering {
+    a.name
+        .to_lowercase()
+        .cmp(&b.name.to_lowercase())
+        .then_with(|| a.id.cmp(&b.id))
+}
+
+pub fn entry_labels(entries: &[NamedEntry]) -> Vec<String> {
+    let mut counts: HashMap<(&str, &str), usize> = HashMap::new();
+    for entry in entries {
+        *counts.entry((&entry.name, &entry.kind)).or_default() += 1;
+    }
+
+    entries
+        .iter()
+        .map(|entry| {
+            if counts[&(entry.name.as_str(), entry.kind.as_str())] > 1 {
+                format!("{} ({}, {})", entry.name, entry.kind, entry.short_id())
+            } else {
+                format!("{} ({})", entry.name, entry.kind)
+            }
+        })
+        .collect()
+}
+
+pub fn select_entry(
+    session: &VaultSession,
+    query: Option<&str>,
+    prompt: &str,
+) -> Result<Option<NamedEntry>, Error> {
+    let entries = list_named_entries(session)?;
+    if entries.is_empty() && query.is_none() {
+        println!("{}", "No items found in the vault.".red());
+        return Ok(None);
+    }
+    choose_entry(entries, query, prompt).map(Some)
+}
+
+pub fn choose_entry(
+    mut entries: Vec<NamedEntry>,
+    query: Option<&str>,
+    prompt: &str,
+) -> Result<NamedEntry, Error> {
+    if let Some(query) = query.map(str::trim).filter(|query| !query.is_empty()) {
+        let by_name = |entry: &NamedEntry| entry.name.eq_ignore_ascii_case(query);
+        if entries.iter().any(by_name) {
+            entries.retain(by_name);
+        } else {
+            entries.retain(|entry| entry.id.starts_with(query));
+        }
+        if entries.is_empty() {
+            return Err(Error::NotFound(format!("No entry named `{}`.", query)));
+        }
+        if entries.len() > 1 && !is_interactive() {
+            let ids: Vec<&str> = entries.iter().map(NamedEntry::short_id).collect();
+            return Err(Error::Usage(format!(
+                "Several entries are named `{}` ({}). Pass one of the IDs instead.",
+                query,
+                ids.join(", ")
+            )));
+        }
+    } else {
+        require_interactive("an entry name")?;
+    }
+
+    if entries.len() == 1 {
+        return Ok(entries.swap_remove(0));
+    }
+    let selection = Select::new()
+        .with_prompt(prompt)
+        .items(&entry_labels(&entries))
+        .default(0)
+        .interact()
+        .map_err(prompt_aborted)?;
+    Ok(entries.swap_remove(selection))
+}
+
+pub fn new_entry_id() -> String {
+    Uuid::new_v4().to_string()
+}
+
+pub fn is_entry_id(key: &str) -> bool {
+    Uuid::parse_str(key).is_ok()
+}
+
+pub const REFERENCE_SCHEME: &str = "x://";
+
+pub struct SecretResolver<'a> {
+    session: &'a VaultSession,
+    entries: Vec<NamedEntry>,
+    items: HashMap<String, StoredItem>,
+}
+
+impl<'a> SecretResolver<'a> {
+    pub fn new(session: &'a VaultSession) -> Result<Self, Error> {
+        Ok(Self {
+            session,
+            entries: list_named_entries(session)?,
+            items: HashMap::new(),
+        })
+    }
+
+    pub fn resolve(&mut self, reference: &str) -> Result<String, Error> {
+        let (name, field) = parse_reference(reference)?;
+        let entry = choose_entry(
+            self.entries.clone(),
+            Some(name),
+            &format!("Select the item for {}", reference),
+        )?;
+        if !self.items.contains_key(&entry.id) {
+            let (_, item) = open_item(self.session, &entry.id)?;
+            self.items.insert(entry.id.clone(), item);
+        }
+        self.items[&entry.id].field(field)
+    }
+}
+
+pub fn parse_reference(reference: &str) -> Result<(&str, &str), Error> {
+    let path = reference.trim();
+    let path = path.strip_prefix(REFERENCE_SCHEME).unwrap_or(path);
+    path.rsplit_once('/')
+        .map(|(name, field)| (name.trim(), field.trim()))
+        .filter(|(name, field)| !name.is_empty() && !field.is_empty())
+        .ok_or_else(|| {
+            Error::Usage(format!(
+                "Expected a reference like `x://item/field`, got `{}`",
+                reference
+            ))
         })
+}
+
+pub fn short_id(id: &str) -> &str {
+    id.get(..8).unwrap_or(id)
+}
+
+pub fn upgrade_entries(session: &VaultSession) -> Result<usize, Error> {
+    let Ok(remote) = session.remote() else {
+        return Ok(0);
+    };
+    if VaultIndex::load(session).0.upgraded {
+        return Ok(0);
+    }
+    let mut legacy: Vec<(String, bool)> = load_all_entries(session)?
+        .into_iter()
+        .filter(|entry| {
+            !is_entry_id(&entry.key) || SecureEntry::has_plaintext_metadata(&entry.value)
+        })
+        .map(|entry| (entry.key, SecureEntry::has_plaintext_metadata(&entry.value)))
+        .collect();
+    legacy.extend(
+        remote
+            .list_trash()?
+            .into_iter()
+            .filter(|item| !is_entry_id(&item.id))
+            .map(|item| (item.id, false)),
+    );
+    if legacy.is_empty() {
+        mark_upgraded(session)?;
+        return Ok(0);
+    }
+
+    let mut upgraded = 0;
+    for (key, reseal) in &legacy {
+        let mut id = key.clone();
+        if !is_entry_id(key) {
+            let target = new_entry_id();
+            let moved = retry_on_conflict(|| {
+                let revision = match remote.fetch_item(key) {
+                    Ok(current) => current.revision,
+                    Err(CloudClientError::NotFound(_)) => match remote.fetch_trashed(key) {
+                        Ok(current) => current.revision,
+                        Err(CloudClientError::NotFound(_)) => return Ok(false),
+                        Err(err) => return Err(err),
+                    },
+                    Err(err) => return Err(err),
+                };
+                match remote.move_item(key, &target, revision) {
+                    Ok(_) => Ok(true),
+                    Err(CloudClientError::NotFound(_)) => Ok(false),
+                    Err(err) => Err(err),
+                }
+            })?;
+            if !moved {
+                continue;
+            }
+            id = target;
+        }
+        if *reseal {
+            retry_on_conflict(|| {
+                let entry = remote.fetch_item(&id)?;
+                let stored: SecureEntry =
+                    serde_json::from_str(&entry.value).map_err(decode_error)?;
+                let value = stored.serialize()?;
+                remote.store_item(&VaultEntry { value, ..entry })?;
+                Ok::<_, Error>(())
+            })?;
+        }
+        upgraded += 1;
+    }
+    session.synchronize()?;
+    mark_upgraded(session)?;
+    Ok(upgraded)
+}
+
+fn mark_upgraded(session: &VaultSession) -> Result<(), Error> {
+    retry_on_conflict(|| {
+        let (mut index, revision) = VaultIndex::load(session);
+        if index.upgraded {
+            return Ok(());
+        }
+        index.upgraded = true;
+        index.store(session, revision)
+    })
+}
+
+pub fn refresh_index(session: &VaultSession) -> Result<(), Error> {
+    if session.remote().is_err() {
+        return Ok(());
+    }
+    retry_on_conflict(|| {
+        let (mut index, revision) = VaultIndex::load(session);
+        if !index.reconcile(session)? {
+            return Ok(());
+        }
+        index.store(session, revision)
+    })
+}
+
+// `revision` is the revision the caller last saw (0 for a new id); a newer
+// revision on the host is reported as a conflict instead of being overwritten.
+pub fn store_entry(
+    session: &VaultSession,
+    key: &str,
+    value: &str,
+    revision: u64,
+) -> Result<(), Error> {
+    session.store_item(&VaultEntry {
+        key: key.to_string(),
+        value: value.to_string(),
+        revision,
+    })?;
+    Ok(())
+}
+
+pub fn update_entry<F>(session: &VaultSession, id: &str, change: F) -> Result<(), Error>
+where
+    F: Fn(VaultItem) -> Result<VaultItem, Error>,
+{
+    update_stored_entry(session, id, |current| {
+        Ok(StoredItem::new(change(current.item)?, current.ext))
+    })
+}
+
+pub fn update_stored_entry<F>(session: &VaultSession, id: &str, change: F) -> Result<(), Error>
+where
+    F: Fn(StoredItem) -> Result<StoredItem, Error>,
+{
+    retry_on_conflict(|| {
+        let entry = session.fetch_item(id)?;
+        let item = SecureEntry::decrypt_stored(session.encryption_key(), &entry.value)?;
+        let value = change(item)
+            .and_then(|updated| SecureEntry::encrypt(session.encryption_key(), &updated))
+            .and_then(|stored| stored.serialize())?;
+        session.store_item(&VaultEntry { value, ..entry })?;
+        Ok::<_, Error>(())
+    })
+}
+
+pub fn delete_entry(session: &VaultSession, id: &str, revision: u64) -> Result<(), Error> {
+    session.delete_item(id, revision)?;
+    Ok(())
+}
+
+pub fn quarantine_entry(session: &VaultSession, id: &str, revision: u64) -> Result<(), Error> {
+    session.quarantine_item(id, revision)?;
+    Ok(())
+}
+
+pub fn merge_changes(
+    original: &StoredItem,
+    edited: &StoredItem,
+    current: StoredItem,
+) -> Result<StoredItem, Error> {
+    let encode = |item: &StoredItem| {
+        serde_json::to_value(item).map_err(|err| Error::from(format!("Encode error: {}", err)))
+    };
+    let original = encode(original)?;
+    let edited = encode(edited)?;
+    let mut merged = encode(&current)?;
+    if original.get("type") != merged.get("type") {
+        return Err(Error::Conflict(
+            "The item type was changed on another machine".to_string(),
+        ));
     }
 
-    pub fn decrypt(key: &Key<Aes256Gcm>, value: &str) -> Result<VaultItem, String> {
-        let stored: SecureEntry =
-            serde_json::from_str(value).map_err(|err| format!("Decode error: {}", err))?;
-        let plaintext = decrypt_data(key, &stored.data);
-        serde_json::from_str(&plaintext).map_err(|err| format!("Decode error: {}", err))
+    merge_fields(&original, &edited, &mut merged);
+    if let Some(merged) = merged.as_object_mut() {
+        let mut ext = merged
+            .remove("ext")
+            .unwrap_or_else(|| serde_json::Value::Object(Default::default()));
+        let empty = serde_json::Value::Object(Default::default());
+        merge_fields(
+            original.get("ext").unwrap_or(&empty),
+            edited.get("ext").unwrap_or(&empty),
+            &mut ext,
+        );
+        merged.insert("ext".to_string(), ext);
     }
+    serde_json::from_value(merged).map_err(decode_error)
+}
 
-    pub fn serialize(&self) -> Result<String, String> {
-        serde_json::to_string(self).map_err(|err| format!("Encode error: {}", err))
+fn merge_fields(
+    original: &serde_json::Value,
+    edited: &serde_json::Value,
+    merged: &mut serde_json::Value,
+) {
+    let (Some(original), Some(edited), Some(merged)) = (
+        original.as_object(),
+        edited.as_object(),
+        merged.as_object_mut(),
+    ) else {
+        return;
+    };
+    for (field, value) in edited {
+        if field != "ext" && original.get(field) != Some(value) {
+            merged.insert(field.clone(), value.clone());
+        }
+    }
+    for field in original.keys() {
+        if field != "ext" && !edited.contains_key(field) {
+            merged.remove(field);
+        }
     }
 }
 
-fn timestamp_now() -> u64 {
-    SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_secs()
+pub fn retry_on_conflict<T, E, F>(mut attempt: F) -> Result<T, Error>
+where
+    E: Into<Error>,
+    F: FnMut() -> Result<T, E>,
+{
+    for _ in 1..MAX_CONFLICT_ATTEMPTS {
+        match attempt().map_err(Into::into) {
+            Err(Error::Conflict(message)) => {
+                eprintln!("{}", format!("{}. Retrying...", message).yellow());
+            }
+            other => return other,
+        }
+    }
+    attempt().map_err(Into::into)
 }
 
-pub fn load_vault(session: &RemoteSession) -> Result<Vec<VaultEntry>, String> {
-    session.fetch_vault().map_err(|err| err.to_string())
+fn decode_error(err: serde_json::Error) -> Error {
+    Error::Decrypt(format!("Decode error: {}", err))
 }
 
-pub fn save_vault(session: &RemoteSession, vault: &[VaultEntry]) -> Result<(), String> {
-    session.save_vault(vault).map_err(|err| err.to_string())
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn login(username: &str, password: &str, ext: ItemExtension) -> StoredItem {
+        StoredItem::new(
+            VaultItem::Password {
+                name: "GitHub".to_string(),
+                website: "https://github.com".to_string(),
+                email: String::new(),
+                username: username.to_string(),
+                password: password.to_string(),
+                totp: String::new(),
+            },
+            ext,
+        )
+    }
+
+    fn field(item: &StoredItem, name: &str) -> String {
+        item.item.field(name).unwrap()
+    }
+
+    #[test]
+    fn merge_keeps_changes_to_different_fields() {
+        let original = login("me", "old", ItemExtension::default());
+        let edited = login("me", "new", ItemExtension::default());
+        let current = login("other", "old", ItemExtension::default());
+
+        let merged = merge_changes(&original, &edited, current).unwrap();
+        assert_eq!(field(&merged, "username"), "other");
+        assert_eq!(field(&merged, "password"), "new");
+    }
+
+    #[test]
+    fn merge_prefers_the_local_edit_of_the_same_field() {
+        let original = login("me", "old", ItemExtension::default());
+        let edited = login("me", "mine", ItemExtension::default());
+        let current = login("me", "theirs", ItemExtension::default());
+
+        let merged = merge_changes(&original, &edited, current).unwrap();
+        assert_eq!(field(&merged, "password"), "mine");
+    }
+
+    #[test]
+    fn merge_combines_extension_changes() {
+        let original = login("me", "pw", ItemExtension::default());
+        let mut tagged = ItemExtension::default();
+        tagged.add_tag("work");
+        let edited = login("me", "pw", tagged);
+        let mut filed = ItemExtension::default();
+        filed.set_folder("Dev");
+        let current = login("me", "pw", filed);
+
+        let merged = merge_changes(&original, &edited, current).unwrap();
+        assert!(merged.ext.has_tag("work"));
+        assert_eq!(merged.ext.folder, "Dev");
+    }
+
+    #[test]
+    fn merge_refuses_a_changed_item_type() {
+        let original = login("me", "pw", ItemExtension::default());
+        let current = StoredItem::new(
+            VaultItem::SecureNote {
+                title: "GitHub".to_string(),
+                note: "moved".to_string(),
+            },
+            ItemExtension::default(),
+        );
+
+        let result = merge_changes(&original, &original.clone(), current);
+        assert!(matches!(result, Err(Error::Conflict(_))));
+    }
+
+    #[test]
+    fn retries_conflicts_until_the_attempts_run_out() {
+        let mut attempts = 0;
+        let result = retry_on_conflict(|| {
+            attempts += 1;
+            if attempts < MAX_CONFLICT_ATTEMPTS {
+                Err(Error::Conflict("stale".to_string()))
+            } else {
+                Ok(attempts)
+            }
+        });
+        assert_eq!(result.unwrap(), MAX_CONFLICT_ATTEMPTS);
+
+        let mut attempts = 0;
+        let result: Result<(), Error> = retry_on_conflict(|| {
+            attempts += 1;
+            Err(Error::Conflict("stale".to_string()))
+        });
+        assert!(matches!(result, Err(Error::Conflict(_))));
+        assert_eq!(attempts, MAX_CONFLICT_ATTEMPTS);
+    }
+
+    #[test]
+    fn does_not_retry_other_errors() {
+        let mut attempts = 0;
+        let result: Result<(), Error> = retry_on_conflict(|| {
+            attempts += 1;
+            Err(Error::NotFound("gone".to_string()))
+        });
+        assert!(matches!(result, Err(Error::NotFound(_))));
+        assert_eq!(attempts, 1);
+    }
+
+    #[test]
+    fn parses_references() {
+        assert_eq!(
+            parse_reference("x://GitHub/password").unwrap(),
+            ("GitHub", "password")
+        );
+        assert_eq!(
+            parse_reference(" Work/GitHub / username ").unwrap(),
+            ("Work/GitHub", "username")
+        );
+        for invalid in ["x://GitHub", "x:///password", "x://GitHub/", ""] {
+            assert!(
+                matches!(parse_reference(invalid), Err(Error::Usage(_))),
+                "{}",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn pads_entries_to_whole_blocks() {
+        assert_eq!(padded(String::new()).len(), PADDING_BLOCK);
+        assert_eq!(padded("x".repeat(PADDING_BLOCK)).len(), PADDING_BLOCK);
+        assert_eq!(
+            padded("x".repeat(PADDING_BLOCK + 1)).len(),
+            2 * PADDING_BLOCK
+        );
+
+        let key = Key::<Aes256Gcm>::from([7u8; 32]);
+        let item = login("me", "pw", ItemExtension::default());
+        let value = SecureEntry::encrypt(&key, &item)
+            .and_then(|stored| stored.serialize())
+            .unwrap();
+        let decrypted = SecureEntry::decrypt_stored(&key, &value).unwrap();
+        assert_eq!(field(&decrypted, "password"), "pw");
+    }
 }